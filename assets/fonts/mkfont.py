@@ -0,0 +1,278 @@
+#!/usr/bin/env python3
+# Rasterizes a TrueType font into a BMFont atlas (text format), see LICENSE.
+#
+# Usage: mkfont.py <font.ttf> <output path without extension> <em size in px> <face name>
+import struct, zlib, sys, math
+
+TTF = sys.argv[1]
+OUT = sys.argv[2]          # path without extension
+PX = int(sys.argv[3])      # em size in pixels
+FACE = sys.argv[4]
+SS = 4                     # supersampling per axis
+
+data = open(TTF, 'rb').read()
+
+def u16(o): return struct.unpack('>H', data[o:o+2])[0]
+def i16(o): return struct.unpack('>h', data[o:o+2])[0]
+def u32(o): return struct.unpack('>I', data[o:o+4])[0]
+
+tables = {}
+for i in range(u16(4)):
+    rec = 12 + 16 * i
+    tables[data[rec:rec+4].decode('latin1')] = u32(rec + 8)
+
+head = tables['head']
+units = u16(head + 18)
+loca_long = i16(head + 50) == 1
+hhea = tables['hhea']
+ascent, descent = i16(hhea + 4), i16(hhea + 6)
+nhm = u16(hhea + 34)
+num_glyphs = u16(tables['maxp'] + 4)
+
+def advance(g):
+    return u16(tables['hmtx'] + 4 * min(g, nhm - 1))
+
+def loca(g):
+    if loca_long:
+        return u32(tables['loca'] + 4 * g)
+    return u16(tables['loca'] + 2 * g) * 2
+
+# cmap format 4
+cmap = tables['cmap']
+sub = None
+for i in range(u16(cmap + 2)):
+    rec = cmap + 4 + 8 * i
+    if u16(rec) == 3 and u16(rec + 2) == 1:
+        sub = cmap + u32(rec + 4)
+assert sub is not None and u16(sub) == 4
+segx2 = u16(sub + 6)
+ends = sub + 14
+starts = ends + segx2 + 2
+deltas = starts + segx2
+ranges = deltas + segx2
+
+def glyph_index(c):
+    for i in range(segx2 // 2):
+        end = u16(ends + 2 * i)
+        if c <= end:
+            start = u16(starts + 2 * i)
+            if c < start:
+                return 0
+            delta = u16(deltas + 2 * i)
+            ro = u16(ranges + 2 * i)
+            if ro == 0:
+                return (c + delta) & 0xFFFF
+            g = u16(ranges + 2 * i + ro + 2 * (c - start))
+            return (g + delta) & 0xFFFF if g else 0
+    return 0
+
+def contours(g, xform=(1, 0, 0, 1, 0, 0)):
+    start = tables['glyf'] + loca(g)
+    if loca(g + 1) == loca(g):
+        return []
+    n = i16(start)
+    out = []
+    if n >= 0:
+        end_pts = [u16(start + 10 + 2 * i) for i in range(n)]
+        npts = end_pts[-1] + 1 if n else 0
+        o = start + 10 + 2 * n
+        o += 2 + u16(o)
+        flags = []
+        while len(flags) < npts:
+            f = data[o]; o += 1
+            flags.append(f)
+            if f & 8:
+                r = data[o]; o += 1
+                flags.extend([f] * r)
+        xs, ys = [], []
+        v = 0
+        for f in flags:
+            if f & 2:
+                d = data[o]; o += 1
+                v += d if f & 16 else -d
+            elif not f & 16:
+                v += i16(o); o += 2
+            xs.append(v)
+        v = 0
+        for f in flags:
+            if f & 4:
+                d = data[o]; o += 1
+                v += d if f & 32 else -d
+            elif not f & 32:
+                v += i16(o); o += 2
+            ys.append(v)
+        a, b, c, d, e, f_ = xform
+        s = 0
+        for end in end_pts:
+            pts = []
+            for k in range(s, end + 1):
+                x, y = xs[k], ys[k]
+                pts.append((a * x + c * y + e, b * x + d * y + f_, flags[k] & 1))
+            out.append(pts)
+            s = end + 1
+    else:
+        o = start + 10
+        while True:
+            fl = u16(o); gi = u16(o + 2); o += 4
+            if fl & 1:
+                dx, dy = i16(o), i16(o + 2); o += 4
+            else:
+                dx, dy = struct.unpack('bb', data[o:o+2]); o += 2
+            sa, sb, sc, sd = 1, 0, 0, 1
+            if fl & 8:
+                sa = sd = i16(o) / 16384; o += 2
+            elif fl & 0x40:
+                sa, sd = i16(o) / 16384, i16(o + 2) / 16384; o += 4
+            elif fl & 0x80:
+                sa, sb, sc, sd = [i16(o + 2 * k) / 16384 for k in range(4)]; o += 8
+            a, b, c, d, e, f_ = xform
+            na = a * sa + c * sb; nb = b * sa + d * sb
+            nc = a * sc + c * sd; nd = b * sc + d * sd
+            ne = a * dx + c * dy + e; nf = b * dx + d * dy + f_
+            out.extend(contours(gi, (na, nb, nc, nd, ne, nf)))
+            if not fl & 0x20:
+                break
+    return out
+
+def flatten(pts):
+    # Quadratic B-spline with implied on-curve points -> polyline.
+    n = len(pts)
+    if n == 0:
+        return []
+    # Start on an on-curve point.
+    k0 = next((k for k in range(n) if pts[k][2]), None)
+    if k0 is None:
+        p0 = ((pts[0][0] + pts[1][0]) / 2, (pts[0][1] + pts[1][1]) / 2, 1)
+        pts = [p0] + pts
+        n += 1
+        k0 = 0
+    pts = pts[k0:] + pts[:k0]
+    poly = [(pts[0][0], pts[0][1])]
+    cur = pts[0]
+    ctrl = None
+    for k in range(1, n + 1):
+        p = pts[k % n]
+        if p[2]:
+            if ctrl is None:
+                poly.append((p[0], p[1]))
+            else:
+                for t in range(1, 9):
+                    t /= 8
+                    x = (1 - t) ** 2 * cur[0] + 2 * (1 - t) * t * ctrl[0] + t * t * p[0]
+                    y = (1 - t) ** 2 * cur[1] + 2 * (1 - t) * t * ctrl[1] + t * t * p[1]
+                    poly.append((x, y))
+                ctrl = None
+            cur = p
+        else:
+            if ctrl is not None:
+                mid = ((ctrl[0] + p[0]) / 2, (ctrl[1] + p[1]) / 2, 1)
+                for t in range(1, 9):
+                    t /= 8
+                    x = (1 - t) ** 2 * cur[0] + 2 * (1 - t) * t * ctrl[0] + t * t * mid[0]
+                    y = (1 - t) ** 2 * cur[1] + 2 * (1 - t) * t * ctrl[1] + t * t * mid[1]
+                    poly.append((x, y))
+                cur = mid
+            ctrl = p
+    return poly
+
+scale = PX / units
+base = int(round(ascent * scale))
+line_height = int(round((ascent - descent) * scale))
+
+def rasterize(g):
+    polys = [[(x * scale, y * scale) for (x, y) in flatten(c)] for c in contours(g)]
+    polys = [p for p in polys if len(p) > 2]
+    if not polys:
+        return None
+    xs = [x for p in polys for x, _ in p]
+    ys = [y for p in polys for _, y in p]
+    x0, x1 = int(math.floor(min(xs))), int(math.ceil(max(xs)))
+    y0, y1 = int(math.floor(min(ys))), int(math.ceil(max(ys)))
+    w, h = x1 - x0, y1 - y0
+    edges = []
+    for p in polys:
+        for i in range(len(p)):
+            a, b = p[i], p[(i + 1) % len(p)]
+            if a[1] != b[1]:
+                edges.append((a, b))
+    cov = [[0] * w for _ in range(h)]
+    for row in range(h):
+        for sy in range(SS):
+            # Bitmap rows go from top to bottom.
+            y = y1 - row - (sy + 0.5) / SS
+            xs_w = []
+            for a, b in edges:
+                if (a[1] <= y < b[1]) or (b[1] <= y < a[1]):
+                    t = (y - a[1]) / (b[1] - a[1])
+                    xs_w.append((a[0] + t * (b[0] - a[0]), 1 if b[1] > a[1] else -1))
+            xs_w.sort()
+            wind = 0
+            for i in range(len(xs_w) - 1):
+                wind += xs_w[i][1]
+                if wind != 0:
+                    l, r = xs_w[i][0] - x0, xs_w[i + 1][0] - x0
+                    for sx in range(w * SS):
+                        cx = (sx + 0.5) / SS
+                        if l <= cx < r:
+                            cov[row][sx // SS] += 1
+    alpha = [[min(255, c * 255 // (SS * SS)) for c in r] for r in cov]
+    return (x0, y1, w, h, alpha)
+
+chars = list(range(32, 127)) + list(range(160, 256))
+glyphs = []
+for c in chars:
+    g = glyph_index(c)
+    adv = int(round(advance(g) * scale))
+    r = rasterize(g) if g else None
+    glyphs.append((c, adv, r))
+
+# Pack into rows.
+W = 256
+x = y = 1
+row_h = 0
+placed = []
+for c, adv, r in glyphs:
+    if r is None:
+        placed.append((c, 0, 0, 0, 0, 0, 0, adv, None))
+        continue
+    x0, top, w, h, alpha = r
+    if x + w + 1 > W:
+        x = 1
+        y += row_h + 1
+        row_h = 0
+    placed.append((c, x, y, w, h, x0, base - top, adv, alpha))
+    x += w + 1
+    row_h = max(row_h, h)
+H = 1
+while H < y + row_h + 1:
+    H *= 2
+
+img = [[0] * W for _ in range(H)]
+for c, gx, gy, w, h, xo, yo, adv, alpha in placed:
+    if alpha is None:
+        continue
+    for j in range(h):
+        for i in range(w):
+            img[gy + j][gx + i] = alpha[j][i]
+
+# White glyphs, coverage in the alpha channel.
+raw = b''.join(b'\x00' + bytes(v for a in r for v in (255, 255, 255, a)) for r in img)
+def chunk(t, d):
+    c = struct.pack('>I', len(d)) + t + d
+    return c + struct.pack('>I', zlib.crc32(t + d) & 0xffffffff)
+png = b'\x89PNG\r\n\x1a\n' + chunk(b'IHDR', struct.pack('>IIBBBBB', W, H, 8, 6, 0, 0, 0)) \
+    + chunk(b'IDAT', zlib.compress(raw, 9)) + chunk(b'IEND', b'')
+open(OUT + '.png', 'wb').write(png)
+
+name = OUT.split('/')[-1]
+with open(OUT + '.fnt', 'w') as f:
+    f.write('info face="%s" size=%d bold=0 italic=0 charset="" unicode=1 stretchH=100 '
+            'smooth=1 aa=1 padding=0,0,0,0 spacing=1,1\n' % (FACE, PX))
+    f.write('common lineHeight=%d base=%d scaleW=%d scaleH=%d pages=1 packed=0\n'
+            % (line_height, base, W, H))
+    f.write('page id=0 file="%s.png"\n' % name)
+    f.write('chars count=%d\n' % len(placed))
+    for c, gx, gy, w, h, xo, yo, adv, _ in placed:
+        f.write('char id=%d x=%d y=%d width=%d height=%d xoffset=%d yoffset=%d '
+                'xadvance=%d page=0 chnl=15\n' % (c, gx, gy, w, h, xo, yo, adv))
+    f.write('kernings count=0\n')
@@ -1,14 +1,24 @@
-
 use super::clock_ticks;
 
 use std::time::Duration;
 use std::thread;
 use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
 use std::sync::mpsc::channel;
-use loader::Loader;
+use loader::{LoadError, Loader, NextResourceInfo};
+
+// Sent by the thread doing the loading.
+enum Message<R> {
+    Next(NextResourceInfo),
+    Loaded(R),
+    Failed(LoadError),
+}
 
+/// Run a `Loader` in another thread and
+/// keep track of its progress.
 pub struct DeferredLoader<R> {
-    rx_resource: Receiver<R>,
+    rx_message: Receiver<Message<R>>,
+    current: NextResourceInfo,
 }
 
 impl<R> DeferredLoader<R>
@@ -18,19 +28,61 @@ impl<R> DeferredLoader<R>
     pub fn new(mut loader: Box<Loader<Resources=R>>) -> DeferredLoader<R> {
 
         let (tx, rx) = channel();
+        let current = loader.next_resource_info().unwrap_or(NextResourceInfo {
+            name: String::new(),
+            progress: 1.0,
+        });
 
         thread::spawn(move|| {
-            let r = loader.load_resources();
-            tx.send(r).unwrap();
+            while let Some(info) = loader.next_resource_info() {
+                // Nobody is waiting for the resources anymore.
+                if tx.send(Message::Next(info)).is_err() {
+                    return;
+                }
+                if let Err(e) = loader.load_next_resource() {
+                    let _ = tx.send(Message::Failed(e));
+                    return;
+                }
+            }
+            let _ = tx.send(Message::Loaded(loader.take_resources()));
         });
 
         DeferredLoader {
-            rx_resource: rx,
+            rx_message: rx,
+            current: current,
         }
     }
 
-    pub fn while_waiting<F>(self, mut actions: F) -> R
-        where F: FnMut(u64)
+    /// What is being loaded.
+    pub fn current(&self) -> &NextResourceInfo {
+        &self.current
+    }
+
+    /// Returns the resources once they are loaded, or why they
+    /// couldn't be, without blocking. It must not be called again after that.
+    pub fn poll(&mut self) -> Option<Result<R, LoadError>> {
+        loop {
+            match self.rx_message.try_recv() {
+                Ok(Message::Next(info)) => self.current = info,
+                Ok(Message::Loaded(r)) => {
+                    debug!("Deferred Finished");
+                    return Some(Ok(r));
+                }
+                Ok(Message::Failed(e)) => return Some(Err(e)),
+                Err(TryRecvError::Empty) => return None,
+                // The loader panicked.
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err(LoadError::new(&self.current.name, "the loading thread has stopped")));
+                }
+            }
+        }
+    }
+
+    /// Block until the resources are loaded, `actions` is
+    /// called meanwhile with the time elapsed since its last
+    /// call and what is being loaded.
+    pub fn while_waiting<F>(mut self, mut actions: F) -> Result<R, LoadError>
+        where F: FnMut(u64, &NextResourceInfo)
     {
         debug!("Deferred Started");
 
@@ -39,15 +91,81 @@ impl<R> DeferredLoader<R>
         loop {
             let now = clock_ticks::precise_time_ns();
 
-            if let Ok(r) = self.rx_resource.try_recv() {
-                debug!("Deferred Finished");
+            if let Some(r) = self.poll() {
                 return r;
             }
 
-            actions(now - previous_clock);
+            actions(now - previous_clock, &self.current);
 
             previous_clock = now;
             thread::sleep(Duration::from_millis(8));
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use loader::{LoadError, Loader, NextResourceInfo};
+    use super::DeferredLoader;
+
+    // Counts up to `steps`, fails at `fail_at` if given.
+    struct Counter {
+        steps: u32,
+        count: u32,
+        fail_at: Option<u32>,
+    }
+
+    impl Loader for Counter {
+        type Resources = u32;
+
+        fn next_resource_info(&self) -> Option<NextResourceInfo> {
+            if self.count == self.steps {
+                return None;
+            }
+            Some(NextResourceInfo {
+                name: format!("step {}", self.count),
+                progress: self.count as f32 / self.steps as f32,
+            })
+        }
+
+        fn load_next_resource(&mut self) -> Result<(), LoadError> {
+            if self.fail_at == Some(self.count) {
+                return Err(LoadError::new(&format!("step {}", self.count), "failed"));
+            }
+            self.count += 1;
+            Ok(())
+        }
+
+        fn take_resources(&mut self) -> u32 {
+            self.count
+        }
+    }
+
+    #[test]
+    fn load_at_once() {
+        let mut counter = Counter { steps: 3, count: 0, fail_at: None };
+        assert_eq!(counter.load_resources(), Ok(3));
+    }
+
+    #[test]
+    fn progress() {
+        let loader = DeferredLoader::new(Box::new(Counter { steps: 4, count: 0, fail_at: None }));
+        assert_eq!(loader.current().name, "step 0");
+
+        let mut progress = Vec::new();
+        let count = loader.while_waiting(|_, info| progress.push(info.progress));
+        assert_eq!(count, Ok(4));
+        // The steps may be quicker than the waiting loop.
+        assert!(progress.windows(2).all(|w| w[0] <= w[1]));
+        assert!(progress.iter().all(|&p| p < 1.0));
+    }
+
+    #[test]
+    fn failure() {
+        let loader = DeferredLoader::new(Box::new(Counter { steps: 4, count: 0, fail_at: Some(2) }));
+        let error = loader.while_waiting(|_, _| ()).unwrap_err();
+        assert_eq!(error, LoadError::new("step 2", "failed"));
+        assert_eq!(error.to_string(), "Couldn't load `step 2`: failed");
+    }
+}
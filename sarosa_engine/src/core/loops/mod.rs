@@ -2,29 +2,123 @@ extern crate clock_ticks;
 
 use std::thread;
 use std::time::Duration;
+use glium::{Frame, Surface};
 
 use events::EventSystem;
 use profiler::Profiler;
-use super::GameInstance;
+use rendering::MenuRenderer;
+use rendering::font::{Font, DEFAULT_FONT_PATH};
+use ui::Document;
 use Window;
 use Server;
+use Settings;
 
 pub mod deferred;
 
+/// What the loop does with the state that was just updated.
+///
+/// The transitions apply where the state is in the stack:
+/// the states above it are dropped first.
 pub enum LoopState {
     Continue,
+    /// Show a state above this one.
+    Push(Box<GameState>),
+    /// Go back to the state below.
+    Pop,
+    /// Show another state instead of this one.
+    Replace(Box<GameState>),
+    /// Leave the game.
     Break,
 }
 
-pub struct GameLoop;
+/// A step of the game, such as a menu or the world.
+///
+/// Only the top of the stack is drawn and gets the input,
+/// unless it is an overlay, but all the states are updated.
+pub trait GameState {
+
+    /// Called when the state gets on top of the stack,
+    /// when it is shown or the state above it is popped.
+    fn enter(&mut self, _: &mut Context) {}
+
+    /// Handle the input of the frame.
+    fn event_update(&mut self, ctx: &mut Context, event_sys: &mut EventSystem) -> LoopState;
+
+    /// Called once per frame with the time elapsed, from the bottom of the stack.
+    fn update(&mut self, _: &mut Context, _: u64) -> LoopState {
+        LoopState::Continue
+    }
+
+    /// Draw the state over the ones below it, if they are shown.
+    fn render(&mut self, ctx: &mut Context, target: &mut Frame);
+
+    /// Whether the state below is still drawn and gets the input
+    /// once this one is done with it, such as an in-game menu.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// Whether the input goes to the menus, see `EventSystem::set_menu_open`.
+    fn is_menu(&self) -> bool {
+        false
+    }
+}
+
+/// What the states share: the window, the connection
+/// to the server and the UI of the menus.
+pub struct Context {
+    pub window: Window,
+    pub server: Server,
+    pub settings: Settings,
+    /// All the menus use it, each one shows its view when entered.
+    pub document: Document,
+    menu_renderer: MenuRenderer,
+}
+
+impl Context {
+
+    pub fn new(window: Window, server: Server, settings: Settings) -> Context {
+
+        // The menus are measured with the font they are drawn with.
+        let font = Font::load(DEFAULT_FONT_PATH).unwrap_or_else(|e| {
+            panic!("Couldn't load font `{}`: {}", DEFAULT_FONT_PATH, e)
+        });
+        let document = Document::load(window.dimensions(), Box::new(font)).unwrap_or_else(|e| {
+            panic!("Couldn't load the UI: {}", e)
+        });
+
+        Context {
+            menu_renderer: MenuRenderer::new(&window),
+            window: window,
+            server: server,
+            settings: settings,
+            document: document,
+        }
+    }
+
+    /// Draw the view shown by the document.
+    pub fn render_menu(&mut self, target: &mut Frame) {
+        self.menu_renderer.update(&mut self.document);
+        self.menu_renderer.render(target, &self.window);
+    }
+}
+
+pub struct GameLoop {
+    context: Context,
+    states: Vec<Box<GameState>>,
+}
 
 impl GameLoop {
 
-    pub fn new() -> GameLoop {
-        GameLoop
+    pub fn new(window: Window, server: Server, settings: Settings) -> GameLoop {
+        GameLoop {
+            context: Context::new(window, server, settings),
+            states: Vec::new(),
+        }
     }
 
-    pub fn run_loop(&mut self, window: &mut Window, instance: &mut GameInstance, server: &mut Server)
+    /// Run the game from `first` until there are no more states.
+    pub fn run_loop(&mut self, first: Box<GameState>)
     {
         debug!("Game has started");
 
@@ -33,58 +127,47 @@ impl GameLoop {
         let mut previous_clock = clock_ticks::precise_time_ns();
         let mut event_sys = EventSystem::default();
 
-        loop {
+        self.states.push(first);
+        self.enter_top();
+
+        while !self.states.is_empty() {
             p.start_frame();
 
             // Lookup all events:
             p.enter("Poll events");
-            window.poll_events(&mut event_sys);
-            p.leave();
-
-            // Push them to the server
-            p.enter("Push event to server");
-            server.event_update(&event_sys);
+            let menu_open = self.states.last().map_or(false, |s| s.is_menu());
+            event_sys.set_menu_open(menu_open);
+            self.context.window.poll_events(&mut event_sys);
+            if let Some((width, height)) = event_sys.resized() {
+                self.context.document.resize(width, height);
+            }
             p.leave();
 
-            // Then the game instance
-            p.enter("Local event update");
-            match instance.event_update(&event_sys) {
-                LoopState::Break => break,
-                LoopState::Continue => ()
-            };
+            // Give them to the states
+            p.enter("Event update");
+            self.event_update(&mut event_sys);
             p.leave();
 
             // Mark all events as consumed
             event_sys.clear();
 
-            // Remote update:
-            p.enter("Server event update");
-            match server.remote_update(instance) {
-                Err(_) => break,
-                _ => ()
-            }
-            p.leave();
-
-            // Frame update
-            p.enter("Frame update");
-            instance.frame_update(window);
-            p.leave();
-
-            // Fixed update
-            p.enter("Fixed update");
+            // Update all the states
+            p.enter("Update");
             let now = clock_ticks::precise_time_ns();
             lag += now - previous_clock;
             previous_clock = now;
 
             const FIXED_TIME_STAMP: u64 = 16666667;
-            // while lag >= FIXED_TIME_STAMP {
-            //    lag -= FIXED_TIME_STAMP;
-            // }
+            // Only whole steps are simulated, the rest waits for the next frame.
             let fixed_update = (lag / FIXED_TIME_STAMP) * FIXED_TIME_STAMP;
-            instance.fixed_update(lag);
+            self.update(fixed_update);
             lag -= fixed_update;
             p.leave();
 
+            // Frame update
+            p.enter("Render");
+            self.render();
+            p.leave();
 
             thread::sleep(Duration::from_millis(((FIXED_TIME_STAMP - lag) / 1000000) as u64));
             p.end_frame();
@@ -94,4 +177,72 @@ impl GameLoop {
 
         debug!("Game has finished");
     }
+
+    // From the top, and below as long as the states are overlays.
+    fn event_update(&mut self, event_sys: &mut EventSystem) {
+        let mut index = self.states.len();
+        while index > 0 {
+            index -= 1;
+            let state = self.states[index].event_update(&mut self.context, event_sys);
+            let overlay = self.states[index].is_overlay();
+            if self.transition(index, state) || !overlay {
+                break;
+            }
+        }
+    }
+
+    fn update(&mut self, elapsed: u64) {
+        let mut index = 0;
+        while index < self.states.len() {
+            let state = self.states[index].update(&mut self.context, elapsed);
+            if self.transition(index, state) {
+                break;
+            }
+            index += 1;
+        }
+    }
+
+    // From the lowest state shown to the top.
+    fn render(&mut self) {
+        if self.states.is_empty() {
+            return;
+        }
+        let mut first = self.states.len() - 1;
+        while first > 0 && self.states[first].is_overlay() {
+            first -= 1;
+        }
+
+        let mut target = self.context.window.draw();
+        target.clear_color(0.0, 0.0, 0.0, 1.0);
+        for state in &mut self.states[first..] {
+            state.render(&mut self.context, &mut target);
+        }
+        target.finish().unwrap();
+    }
+
+    // Apply what the state at `index` asked for,
+    // returns whether the stack has changed.
+    fn transition(&mut self, index: usize, state: LoopState) -> bool {
+        match state {
+            LoopState::Continue => return false,
+            LoopState::Push(next) => {
+                self.states.truncate(index + 1);
+                self.states.push(next);
+            }
+            LoopState::Pop => self.states.truncate(index),
+            LoopState::Replace(next) => {
+                self.states.truncate(index);
+                self.states.push(next);
+            }
+            LoopState::Break => self.states.clear(),
+        }
+        self.enter_top();
+        true
+    }
+
+    fn enter_top(&mut self) {
+        if let Some(top) = self.states.last_mut() {
+            top.enter(&mut self.context);
+        }
+    }
 }
@@ -1,6 +1,8 @@
 use cgmath::Vector2;
 use cgmath::Zero;
 
+use glium::Frame;
+
 use events::{
     EventSystem,
     UserEventType,
@@ -9,25 +11,34 @@ use events::{
 use models::game::GameData;
 use models::player::{
     Player,
-    PlayerId
+    PlayerId,
+    PlayerInfo
 };
 use animation::TextureId;
 use animation::AnimationManager;
 use rendering::GameRenderer;
+use rendering::save_screenshot;
 use rendering::scene::WorldScene;
 use Window;
 use Settings;
 
-pub use self::loops::GameLoop;
-pub use self::loops::LoopState;
+pub use self::loops::{Context, GameLoop, GameState, LoopState};
+pub use self::states::{Connecting, Disconnected, MainMenu, Paused, Reconnecting};
+pub use self::resources::{GameLoader, GameResources};
 pub mod loops;
+mod states;
+mod resources;
+
+const SCREENSHOTS_DIR: &'static str = "./screenshots";
 
 pub struct GameInstance {
     renderer: GameRenderer,
     world_scene: WorldScene,
     game_data: GameData,
     anim_manager: AnimationManager,
-    // ui_router: oil::Router,
+    screenshot_requested: bool,
+    // The world doesn't move while the server is reconnected to.
+    frozen: bool,
 }
 
 pub struct GameDataRefMut<'a> {
@@ -39,11 +50,19 @@ pub struct GameDataRefMut<'a> {
 
 impl<'a> GameDataRefMut<'a> {
 
-    pub fn add_player(&mut self, id: PlayerId, initial_pos: Vector2<f32>, tex_id: TextureId) {
+    pub fn add_player(
+        &mut self,
+        id: PlayerId,
+        initial_pos: Vector2<f32>,
+        tex_id: TextureId,
+        info: PlayerInfo)
+    {
         debug!("Player id: {:?}", tex_id);
-        let player = Player::new(initial_pos, Vector2::zero(), tex_id, self.anim_manager);
-        let is_new = self.game_data.add_player(id, player);
-        self.should_require_gpu_init |= is_new;
+        let mut player = Player::new(initial_pos, Vector2::zero(), tex_id, self.anim_manager);
+        player.info = info;
+        self.game_data.add_player(id, player);
+        // The name plate may have changed even if the player was known.
+        self.should_require_gpu_init = true;
     }
 
     pub fn update_player(&mut self, id: PlayerId, pos: Vector2<f32>, speed: Vector2<f32>) {
@@ -55,6 +74,16 @@ impl<'a> GameDataRefMut<'a> {
         self.game_data.remove_player(id);
         self.should_require_gpu_init = true;
     }
+
+    pub fn clear_players(&mut self) {
+        self.game_data.clear_players();
+        self.should_require_gpu_init = true;
+    }
+
+    pub fn add_chat_message(&mut self, id: PlayerId, message: String) {
+        self.game_data.say(id, message);
+        self.should_require_gpu_init = true;
+    }
 }
 
 impl<'a> Drop for GameDataRefMut<'a> {
@@ -67,7 +96,7 @@ impl<'a> Drop for GameDataRefMut<'a> {
 
 impl GameInstance {
 
-    pub fn new(window: &Window, _: Settings) -> GameInstance {
+    pub fn new(window: &Window, settings: Settings, resources: GameResources) -> GameInstance {
 
         let anim_manager = AnimationManager::new();
         // TODO(Nemikolh):
@@ -83,8 +112,11 @@ impl GameInstance {
         //  use a local cache or something to know what is the
         //  correct skin id.
         //
-        let game_data = GameData::new(TextureId(0), &anim_manager);
-        let mut renderer = GameRenderer::new(window);
+        let mut game_data = GameData::new(TextureId(0), &anim_manager, resources.map);
+        game_data.set_render_delay(settings.network().render_delay());
+        // A replay shows what was recorded, whatever the keys pressed.
+        game_data.set_prediction(settings.network().replay_path().is_none());
+        let mut renderer = GameRenderer::new(window, resources.textures);
         renderer.initialize_gpu_mem(&game_data, window);
 
         GameInstance {
@@ -92,6 +124,8 @@ impl GameInstance {
             world_scene: WorldScene::new(),
             game_data: game_data,
             anim_manager: anim_manager,
+            screenshot_requested: false,
+            frozen: false,
         }
     }
 
@@ -104,28 +138,83 @@ impl GameInstance {
         }
     }
 
-    fn event_update(&mut self, event_sys: &EventSystem) -> LoopState {
+    fn fixed_update(&mut self, fixed_timestamp: u64) {
+        self.game_data.fixed_update(&self.anim_manager, fixed_timestamp);
+        // The local player moves between server updates.
+        self.renderer.update_gpu_mem(&self.game_data);
+    }
+}
+
+/// The world, once connected.
+impl GameState for GameInstance {
+
+    fn event_update(&mut self, ctx: &mut Context, event_sys: &mut EventSystem) -> LoopState {
+        // Push them to the server
+        ctx.server.event_update(event_sys);
+
+        // Show the answers to what was just said.
+        if !event_sys.chat_messages().is_empty() {
+            self.game_data.chat_mut().scroll_to_bottom();
+        }
+        let mut paused = false;
         for &e in event_sys.iter() {
+            match e.kind {
+                UserEventType::CmdUp | UserEventType::CmdDown |
+                UserEventType::CmdLeft | UserEventType::CmdRight => {
+                    self.game_data.push_command(e.kind, e.state == UserEventState::Start);
+                }
+                _ => (),
+            }
             if e.state == UserEventState::Start {
                 match e.kind {
                     UserEventType::Quit => return LoopState::Break,
+                    UserEventType::Menu => paused = true,
                     UserEventType::ZoomIn => self.world_scene.camera().zoom_in(),
                     UserEventType::ZoomOut => self.world_scene.camera().zoom_out(),
+                    UserEventType::Screenshot => self.screenshot_requested = true,
+                    UserEventType::ChatScrollUp => self.game_data.chat_mut().scroll_up(1),
+                    UserEventType::ChatScrollDown => self.game_data.chat_mut().scroll_down(1),
                     _ => (),
                 }
             }
         }
-        LoopState::Continue
+        if paused {
+            LoopState::Push(Box::new(Paused::new()))
+        } else {
+            LoopState::Continue
+        }
     }
 
-    fn frame_update(&mut self, window: &mut Window) {
-        self.world_scene.update_world(&self.game_data);
-        //self.renderer.update_gpu_mem(&self.game_data);
-        self.renderer.render(&self.world_scene, window);
+    fn update(&mut self, ctx: &mut Context, elapsed: u64) -> LoopState {
+        if let Err(e) = ctx.server.remote_update(self) {
+            info!("Left the game: {}", e);
+            ctx.server.disconnect();
+            return LoopState::Replace(Box::new(Disconnected::new(&e)));
+        }
+        if ctx.server.is_reconnecting() {
+            if !self.frozen {
+                self.frozen = true;
+                return LoopState::Push(Box::new(Reconnecting::new()));
+            }
+            return LoopState::Continue;
+        }
+        self.frozen = false;
+        self.fixed_update(elapsed);
+        LoopState::Continue
     }
 
-    fn fixed_update(&mut self, fixed_timestamp: u64) {
-        self.game_data.fixed_update(&self.anim_manager, fixed_timestamp);
-    }
+    fn render(&mut self, ctx: &mut Context, target: &mut Frame) {
+        // The last frame is still the one displayed.
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            match save_screenshot(&ctx.window.capture_frame(), SCREENSHOTS_DIR) {
+                Ok(path) => info!("Screenshot saved to `{}`", path.display()),
+                Err(e) => error!("Couldn't save screenshot: {}", e),
+            }
+        }
 
+        self.world_scene.update_world(&self.game_data);
+        self.renderer.update_hud(&self.game_data, &ctx.window);
+        self.renderer.render(target, &self.world_scene, &ctx.window);
+    }
 }
@@ -0,0 +1,81 @@
+use std::mem;
+use image::{self, RgbaImage};
+
+use loader::{LoadError, Loader, NextResourceInfo};
+use models::map::GameMap;
+use rendering::{Textures, PLAYER_SKINS};
+
+/// What the world needs before being shown.
+pub struct GameResources {
+    pub map: GameMap,
+    pub textures: Textures,
+}
+
+/// Loads the map, then its tileset and the skins of the players.
+pub struct GameLoader {
+    map_path: String,
+    map: Option<GameMap>,
+    tileset: Option<RgbaImage>,
+    skins: Vec<RgbaImage>,
+}
+
+impl GameLoader {
+
+    pub fn new(map_path: &str) -> GameLoader {
+        GameLoader {
+            map_path: map_path.to_string(),
+            map: None,
+            tileset: None,
+            skins: Vec::new(),
+        }
+    }
+}
+
+impl Loader for GameLoader {
+
+    type Resources = GameResources;
+
+    fn next_resource_info(&self) -> Option<NextResourceInfo> {
+        let steps = 2 + PLAYER_SKINS.len();
+        let (done, name) = match (&self.map, &self.tileset) {
+            (&None, _) => (0, self.map_path.clone()),
+            (&Some(ref map), &None) => (1, map.tileset().path().to_string()),
+            _ if self.skins.len() < PLAYER_SKINS.len() => {
+                (2 + self.skins.len(), PLAYER_SKINS[self.skins.len()].to_string())
+            }
+            _ => return None,
+        };
+        Some(NextResourceInfo {
+            name: name,
+            progress: done as f32 / steps as f32,
+        })
+    }
+
+    fn load_next_resource(&mut self) -> Result<(), LoadError> {
+        if self.map.is_none() {
+            let map = try!(GameMap::load(&self.map_path).map_err(|e| LoadError::new(&self.map_path, e)));
+            self.map = Some(map);
+        } else if self.tileset.is_none() {
+            let path = self.map.as_ref().unwrap().tileset().path().to_string();
+            self.tileset = Some(try!(load_image(&path)));
+        } else if self.skins.len() < PLAYER_SKINS.len() {
+            let image = try!(load_image(PLAYER_SKINS[self.skins.len()]));
+            self.skins.push(image);
+        }
+        Ok(())
+    }
+
+    fn take_resources(&mut self) -> GameResources {
+        GameResources {
+            map: self.map.take().expect("The map isn't loaded"),
+            textures: Textures {
+                tileset: self.tileset.take().expect("The tileset isn't loaded"),
+                skins: mem::replace(&mut self.skins, Vec::new()),
+            },
+        }
+    }
+}
+
+fn load_image(path: &str) -> Result<RgbaImage, LoadError> {
+    image::open(path).map(|image| image.to_rgba()).map_err(|e| LoadError::new(path, e))
+}
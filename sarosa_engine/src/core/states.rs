@@ -0,0 +1,432 @@
+use glium::Frame;
+
+use events::{
+    EventSystem,
+    PushEvent,
+    UserEventType,
+    UserEventState
+};
+use loader::{DeferredLoader, LoadError};
+use models::map::DEFAULT_MAP_PATH;
+use net::{ConnectionStatus, ServerError};
+use ui::{Action, UiEvent, MAIN_VIEW};
+use super::GameInstance;
+use super::loops::{Context, GameState, LoopState};
+use super::resources::{GameLoader, GameResources};
+
+// Views of `assets/ui/markup`.
+const CONNECT_VIEW: &'static str = "connect";
+const SETTINGS_VIEW: &'static str = "settings";
+const CONNECTING_VIEW: &'static str = "connecting";
+const LOADING_VIEW: &'static str = "loading";
+const DISCONNECTED_VIEW: &'static str = "disconnected";
+const PAUSE_VIEW: &'static str = "pause";
+const RECONNECTING_VIEW: &'static str = "reconnecting";
+// Values of their line inputs, and the message shown below them.
+const SERVER_ADDRESS: &'static str = "server.address";
+const RENDER_DELAY: &'static str = "settings.render_delay";
+const CHAT_MESSAGE: &'static str = "chat.msg";
+const STATUS: &'static str = "menu.status";
+const ERROR_TITLE: &'static str = "error.title";
+// What is being loaded, and how much of it in percents.
+const LOADING_NAME: &'static str = "loading.name";
+const LOADING_PROGRESS: &'static str = "loading.progress";
+
+/// The menu shown before joining a game: connect to a server,
+/// play offline, change the settings or quit.
+pub struct MainMenu;
+
+impl MainMenu {
+
+    pub fn new() -> MainMenu {
+        MainMenu
+    }
+
+    fn connect(&mut self, ctx: &mut Context) -> LoopState {
+        let address = ctx.document.value(SERVER_ADDRESS).unwrap_or("").trim().to_string();
+        if address.is_empty() {
+            set_status(ctx, "Enter the address of the server");
+            return LoopState::Continue;
+        }
+        let mut network = ctx.settings.network_mut();
+        network.set_addr(address);
+        network.set_offline_server(false);
+        LoopState::Replace(Box::new(Connecting::new()))
+    }
+
+    fn save_settings(&mut self, ctx: &mut Context) {
+        let render_delay = ctx.document.value(RENDER_DELAY).unwrap_or("").trim().parse::<u64>();
+        match render_delay {
+            Ok(ms) => {
+                ctx.settings.network_mut().set_render_delay(ms * 1_000_000);
+                set_status(ctx, "Settings saved");
+            }
+            Err(_) => set_status(ctx, "The render delay must be a number of milliseconds"),
+        }
+    }
+}
+
+impl GameState for MainMenu {
+
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.document.goto(MAIN_VIEW);
+        // Fill the line inputs with the current settings.
+        let (address, render_delay) = {
+            let network = ctx.settings.network();
+            (network.addr().to_string(), network.render_delay() / 1_000_000)
+        };
+        ctx.document.set_value(SERVER_ADDRESS, address);
+        ctx.document.set_value(RENDER_DELAY, render_delay.to_string());
+        set_status(ctx, "");
+    }
+
+    fn event_update(&mut self, ctx: &mut Context, event_sys: &mut EventSystem) -> LoopState {
+        if quit_requested(event_sys) {
+            return LoopState::Break;
+        }
+        for ui_event in ctx.document.event_update(event_sys) {
+            match ui_event {
+                UiEvent::Action(Action::Quit) => return LoopState::Break,
+                UiEvent::Action(Action::Offline) => {
+                    ctx.settings.network_mut().set_offline_server(true);
+                    return LoopState::Replace(Box::new(Connecting::new()));
+                }
+                UiEvent::Action(Action::Play) => return self.connect(ctx),
+                // Submitting the line input of a view.
+                UiEvent::Action(Action::Send) => {
+                    if ctx.document.view_name() == CONNECT_VIEW {
+                        return self.connect(ctx);
+                    } else if ctx.document.view_name() == SETTINGS_VIEW {
+                        self.save_settings(ctx);
+                    }
+                }
+                UiEvent::Back => {
+                    ctx.document.goto(MAIN_VIEW);
+                }
+            }
+        }
+        LoopState::Continue
+    }
+
+    fn render(&mut self, ctx: &mut Context, target: &mut Frame) {
+        ctx.render_menu(target);
+    }
+
+    fn is_menu(&self) -> bool {
+        true
+    }
+}
+
+/// Connect to the server given by the network settings, without
+/// blocking the game so that the player can cancel.
+pub struct Connecting {
+    started: bool,
+}
+
+impl Connecting {
+
+    pub fn new() -> Connecting {
+        Connecting {
+            started: false,
+        }
+    }
+
+    fn cancel(&mut self, ctx: &mut Context) -> LoopState {
+        info!("Connection canceled");
+        ctx.server.disconnect();
+        LoopState::Replace(Box::new(MainMenu::new()))
+    }
+}
+
+impl GameState for Connecting {
+
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.document.goto(CONNECTING_VIEW);
+        let message = {
+            let network = ctx.settings.network();
+            if let Some(path) = network.replay_path() {
+                format!("Replaying {}", path)
+            } else if network.offline_server() {
+                "Starting the offline server".to_string()
+            } else {
+                format!("Connecting to {}", network.addr())
+            }
+        };
+        set_status(ctx, &message);
+    }
+
+    fn event_update(&mut self, ctx: &mut Context, event_sys: &mut EventSystem) -> LoopState {
+        if quit_requested(event_sys) {
+            return LoopState::Break;
+        }
+        for ui_event in ctx.document.event_update(event_sys) {
+            if ui_event == UiEvent::Back {
+                return self.cancel(ctx);
+            }
+        }
+        // The cancel button went to the main view.
+        if ctx.document.view_name() == MAIN_VIEW {
+            return self.cancel(ctx);
+        }
+        LoopState::Continue
+    }
+
+    fn update(&mut self, ctx: &mut Context, _: u64) -> LoopState {
+        if !self.started {
+            ctx.server.connect();
+            self.started = true;
+        }
+        match ctx.server.poll_connect() {
+            Some(Ok(())) => LoopState::Replace(Box::new(Loading::new())),
+            Some(Err(e)) => {
+                warn!("Couldn't connect: {}", e);
+                LoopState::Replace(Box::new(Disconnected::new(&e)))
+            }
+            None => LoopState::Continue,
+        }
+    }
+
+    fn render(&mut self, ctx: &mut Context, target: &mut Frame) {
+        ctx.render_menu(target);
+    }
+
+    fn is_menu(&self) -> bool {
+        true
+    }
+}
+
+/// Load the world in another thread, showing what is being loaded.
+pub struct Loading {
+    loader: DeferredLoader<GameResources>,
+}
+
+impl Loading {
+
+    pub fn new() -> Loading {
+        Loading {
+            loader: DeferredLoader::new(Box::new(GameLoader::new(DEFAULT_MAP_PATH))),
+        }
+    }
+
+    fn show_progress(&self, ctx: &mut Context) {
+        let info = self.loader.current();
+        ctx.document.set_value(LOADING_NAME, info.name.clone());
+        ctx.document.set_value(LOADING_PROGRESS, format!("{}", (info.progress * 100.0) as u32));
+    }
+}
+
+impl GameState for Loading {
+
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.document.goto(LOADING_VIEW);
+        self.show_progress(ctx);
+    }
+
+    fn event_update(&mut self, _: &mut Context, event_sys: &mut EventSystem) -> LoopState {
+        if quit_requested(event_sys) {
+            LoopState::Break
+        } else {
+            LoopState::Continue
+        }
+    }
+
+    fn update(&mut self, ctx: &mut Context, _: u64) -> LoopState {
+        match self.loader.poll() {
+            Some(Ok(resources)) => {
+                let instance = GameInstance::new(&ctx.window, ctx.settings.clone(), resources);
+                LoopState::Replace(Box::new(instance))
+            }
+            Some(Err(e)) => {
+                error!("{}", e);
+                ctx.server.disconnect();
+                LoopState::Replace(Box::new(Disconnected::load_failed(&e)))
+            }
+            None => {
+                self.show_progress(ctx);
+                LoopState::Continue
+            }
+        }
+    }
+
+    fn render(&mut self, ctx: &mut Context, target: &mut Frame) {
+        ctx.render_menu(target);
+    }
+
+    fn is_menu(&self) -> bool {
+        true
+    }
+}
+
+/// The menu opened with the menu key while playing,
+/// the world is still updated below it.
+pub struct Paused;
+
+impl Paused {
+
+    pub fn new() -> Paused {
+        Paused
+    }
+}
+
+impl GameState for Paused {
+
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.document.goto(PAUSE_VIEW);
+    }
+
+    fn event_update(&mut self, ctx: &mut Context, event_sys: &mut EventSystem) -> LoopState {
+        for ui_event in ctx.document.event_update(event_sys) {
+            match ui_event {
+                UiEvent::Back | UiEvent::Action(Action::Play) => return LoopState::Pop,
+                UiEvent::Action(Action::Quit) => return LoopState::Break,
+                // The game below sends it.
+                UiEvent::Action(Action::Send) => {
+                    let message = ctx.document.value(CHAT_MESSAGE).unwrap_or("").trim().to_string();
+                    if !message.is_empty() {
+                        event_sys.push_chat(message);
+                    }
+                    ctx.document.set_value(CHAT_MESSAGE, String::new());
+                }
+                // Only for the main menu, the game is already started.
+                UiEvent::Action(Action::Offline) => (),
+            }
+        }
+        LoopState::Continue
+    }
+
+    fn render(&mut self, ctx: &mut Context, target: &mut Frame) {
+        ctx.render_menu(target);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn is_menu(&self) -> bool {
+        true
+    }
+}
+
+/// Shown over the world, frozen while the connection to
+/// the server is being restored, see `Server::status`.
+pub struct Reconnecting;
+
+impl Reconnecting {
+
+    pub fn new() -> Reconnecting {
+        Reconnecting
+    }
+}
+
+impl GameState for Reconnecting {
+
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.document.goto(RECONNECTING_VIEW);
+        set_status(ctx, "");
+    }
+
+    fn event_update(&mut self, ctx: &mut Context, event_sys: &mut EventSystem) -> LoopState {
+        for ui_event in ctx.document.event_update(event_sys) {
+            if ui_event == UiEvent::Action(Action::Quit) {
+                return LoopState::Break;
+            }
+        }
+        LoopState::Continue
+    }
+
+    fn update(&mut self, ctx: &mut Context, _: u64) -> LoopState {
+        match ctx.server.status() {
+            ConnectionStatus::Connected => LoopState::Pop,
+            ConnectionStatus::Reconnecting { attempt } => {
+                set_status(ctx, &format!("Reconnecting, attempt {}", attempt));
+                LoopState::Continue
+            }
+        }
+    }
+
+    fn render(&mut self, ctx: &mut Context, target: &mut Frame) {
+        ctx.render_menu(target);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn is_menu(&self) -> bool {
+        true
+    }
+}
+
+/// Shown when the server can't be connected to, the connection
+/// is lost or the world can't be loaded, to try again or go back
+/// to the main menu.
+pub struct Disconnected {
+    title: &'static str,
+    message: String,
+}
+
+impl Disconnected {
+
+    pub fn new(error: &ServerError) -> Disconnected {
+        let title = match *error {
+            ServerError::Map(_) => "Loading failed",
+            _ => "Disconnected",
+        };
+        Disconnected {
+            title: title,
+            message: error.to_string(),
+        }
+    }
+
+    pub fn load_failed(error: &LoadError) -> Disconnected {
+        Disconnected {
+            title: "Loading failed",
+            message: error.to_string(),
+        }
+    }
+}
+
+impl GameState for Disconnected {
+
+    fn enter(&mut self, ctx: &mut Context) {
+        ctx.document.goto(DISCONNECTED_VIEW);
+        ctx.document.set_value(ERROR_TITLE, self.title.to_string());
+        set_status(ctx, &self.message);
+    }
+
+    fn event_update(&mut self, ctx: &mut Context, event_sys: &mut EventSystem) -> LoopState {
+        if quit_requested(event_sys) {
+            return LoopState::Break;
+        }
+        for ui_event in ctx.document.event_update(event_sys) {
+            match ui_event {
+                UiEvent::Action(Action::Play) => return LoopState::Replace(Box::new(Connecting::new())),
+                UiEvent::Action(Action::Quit) => return LoopState::Break,
+                UiEvent::Back => return LoopState::Replace(Box::new(MainMenu::new())),
+                _ => (),
+            }
+        }
+        // A button went to the main view.
+        if ctx.document.view_name() == MAIN_VIEW {
+            return LoopState::Replace(Box::new(MainMenu::new()));
+        }
+        LoopState::Continue
+    }
+
+    fn render(&mut self, ctx: &mut Context, target: &mut Frame) {
+        ctx.render_menu(target);
+    }
+
+    fn is_menu(&self) -> bool {
+        true
+    }
+}
+
+fn set_status(ctx: &mut Context, message: &str) {
+    ctx.document.set_value(STATUS, message.to_string());
+}
+
+// The window was closed.
+fn quit_requested(event_sys: &EventSystem) -> bool {
+    event_sys.iter().any(|e| e.kind == UserEventType::Quit && e.state == UserEventState::Start)
+}
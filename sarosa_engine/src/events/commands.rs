@@ -0,0 +1,107 @@
+use std::cmp::max;
+use cgmath::Vector2;
+use events::UserEventType;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Direction {
+
+    /// Unit vector pointing toward that direction.
+    pub fn to_vector(&self) -> Vector2<f32> {
+        match *self {
+            Direction::North => Vector2::new( 0.0,  1.0),
+            Direction::South => Vector2::new( 0.0, -1.0),
+            Direction::East  => Vector2::new( 1.0,  0.0),
+            Direction::West  => Vector2::new(-1.0,  0.0),
+        }
+    }
+
+    /// The way something moving at `speed` walks, `None` when it stands still.
+    pub fn of_speed(speed: Vector2<f32>) -> Option<Direction> {
+        if speed.x == 0.0 && speed.y == 0.0 {
+            None
+        } else if speed.x.abs() > speed.y.abs() {
+            Some(if speed.x > 0.0 { Direction::East } else { Direction::West })
+        } else {
+            Some(if speed.y > 0.0 { Direction::North } else { Direction::South })
+        }
+    }
+}
+
+/// Keeps track of the movement commands currently pressed.
+/// The last pressed command wins, when it is released the
+/// previous one still pressed is used again.
+pub struct CommandStates {
+    up: u8,
+    down: u8,
+    left: u8,
+    right: u8,
+}
+
+impl CommandStates {
+    pub fn new() -> CommandStates {
+        CommandStates {
+            up: 0,
+            down: 0,
+            left: 0,
+            right: 0,
+        }
+    }
+
+    pub fn inject(&mut self, command: UserEventType, reset: bool) {
+        let max_value = max(max(max(self.up, self.down), self.left), self.right);
+        if reset {
+            let old_value = match command {
+                UserEventType::CmdUp => {
+                    let old_value = self.up;
+                    self.up = 0;
+                    old_value
+                }
+                UserEventType::CmdDown => {
+                    let old_value = self.down;
+                    self.down = 0;
+                    old_value
+                }
+                UserEventType::CmdLeft => {
+                    let old_value = self.left;
+                    self.left = 0;
+                    old_value
+                }
+                UserEventType::CmdRight => {
+                    let old_value = self.right;
+                    self.right = 0;
+                    old_value
+                }
+                _ => return,
+            };
+
+            if self.up    > old_value { self.up    = self.up    - 1 }
+            if self.down  > old_value { self.down  = self.down  - 1 }
+            if self.left  > old_value { self.left  = self.left  - 1 }
+            if self.right > old_value { self.right = self.right - 1 }
+        } else {
+            match command {
+                UserEventType::CmdUp => self.up = max_value + 1,
+                UserEventType::CmdDown => self.down = max_value + 1,
+                UserEventType::CmdLeft => self.left = max_value + 1,
+                UserEventType::CmdRight => self.right = max_value + 1,
+                _ => return,
+            }
+        }
+    }
+
+    pub fn next_direction(&self) -> Option<Direction> {
+        let max_value = max(max(max(self.up, self.down), self.left), self.right);
+        if max_value == 0 { None }
+        else if self.up == max_value { Some(Direction::North) }
+        else if self.down == max_value { Some(Direction::South) }
+        else if self.left == max_value { Some(Direction::West) }
+        else { Some(Direction::East) }
+    }
+}
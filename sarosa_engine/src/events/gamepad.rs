@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use gilrs;
+
+use events::{
+    PushEvent,
+    UserEvent,
+    UserEventType
+};
+use events::UserEventState::{
+    Start,
+    Stop
+};
+use events::commands::Direction;
+use models::settings::GamepadSettings;
+
+/// Stick deflection, between 0 and 1, under which a stick
+/// is considered centered.
+pub const DEFAULT_DEAD_ZONE: f32 = 0.25;
+
+// Generates the buttons enum and the conversions with the names
+// used in the settings file, which are the names of the variants.
+macro_rules! gamepad_buttons {
+    ($($button:ident),* $(,)*) => {
+
+        /// Gamepad buttons that can be bound to an action.
+        /// The names follow the layout of an Xbox controller.
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        pub enum GamepadButton {
+            $($button,)*
+        }
+
+        impl GamepadButton {
+
+            pub fn from_name(name: &str) -> Option<GamepadButton> {
+                match name {
+                    $(stringify!($button) => Some(GamepadButton::$button),)*
+                    _ => None,
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $(GamepadButton::$button => stringify!($button),)*
+                }
+            }
+
+            fn from_gilrs(button: gilrs::Button) -> Option<GamepadButton> {
+                match button {
+                    $(gilrs::Button::$button => Some(GamepadButton::$button),)*
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+gamepad_buttons! {
+    South, East, North, West,
+    LeftTrigger, LeftTrigger2, RightTrigger, RightTrigger2,
+    Select, Start, Mode, LeftThumb, RightThumb,
+    DPadUp, DPadDown, DPadLeft, DPadRight,
+}
+
+/// Returns the direction the stick at (`x`, `y`) is pointing to,
+/// or `None` if it is in the dead zone. Diagonals go to the axis
+/// the most pushed, like the arrow keys a single direction is used.
+pub fn stick_direction(x: f32, y: f32, dead_zone: f32) -> Option<Direction> {
+    if x * x + y * y < dead_zone * dead_zone {
+        None
+    } else if x.abs() > y.abs() {
+        if x > 0.0 { Some(Direction::East) } else { Some(Direction::West) }
+    } else {
+        if y > 0.0 { Some(Direction::North) } else { Some(Direction::South) }
+    }
+}
+
+fn direction_command(direction: Direction) -> UserEventType {
+    match direction {
+        Direction::North => UserEventType::CmdUp,
+        Direction::South => UserEventType::CmdDown,
+        Direction::East  => UserEventType::CmdRight,
+        Direction::West  => UserEventType::CmdLeft,
+    }
+}
+
+/// Movement stick of a gamepad.
+///
+/// The stick position is turned into movement commands, as if the
+/// matching arrow key was pressed while it is out of the dead zone.
+pub struct Stick {
+    x: f32,
+    y: f32,
+    direction: Option<Direction>,
+}
+
+impl Stick {
+
+    pub fn new() -> Stick {
+        Stick {
+            x: 0.0,
+            y: 0.0,
+            direction: None,
+        }
+    }
+
+    pub fn set_x(&mut self, x: f32, dead_zone: f32, event_sys: &mut PushEvent) {
+        self.x = x;
+        self.update(dead_zone, event_sys);
+    }
+
+    pub fn set_y(&mut self, y: f32, dead_zone: f32, event_sys: &mut PushEvent) {
+        self.y = y;
+        self.update(dead_zone, event_sys);
+    }
+
+    /// Center the stick, for instance when the gamepad is unplugged.
+    pub fn release(&mut self, event_sys: &mut PushEvent) {
+        self.x = 0.0;
+        self.y = 0.0;
+        self.update(1.0, event_sys);
+    }
+
+    fn update(&mut self, dead_zone: f32, event_sys: &mut PushEvent) {
+        let direction = stick_direction(self.x, self.y, dead_zone);
+        if direction == self.direction {
+            return;
+        }
+        if let Some(old) = self.direction {
+            event_sys.push(UserEvent {
+                state: Stop,
+                kind: direction_command(old),
+            });
+        }
+        if let Some(new) = direction {
+            event_sys.push(UserEvent {
+                state: Start,
+                kind: direction_command(new),
+            });
+        }
+        self.direction = direction;
+    }
+}
+
+/// Source of the events of all the gamepads plugged in.
+/// The left stick moves the player, the buttons are
+/// mapped according to the `GamepadSettings`.
+pub struct Gamepads {
+    gilrs: gilrs::Gilrs,
+    sticks: HashMap<usize, Stick>,
+}
+
+impl Gamepads {
+
+    pub fn new() -> Gamepads {
+        Gamepads {
+            gilrs: gilrs::Gilrs::new(),
+            sticks: HashMap::new(),
+        }
+    }
+
+    pub fn poll_events(&mut self, settings: &GamepadSettings, event_sys: &mut PushEvent) {
+        let dead_zone = settings.dead_zone();
+        for (id, event) in self.gilrs.poll_events() {
+            match event {
+                gilrs::Event::ButtonPressed(button, _) => {
+                    push_button(settings, button, true, event_sys);
+                }
+                gilrs::Event::ButtonReleased(button, _) => {
+                    push_button(settings, button, false, event_sys);
+                }
+                gilrs::Event::AxisChanged(gilrs::Axis::LeftStickX, value, _) => {
+                    self.sticks.entry(id).or_insert_with(Stick::new)
+                        .set_x(value, dead_zone, event_sys);
+                }
+                gilrs::Event::AxisChanged(gilrs::Axis::LeftStickY, value, _) => {
+                    self.sticks.entry(id).or_insert_with(Stick::new)
+                        .set_y(value, dead_zone, event_sys);
+                }
+                gilrs::Event::Disconnected => {
+                    if let Some(mut stick) = self.sticks.remove(&id) {
+                        stick.release(event_sys);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+fn push_button(
+    settings: &GamepadSettings,
+    button: gilrs::Button,
+    pressed: bool,
+    event_sys: &mut PushEvent)
+{
+    let kind = GamepadButton::from_gilrs(button).and_then(|b| settings.get(b));
+    if let Some(kind) = kind {
+        event_sys.push(UserEvent {
+            state: if pressed { Start } else { Stop },
+            kind: kind,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use events::{PushEvent, UserEvent, UserEventType};
+    use events::UserEventState::{Start, Stop};
+    use events::commands::Direction;
+    use super::{stick_direction, GamepadButton, Stick};
+
+    impl PushEvent for Vec<UserEvent> {
+        fn push(&mut self, e: UserEvent) {
+            Vec::push(self, e);
+        }
+
+        fn push_chat(&mut self, _: String) {}
+
+        fn push_resize(&mut self, _: u32, _: u32) {}
+    }
+
+    fn event(state: bool, kind: UserEventType) -> UserEvent {
+        UserEvent {
+            state: if state { Start } else { Stop },
+            kind: kind,
+        }
+    }
+
+    #[test]
+    fn dead_zone() {
+        assert_eq!(stick_direction(0.1, -0.1, 0.25), None);
+        assert_eq!(stick_direction(0.3, 0.0, 0.25), Some(Direction::East));
+        assert_eq!(stick_direction(0.2, 0.2, 0.25), Some(Direction::North));
+        assert_eq!(stick_direction(-0.9, 0.5, 0.25), Some(Direction::West));
+        assert_eq!(stick_direction(0.1, -0.8, 0.25), Some(Direction::South));
+    }
+
+    #[test]
+    fn stick_commands() {
+        let mut events = Vec::new();
+        let mut stick = Stick::new();
+
+        stick.set_x(0.1, 0.25, &mut events);
+        assert!(events.is_empty());
+
+        stick.set_x(0.8, 0.25, &mut events);
+        stick.set_x(0.9, 0.25, &mut events);
+        assert_eq!(events, vec![event(true, UserEventType::CmdRight)]);
+
+        events.clear();
+        stick.set_y(-1.0, 0.25, &mut events);
+        assert_eq!(events, vec![event(false, UserEventType::CmdRight),
+                                event(true, UserEventType::CmdDown)]);
+
+        events.clear();
+        stick.release(&mut events);
+        assert_eq!(events, vec![event(false, UserEventType::CmdDown)]);
+    }
+
+    #[test]
+    fn button_names() {
+        assert_eq!(GamepadButton::from_name("DPadUp"), Some(GamepadButton::DPadUp));
+        assert_eq!(GamepadButton::South.name(), "South");
+        assert_eq!(GamepadButton::from_name("A"), None);
+    }
+}
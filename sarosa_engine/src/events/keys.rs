@@ -0,0 +1,55 @@
+use glium::glutin::VirtualKeyCode;
+
+// Generates the conversions between key codes and the names
+// used in the settings file. The name of a key is the name
+// of its `VirtualKeyCode` variant.
+macro_rules! key_names {
+    ($($key:ident),* $(,)*) => {
+
+        /// Returns the key with the given name, if it is supported.
+        pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($key) => Some(VirtualKeyCode::$key),)*
+                _ => None,
+            }
+        }
+
+        /// Returns the name of the given key, if it is supported.
+        pub fn key_name(key: VirtualKeyCode) -> Option<&'static str> {
+            match key {
+                $(VirtualKeyCode::$key => Some(stringify!($key)),)*
+                _ => None,
+            }
+        }
+    }
+}
+
+key_names! {
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8,
+    Key9, Key0, A, B, C, D, E, F,
+    G, H, I, J, K, L, M, N,
+    O, P, Q, R, S, T, U, V,
+    W, X, Y, Z, F1, F2, F3, F4,
+    F5, F6, F7, F8, F9, F10, F11, F12,
+    Escape, Space, Return, Tab, Back, Insert, Delete, Home,
+    End, PageUp, PageDown, Left, Up, Right, Down, LShift,
+    RShift, LControl, RControl, LAlt, RAlt, Numpad0, Numpad1, Numpad2,
+    Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, Add,
+    Subtract, Multiply, Comma, Period, Slash, Backslash, Semicolon, Apostrophe,
+    Minus, Equals, LBracket, RBracket, Grave,
+}
+
+#[cfg(test)]
+mod test {
+
+    use glium::glutin::VirtualKeyCode;
+    use super::{key_from_name, key_name};
+
+    #[test]
+    fn round_trip() {
+        for &key in &[VirtualKeyCode::A, VirtualKeyCode::Up, VirtualKeyCode::F12] {
+            assert_eq!(key_from_name(key_name(key).unwrap()), Some(key));
+        }
+        assert_eq!(key_from_name("Foo"), None);
+    }
+}
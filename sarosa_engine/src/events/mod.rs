@@ -4,10 +4,20 @@ use std::collections::HashMap;
 use std::collections::hash_map::Entry::*;
 //use glutin::
 
+pub mod commands;
+pub mod gamepad;
+pub mod keys;
+
 #[derive(Default)]
 pub struct EventSystem {
     queue: Vec<UserEvent>,
     seen_events: HashMap<UserEventType, UserEvent>,
+    chat_messages: Vec<String>,
+    resized: Option<(u32, u32)>,
+    // While a menu is open, it takes the input before the gameplay.
+    menu_open: bool,
+    menu_keys: Vec<MenuKey>,
+    typed_text: String,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -31,16 +41,154 @@ pub enum UserEventType {
     CmdRight,
     ZoomIn,
     ZoomOut,
-    Attack
+    Attack,
+    Screenshot,
+    /// Start typing a chat message.
+    Chat,
+    ChatScrollUp,
+    ChatScrollDown,
+    /// Open or close the menu.
+    Menu,
+}
+
+/// Keys of the menus. They don't depend on the bindings
+/// of the settings, like the keys used to type.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum MenuKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Focus the next element, in the order of the markup.
+    Next,
+    Previous,
+    /// Press the button or submit the line input with the focus.
+    Activate,
+    /// Remove the last character of the line input with the focus.
+    Erase,
+    /// Leave the menu.
+    Back,
 }
 
+impl UserEventType {
+
+    /// Name of the event in the settings files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            UserEventType::Quit => "quit",
+            UserEventType::CmdUp => "up",
+            UserEventType::CmdDown => "down",
+            UserEventType::CmdLeft => "left",
+            UserEventType::CmdRight => "right",
+            UserEventType::ZoomIn => "zoom_in",
+            UserEventType::ZoomOut => "zoom_out",
+            UserEventType::Attack => "attack",
+            UserEventType::Screenshot => "screenshot",
+            UserEventType::Chat => "chat",
+            UserEventType::ChatScrollUp => "chat_scroll_up",
+            UserEventType::ChatScrollDown => "chat_scroll_down",
+            UserEventType::Menu => "menu",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<UserEventType> {
+        match name {
+            "quit" => Some(UserEventType::Quit),
+            "up" => Some(UserEventType::CmdUp),
+            "down" => Some(UserEventType::CmdDown),
+            "left" => Some(UserEventType::CmdLeft),
+            "right" => Some(UserEventType::CmdRight),
+            "zoom_in" => Some(UserEventType::ZoomIn),
+            "zoom_out" => Some(UserEventType::ZoomOut),
+            "attack" => Some(UserEventType::Attack),
+            "screenshot" => Some(UserEventType::Screenshot),
+            "chat" => Some(UserEventType::Chat),
+            "chat_scroll_up" => Some(UserEventType::ChatScrollUp),
+            "chat_scroll_down" => Some(UserEventType::ChatScrollDown),
+            "menu" => Some(UserEventType::Menu),
+            _ => None,
+        }
+    }
+}
 
 pub trait PushEvent {
     fn push(&mut self, e: UserEvent);
+
+    /// A chat message typed by the user.
+    fn push_chat(&mut self, message: String);
+
+    /// The window now has `width` by `height` pixels.
+    fn push_resize(&mut self, width: u32, height: u32);
+
+    /// A key pressed while a menu is open.
+    fn push_menu_key(&mut self, _key: MenuKey) {}
+
+    /// A character typed while a menu is open.
+    fn push_char(&mut self, _c: char) {}
+
+    /// Whether the keys go to a menu rather than to the bindings.
+    fn menu_open(&self) -> bool {
+        false
+    }
+}
+
+impl EventSystem {
+
+    /// Chat messages typed since the last call to `clear`.
+    pub fn chat_messages(&self) -> &[String] {
+        &self.chat_messages
+    }
+
+    /// New size of the window if it was resized
+    /// since the last call to `clear`.
+    pub fn resized(&self) -> Option<(u32, u32)> {
+        self.resized
+    }
+
+    /// Keys pressed in the menu since the last call to `clear`.
+    pub fn menu_keys(&self) -> &[MenuKey] {
+        &self.menu_keys
+    }
+
+    /// Text typed in the menu since the last call to `clear`.
+    pub fn typed_text(&self) -> &str {
+        &self.typed_text
+    }
+
+    /// While the menu is open, the gameplay commands are ignored
+    /// and the gamepad moves in the menu.
+    pub fn set_menu_open(&mut self, open: bool) {
+        self.menu_open = open;
+    }
+
+    /// Mark all events as consumed.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.chat_messages.clear();
+        self.resized = None;
+        self.menu_keys.clear();
+        self.typed_text.clear();
+    }
 }
 
 impl PushEvent for EventSystem {
     fn push(&mut self, e: UserEvent) {
+        // Releases still go through so that nothing stays pressed.
+        if self.menu_open && e.state == UserEventState::Start && e.kind != UserEventType::Quit {
+            let key = match e.kind {
+                UserEventType::CmdUp => Some(MenuKey::Up),
+                UserEventType::CmdDown => Some(MenuKey::Down),
+                UserEventType::CmdLeft => Some(MenuKey::Left),
+                UserEventType::CmdRight => Some(MenuKey::Right),
+                UserEventType::Attack => Some(MenuKey::Activate),
+                UserEventType::Menu => Some(MenuKey::Back),
+                _ => None,
+            };
+            if let Some(key) = key {
+                self.menu_keys.push(key);
+            }
+            return;
+        }
         match self.seen_events.entry(e.kind) {
             Occupied(mut old_e) => {
                 if old_e.get().state != e.state {
@@ -54,6 +202,26 @@ impl PushEvent for EventSystem {
             }
         }
     }
+
+    fn push_chat(&mut self, message: String) {
+        self.chat_messages.push(message);
+    }
+
+    fn push_resize(&mut self, width: u32, height: u32) {
+        self.resized = Some((width, height));
+    }
+
+    fn push_menu_key(&mut self, key: MenuKey) {
+        self.menu_keys.push(key);
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.typed_text.push(c);
+    }
+
+    fn menu_open(&self) -> bool {
+        self.menu_open
+    }
 }
 
 impl Deref for EventSystem {
@@ -69,3 +237,44 @@ impl DerefMut for EventSystem {
         &mut self.queue
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::{EventSystem, MenuKey, PushEvent, UserEvent, UserEventState, UserEventType};
+
+    fn event(state: UserEventState, kind: UserEventType) -> UserEvent {
+        UserEvent {
+            state: state,
+            kind: kind,
+        }
+    }
+
+    #[test]
+    fn menu_takes_priority() {
+        let mut event_sys = EventSystem::default();
+        event_sys.push(event(UserEventState::Start, UserEventType::CmdUp));
+        event_sys.set_menu_open(true);
+        event_sys.push(event(UserEventState::Start, UserEventType::Attack));
+        event_sys.push(event(UserEventState::Start, UserEventType::ZoomIn));
+        event_sys.push(event(UserEventState::Stop, UserEventType::CmdUp));
+        event_sys.push_menu_key(MenuKey::Next);
+        event_sys.push_char('a');
+
+        assert_eq!(&event_sys[..], &[
+            event(UserEventState::Start, UserEventType::CmdUp),
+            event(UserEventState::Stop, UserEventType::CmdUp),
+        ]);
+        assert_eq!(event_sys.menu_keys(), &[MenuKey::Activate, MenuKey::Next]);
+        assert_eq!(event_sys.typed_text(), "a");
+
+        // Closing the window still works.
+        event_sys.push(event(UserEventState::Start, UserEventType::Quit));
+        assert_eq!(event_sys.last(), Some(&event(UserEventState::Start, UserEventType::Quit)));
+
+        event_sys.clear();
+        assert!(event_sys.menu_keys().is_empty());
+        assert!(event_sys.typed_text().is_empty());
+        assert!(event_sys.menu_open());
+    }
+}
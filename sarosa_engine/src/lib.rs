@@ -2,24 +2,31 @@
 #[macro_use] extern crate glium;
 extern crate sarosa_net;
 extern crate glutin;
+extern crate gilrs;
 extern crate cgmath;
 extern crate image;
 extern crate num;
+extern crate toml;
+extern crate rustc_serialize;
 #[macro_use] extern crate approx;
 
 pub use self::models::settings::Settings;
 pub use self::rendering::Window;
-pub use self::net::Server;
+pub use self::net::{NetStats, Rate, Server};
 pub use self::core::GameLoop;
 pub use self::core::GameInstance;
+pub use self::core::{GameState, LoopState};
+pub use self::core::{Connecting, MainMenu};
 
 pub mod models;
 pub mod loader;
+pub mod ui;
 
 mod core;
 mod net;
 mod events;
 mod rendering;
 mod animation;
+mod physics;
 mod unit;
 mod profiler;
@@ -1,32 +1,75 @@
 
+use std::error::Error;
+use std::fmt;
+
 // Re-export for doc.
 pub use core::loops::deferred::DeferredLoader;
 
+/// What a loader is about to load, see `Loader::next_resource_info`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NextResourceInfo {
+    /// Shown to the player, such as the path of a file.
+    pub name: String,
+    /// Part of the resources already loaded, from 0 to 1.
+    pub progress: f32,
+}
+
+/// A resource that couldn't be loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadError {
+    /// Same as `NextResourceInfo::name`.
+    pub name: String,
+    pub reason: String,
+}
+
+impl LoadError {
+
+    pub fn new<E: fmt::Display>(name: &str, reason: E) -> LoadError {
+        LoadError {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Couldn't load `{}`: {}", self.name, self.reason)
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        "couldn't load a resource"
+    }
+}
+
 /// Trait to define a loader that could be loaded
 /// using the `DeferredLoader`.
 ///
-/// TODO(Nemikolh): Add also an interface to do
-/// incremental loading with reports after each step.
-/// (Can be usefull to display a percentage or what is being
-/// currently made).
-///
-/// Probably Something like:
-/// ```ignore
-/// struct NextResourceInfo {
-///     name: String,
-///     progress: f32,
-/// }
-/// // To add to the trait Loader.
-/// fn next_resource_kind() -> NextResourceInfo;
-/// fn load_next_resource();
-/// ```
+/// The loading is done in steps, so that the progress
+/// can be reported after each of them.
 pub trait Loader: Send {
 
     type Resources: Send;
 
-    /// This function is supposed to do
-    /// all the loading for R. Here, you should
+    /// What the next call to `load_next_resource` loads,
+    /// `None` once everything is loaded.
+    fn next_resource_info(&self) -> Option<NextResourceInfo>;
+
+    /// Do the next step of the loading. Here, you should
     /// be doing long operation like reading a file,
-    /// and so on.
-    fn load_resources(&mut self) -> Self::Resources;
+    /// and so on. The loading stops at the first error.
+    fn load_next_resource(&mut self) -> Result<(), LoadError>;
+
+    /// Give what was loaded, once there is nothing left.
+    fn take_resources(&mut self) -> Self::Resources;
+
+    /// Do all the loading at once.
+    fn load_resources(&mut self) -> Result<Self::Resources, LoadError> {
+        while self.next_resource_info().is_some() {
+            try!(self.load_next_resource());
+        }
+        Ok(self.take_resources())
+    }
 }
@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+use std::collections::vec_deque::Iter;
+use std::iter::{Rev, Skip, Take};
+
+use models::player::PlayerId;
+
+/// Number of messages kept in the chat history.
+pub const CHAT_HISTORY_SIZE: usize = 100;
+
+/// Maximum number of characters in a message typed by the user.
+pub const MAX_MESSAGE_LENGTH: usize = 200;
+
+/// How long a message stays above the player who said it, in nanoseconds.
+pub const SPEECH_DURATION_NS: u64 = 5_000_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub author: PlayerId,
+    /// Name of the author when the message was said,
+    /// it is still known after the author has left.
+    pub name: String,
+    pub text: String,
+}
+
+/// The last messages said by players, newest last.
+///
+/// The log can be scrolled back: `scroll` is the number of
+/// recent messages hidden below the visible ones.
+pub struct ChatLog {
+    messages: VecDeque<ChatMessage>,
+    capacity: usize,
+    scroll: usize,
+}
+
+impl ChatLog {
+
+    pub fn new() -> ChatLog {
+        ChatLog::with_capacity(CHAT_HISTORY_SIZE)
+    }
+
+    pub fn with_capacity(capacity: usize) -> ChatLog {
+        ChatLog {
+            messages: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+            scroll: 0,
+        }
+    }
+
+    pub fn push(&mut self, author: PlayerId, name: String, text: String) {
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(ChatMessage {
+            author: author,
+            name: name,
+            text: text,
+        });
+        // Keep showing the same messages when scrolled back.
+        if self.scroll > 0 {
+            self.scroll = ::std::cmp::min(self.scroll + 1, self.max_scroll());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Number of recent messages hidden by scrolling back.
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
+    /// Show `lines` older messages.
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = ::std::cmp::min(self.scroll + lines, self.max_scroll());
+    }
+
+    /// Show `lines` more recent messages.
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll = 0;
+    }
+
+    /// The `count` messages visible at the current scroll
+    /// position, from the most recent to the oldest.
+    pub fn visible(&self, count: usize) -> Take<Skip<Rev<Iter<ChatMessage>>>> {
+        self.messages.iter().rev().skip(self.scroll).take(count)
+    }
+
+    // At least one message stays visible.
+    fn max_scroll(&self) -> usize {
+        self.messages.len().saturating_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::ChatLog;
+
+    fn texts(log: &ChatLog, count: usize) -> Vec<&str> {
+        log.visible(count).map(|m| &m.text[..]).collect()
+    }
+
+    #[test]
+    fn bounded_history() {
+        let mut log = ChatLog::with_capacity(3);
+        for i in 0..5 {
+            log.push(1, String::new(), format!("{}", i));
+        }
+        assert_eq!(log.len(), 3);
+        assert_eq!(texts(&log, 10), vec!["4", "3", "2"]);
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut log = ChatLog::with_capacity(10);
+        for i in 0..5 {
+            log.push(1, String::new(), format!("{}", i));
+        }
+
+        log.scroll_up(2);
+        assert_eq!(texts(&log, 2), vec!["2", "1"]);
+
+        // A new message doesn't move what is shown.
+        log.push(2, String::new(), "5".to_string());
+        assert_eq!(texts(&log, 2), vec!["2", "1"]);
+
+        log.scroll_up(100);
+        assert_eq!(texts(&log, 2), vec!["0"]);
+
+        log.scroll_down(100);
+        assert_eq!(texts(&log, 2), vec!["5", "4"]);
+    }
+}
@@ -1,22 +1,37 @@
+use std::iter::Zip;
 use std::slice::Iter;
 use cgmath::Vector2;
 use cgmath::Zero;
 use models::player::Player;
 use models::player::THIS_PLAYER;
 use models::player::PlayerId;
+use models::player::Speech;
 use models::map::GameMap;
+use models::prediction::Predictor;
+use models::interpolation::DEFAULT_RENDER_DELAY_NS;
+use models::chat::{ChatLog, SPEECH_DURATION_NS};
 use animation::TextureId;
 use animation::AnimationManager;
+use events::UserEventType;
+use physics::PhysicsEngine;
 
 pub struct GameData {
     players: Vec<Player>,
     players_id: Vec<PlayerId>,
     map: GameMap,
+    physics: PhysicsEngine,
+    predictor: Predictor,
+    // Off while replaying a recording, which alone moves the player.
+    prediction: bool,
+    chat: ChatLog,
+    // Time elapsed since the beginning of the game, in nanoseconds.
+    clock: u64,
+    render_delay: u64,
 }
 
 impl GameData {
 
-    pub fn new(this_player_tex_id: TextureId, anim_manager: &AnimationManager)
+    pub fn new(this_player_tex_id: TextureId, anim_manager: &AnimationManager, map: GameMap)
         -> GameData
     {
         assert_eq!(THIS_PLAYER, 0);
@@ -35,7 +50,13 @@ impl GameData {
         GameData {
             players: Vec::new(),
             players_id: Vec::new(),
-            map: GameMap::new(),
+            map: map,
+            physics: PhysicsEngine::new(),
+            predictor: Predictor::new(),
+            prediction: true,
+            chat: ChatLog::new(),
+            clock: 0,
+            render_delay: DEFAULT_RENDER_DELAY_NS,
         }
     }
 
@@ -43,7 +64,10 @@ impl GameData {
     /// to the game. If the player was already there,
     /// then the player data is updated and `false` is returned
     /// If the player is new, `true` will be returned.
-    pub fn add_player(&mut self, id: PlayerId, player: Player) -> bool {
+    pub fn add_player(&mut self, id: PlayerId, mut player: Player) -> bool {
+
+        let (position, speed) = (player.position, player.speed);
+        player.snapshots.push(self.clock, position, speed);
 
         let index = match self.players_id.binary_search(&id) {
             Ok(index) => {
@@ -74,20 +98,63 @@ impl GameData {
         self.players_id.remove(index);
     }
 
+    /// Forget all the players, such as when the server
+    /// is about to tell again who is there.
+    pub fn clear_players(&mut self) {
+        self.players.clear();
+        self.players_id.clear();
+    }
+
     pub fn update_player(&mut self, id: PlayerId, pos: Vector2<f32>, speed: Vector2<f32>) {
 
         match self.players_id.binary_search(&id) {
             Ok(index) => {
                 unsafe {
                     let ref mut player = self.players.get_unchecked_mut(index);
-                    player.position = pos;
-                    player.speed = speed;
+                    if id == THIS_PLAYER && !self.prediction {
+                        player.position = pos;
+                        player.speed = speed;
+                    } else if id == THIS_PLAYER {
+                        // Inputs not yet seen by the server are replayed
+                        // on top of the authoritative position.
+                        player.position = self.predictor.reconcile(
+                            &self.physics, &self.map, &pos, &speed);
+                        player.speed = self.predictor.velocity();
+                    } else {
+                        // Remote players are moved in `fixed_update`.
+                        player.snapshots.push(self.clock, pos, speed);
+                    }
                 }
             },
             Err(_) => warn!("Received `update player` for unknown player id: {}", id),
         }
     }
 
+    /// Apply a movement command to `THIS_PLAYER` without
+    /// waiting for the server.
+    pub fn push_command(&mut self, command: UserEventType, pressed: bool) {
+        if !self.prediction {
+            return;
+        }
+        self.predictor.push_command(command, pressed);
+        let velocity = self.predictor.velocity();
+        if let Ok(index) = self.players_id.binary_search(&THIS_PLAYER) {
+            self.players[index].speed = velocity;
+        }
+    }
+
+    /// Whether `THIS_PLAYER` moves as soon as it is told to. Without
+    /// prediction, movement commands are ignored and the positions
+    /// given by the server are applied as they are.
+    pub fn set_prediction(&mut self, prediction: bool) {
+        self.prediction = prediction;
+    }
+
+    /// Set how far in the past remote players are rendered, in nanoseconds.
+    pub fn set_render_delay(&mut self, render_delay: u64) {
+        self.render_delay = render_delay;
+    }
+
     pub fn this_player(&self) -> Option<&Player> {
         self.players.get(THIS_PLAYER as usize)
     }
@@ -100,13 +167,122 @@ impl GameData {
         self.players.iter()
     }
 
+    pub fn iter_players_with_id(&self) -> Zip<Iter<PlayerId>, Iter<Player>> {
+        self.players_id.iter().zip(self.players.iter())
+    }
+
+    pub fn player(&self, id: PlayerId) -> Option<&Player> {
+        self.players_id.binary_search(&id).ok().map(|index| &self.players[index])
+    }
+
     pub fn get_map(&self) -> &GameMap {
         &self.map
     }
 
+    pub fn chat(&self) -> &ChatLog {
+        &self.chat
+    }
+
+    pub fn chat_mut(&mut self) -> &mut ChatLog {
+        &mut self.chat
+    }
+
+    /// Record a message in the chat and show it above the player who said it.
+    pub fn say(&mut self, id: PlayerId, message: String) {
+        let mut name = String::new();
+        if let Ok(index) = self.players_id.binary_search(&id) {
+            let ref mut player = self.players[index];
+            player.speech = Some(Speech {
+                text: message.clone(),
+                until: self.clock + SPEECH_DURATION_NS,
+            });
+            name = player.info.name.clone();
+        }
+        self.chat.push(id, name, message);
+    }
+
     pub fn fixed_update(&mut self, anim_manager: &AnimationManager, time_elapsed: u64) {
+        self.clock += time_elapsed;
+        let render_time = self.clock.saturating_sub(self.render_delay);
+
+        for (player, &id) in self.players.iter_mut().zip(self.players_id.iter()) {
+            if id == THIS_PLAYER {
+                if self.prediction {
+                    self.predictor.step(&self.physics, &self.map, &mut player.position, time_elapsed);
+                }
+            } else {
+                player.snapshots.prune(render_time);
+                if let Some((position, speed)) = player.snapshots.sample(render_time) {
+                    player.position = position;
+                    player.speed = speed;
+                }
+            }
+        }
+        let clock = self.clock;
         for player in self.players.iter_mut() {
             player.animator.update(anim_manager, time_elapsed, &player.speed);
+            if player.speech.as_ref().map_or(false, |s| s.until <= clock) {
+                player.speech = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use cgmath::Vector2;
+    use cgmath::Zero;
+    use animation::AnimationManager;
+    use animation::TextureId;
+    use events::UserEventType;
+    use models::map::GameMap;
+    use models::player::{Player, THIS_PLAYER};
+    use super::GameData;
+
+    const MAP: &'static str = r#"
+        width = 5
+        height = 5
+
+        [tileset]
+        path = "tiles.png"
+        columns = 1
+        rows = 1
+
+        [[layer]]
+        name = "ground"
+        tiles = """
+        . . . . .
+        . . . . .
+        . . . . .
+        . . . . .
+        . . . . .
+        """
+    "#;
+
+    const STEP: u64 = 10_000_000;
+
+    #[test]
+    fn replay_ignores_commands() {
+        let anim_manager = AnimationManager::new();
+        let mut game_data = GameData::new(TextureId(0), &anim_manager, MAP.parse::<GameMap>().unwrap());
+        game_data.set_prediction(false);
+        let player = Player::new(Vector2::zero(), Vector2::zero(), TextureId(0), &anim_manager);
+        game_data.add_player(THIS_PLAYER, player);
+
+        // The viewer presses keys while the recording is played.
+        game_data.push_command(UserEventType::CmdRight, true);
+        for i in 0..10 {
+            let recorded = Vector2::new(0.0, i as f32 * 0.1);
+            game_data.update_player(THIS_PLAYER, recorded, Vector2::new(0.0, 10.0));
+            game_data.fixed_update(&anim_manager, STEP);
+            if i == 5 {
+                game_data.push_command(UserEventType::CmdRight, false);
+                game_data.push_command(UserEventType::CmdDown, true);
+            }
+            let player = game_data.this_player().unwrap();
+            assert_eq!(player.position, recorded);
+            assert_eq!(player.speed, Vector2::new(0.0, 10.0));
         }
     }
 }
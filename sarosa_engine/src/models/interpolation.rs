@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use cgmath::Vector2;
+
+/// Delay applied by default to remote players, in nanoseconds.
+pub const DEFAULT_RENDER_DELAY_NS: u64 = 100_000_000;
+
+/// Maximum time a remote player keeps moving with its last
+/// known speed when no update has been received, in nanoseconds.
+pub const MAX_EXTRAPOLATION_NS: u64 = 250_000_000;
+
+// Snapshots older than that are useless, see `SnapshotBuffer::prune`.
+const MAX_SNAPSHOTS: usize = 32;
+
+#[derive(Debug, Copy, Clone)]
+struct Snapshot {
+    time: u64,
+    position: Vector2<f32>,
+    speed: Vector2<f32>,
+}
+
+/// Positions received from the server for a remote player.
+///
+/// Remote players are rendered slightly in the past so that there is
+/// (most of the time) a snapshot before and after the rendered instant
+/// to interpolate between. When updates are late, the last known speed
+/// is used for a bounded amount of time.
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+
+    pub fn new() -> SnapshotBuffer {
+        SnapshotBuffer {
+            snapshots: VecDeque::with_capacity(4),
+        }
+    }
+
+    /// Record the state received at `time` (in nanoseconds).
+    pub fn push(&mut self, time: u64, position: Vector2<f32>, speed: Vector2<f32>) {
+        // Keep the buffer sorted even if the clock misbehaves.
+        while self.snapshots.back().map_or(false, |s| s.time > time) {
+            self.snapshots.pop_back();
+        }
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot {
+            time: time,
+            position: position,
+            speed: speed,
+        });
+    }
+
+    /// Drop the snapshots that won't be needed to render
+    /// `render_time` or any later instant.
+    pub fn prune(&mut self, render_time: u64) {
+        while self.snapshots.len() > 1 && self.snapshots[1].time <= render_time {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Returns the position and speed of the player at `render_time`.
+    pub fn sample(&self, render_time: u64) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        let first = match self.snapshots.front() {
+            Some(first) => first,
+            None => return None,
+        };
+        if render_time <= first.time {
+            return Some((first.position, first.speed));
+        }
+
+        for (a, b) in self.snapshots.iter().zip(self.snapshots.iter().skip(1)) {
+            if render_time <= b.time {
+                let t = ((render_time - a.time) as f64 / (b.time - a.time) as f64) as f32;
+                let position = a.position + (b.position - a.position) * t;
+                let speed = a.speed + (b.speed - a.speed) * t;
+                return Some((position, speed));
+            }
+        }
+
+        // No update past `render_time` yet: extrapolate.
+        let last = self.snapshots.back().unwrap();
+        let elapsed = ::std::cmp::min(render_time - last.time, MAX_EXTRAPOLATION_NS);
+        let position = last.position + last.speed * ((elapsed as f64 / 1e9) as f32);
+        Some((position, last.speed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use cgmath::Vector2;
+    use cgmath::Zero;
+    use super::{SnapshotBuffer, MAX_EXTRAPOLATION_NS};
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn empty_buffer() {
+        assert!(SnapshotBuffer::new().sample(0).is_none());
+    }
+
+    #[test]
+    fn before_first_snapshot() {
+        let mut buffer = SnapshotBuffer::new();
+        buffer.push(100 * MS, Vector2::new(1.0, 2.0), Vector2::zero());
+        assert_eq!(buffer.sample(50 * MS), Some((Vector2::new(1.0, 2.0), Vector2::zero())));
+    }
+
+    #[test]
+    fn interpolate_between_snapshots() {
+        let mut buffer = SnapshotBuffer::new();
+        let speed = Vector2::new(10.0, 0.0);
+        buffer.push(100 * MS, Vector2::new(0.0, 0.0), speed);
+        buffer.push(200 * MS, Vector2::new(1.0, 0.0), speed);
+        buffer.push(300 * MS, Vector2::new(1.0, 4.0), Vector2::zero());
+
+        let (pos, _) = buffer.sample(150 * MS).unwrap();
+        assert_relative_eq!(pos.x, 0.5);
+        assert_relative_eq!(pos.y, 0.0);
+
+        let (pos, speed) = buffer.sample(250 * MS).unwrap();
+        assert_relative_eq!(pos.x, 1.0);
+        assert_relative_eq!(pos.y, 2.0);
+        assert_relative_eq!(speed.x, 5.0);
+    }
+
+    #[test]
+    fn bounded_extrapolation() {
+        let mut buffer = SnapshotBuffer::new();
+        buffer.push(100 * MS, Vector2::new(0.0, 0.0), Vector2::new(2.0, 0.0));
+
+        let (pos, _) = buffer.sample(200 * MS).unwrap();
+        assert_relative_eq!(pos.x, 0.2, epsilon = 1e-5);
+
+        let max = (MAX_EXTRAPOLATION_NS as f64 / 1e9) as f32 * 2.0;
+        let (pos, _) = buffer.sample(100_000 * MS).unwrap();
+        assert_relative_eq!(pos.x, max, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn prune_keeps_what_is_needed() {
+        let mut buffer = SnapshotBuffer::new();
+        buffer.push(100 * MS, Vector2::new(0.0, 0.0), Vector2::zero());
+        buffer.push(200 * MS, Vector2::new(1.0, 0.0), Vector2::zero());
+        buffer.push(300 * MS, Vector2::new(2.0, 0.0), Vector2::zero());
+
+        buffer.prune(250 * MS);
+        let (pos, _) = buffer.sample(250 * MS).unwrap();
+        assert_relative_eq!(pos.x, 1.5);
+    }
+
+    #[test]
+    fn out_of_order_snapshot() {
+        let mut buffer = SnapshotBuffer::new();
+        buffer.push(200 * MS, Vector2::new(5.0, 0.0), Vector2::zero());
+        buffer.push(100 * MS, Vector2::new(1.0, 0.0), Vector2::zero());
+        assert_eq!(buffer.sample(300 * MS), Some((Vector2::new(1.0, 0.0), Vector2::zero())));
+    }
+}
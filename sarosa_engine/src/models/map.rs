@@ -1,21 +1,187 @@
+use std::io;
+use std::io::Read;
+use std::fs::File;
+use std::fmt;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+use std::collections::HashMap;
+use toml;
+use rustc_serialize::Decodable;
+use cgmath::Vector2;
 
 use animation::TextureId;
+use unit::TILE_SIZE;
+
+// TODO: The map should be sent by the server.
+pub const DEFAULT_MAP_PATH: &'static str = "./assets/maps/default.toml";
+
+/// Index of a tile within the tileset of a map.
+/// Tiles are numbered from left to right, then from top to bottom.
+pub type TileId = u32;
+
+/// Image containing all the tiles used by a map.
+pub struct Tileset {
+    path: String,
+    columns: u32,
+    rows: u32,
+}
+
+/// A grid of tiles covering the whole map.
+/// `None` means that there is no tile at that place.
+pub struct Layer {
+    name: String,
+    pass: LayerPass,
+    tiles: Vec<Option<TileId>>,
+}
+
+/// Tells when a layer is drawn relatively to the players.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LayerPass {
+    /// Ground, paths, decorations the players walk over.
+    BelowPlayers,
+    /// Roofs, tree tops and anything that should hide the players.
+    AbovePlayers,
+}
+
+/// Solid part of a tile. Values are fractions of the tile
+/// size, starting from the bottom left corner of the tile.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TileShape {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
 
 pub struct GameMap {
     tex_id: TextureId,
     width: u32,
     height: u32,
+    tileset: Tileset,
+    layers: Vec<Layer>,
+    collisions: Vec<Option<TileShape>>,
+    spawns: Vec<Vector2<f32>>,
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(io::Error),
+    /// The file isn't a valid toml file.
+    Syntax(String),
+    /// The file is valid toml but doesn't describe a map.
+    Decode(toml::DecodeError),
+    /// The content of a field is invalid.
+    Invalid(String),
+}
+
+// Raw content of a map file, before validation.
+//
+// A map file looks like:
+//
+// ```toml
+// width = 3
+// height = 2
+//
+// [tileset]
+// path = "./assets/maps/tiles.png"
+// columns = 30
+// rows = 16
+//
+// [[layer]]
+// name = "ground"
+// tiles = """
+// 6 7 8
+// 7 . 6
+// """
+//
+// [[layer]]
+// name = "roofs"
+// pass = "above"
+// tiles = """
+// . . .
+// . 12 .
+// """
+//
+// [collision]
+// tiles = """
+// . # .
+// . . v
+// """
+//
+// [[collision.shape]]
+// symbol = "v"
+// x = 0.0
+// y = 0.0
+// width = 1.0
+// height = 0.5
+//
+// [[spawn]]
+// column = 1
+// row = 1
+// ```
+//
+// Rows of a layer are written from top to bottom and
+// `.` stands for an empty tile. Layers are drawn in the order
+// they appear in the file, `pass` (either "below" or "above",
+// "below" by default) tells if they are drawn before or after
+// the players.
+//
+// The optional collision grid uses `.` for free tiles, `#` for
+// blocked ones and the symbols declared as `collision.shape` for
+// tiles that are only partially blocked.
+#[derive(RustcDecodable)]
+struct MapFile {
+    width: u32,
+    height: u32,
+    tileset: TilesetFile,
+    layer: Vec<LayerFile>,
+    collision: Option<CollisionFile>,
+    spawn: Option<Vec<SpawnFile>>,
 }
 
+#[derive(RustcDecodable)]
+struct TilesetFile {
+    path: String,
+    columns: u32,
+    rows: u32,
+}
+
+#[derive(RustcDecodable)]
+struct LayerFile {
+    name: String,
+    pass: Option<String>,
+    tiles: String,
+}
+
+#[derive(RustcDecodable)]
+struct CollisionFile {
+    tiles: String,
+    shape: Option<Vec<ShapeFile>>,
+}
+
+#[derive(RustcDecodable)]
+struct ShapeFile {
+    symbol: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+#[derive(RustcDecodable)]
+struct SpawnFile {
+    column: u32,
+    row: u32,
+}
 
 impl GameMap {
 
-    pub fn new() -> GameMap {
-        GameMap {
-            tex_id: TextureId(0),
-            width: 300,
-            height: 200,
-        }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<GameMap, MapError> {
+        let mut content = String::new();
+        let mut file = try!(File::open(path));
+        try!(file.read_to_string(&mut content));
+        content.parse()
     }
 
     pub fn width(&self) -> u32 {
@@ -29,4 +195,486 @@ impl GameMap {
     pub fn tex_id(&self) -> TextureId {
         self.tex_id
     }
+
+    pub fn tileset(&self) -> &Tileset {
+        &self.tileset
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Positions (in game units) where a player can appear.
+    pub fn spawn_points(&self) -> &[Vector2<f32>] {
+        &self.spawns
+    }
+
+    /// Position (in game units) of the center of the given tile.
+    pub fn tile_center(&self, column: u32, row: u32) -> Vector2<f32> {
+        self.tile_center_unchecked(column as i64, row as i64)
+    }
+
+    /// Same as `tile_center` but also accepts tiles
+    /// outside of the map.
+    pub fn tile_center_unchecked(&self, column: i64, row: i64) -> Vector2<f32> {
+        let x = column - (self.width / 2) as i64;
+        let y = (self.height as i64 - 1 - row) - (self.height / 2) as i64;
+        Vector2::new(x as f32 * TILE_SIZE, y as f32 * TILE_SIZE)
+    }
+
+    /// Returns the `(column, row)` of the tile containing
+    /// the given position or `None` if it is outside of the map.
+    pub fn tile_at(&self, position: &Vector2<f32>) -> Option<(u32, u32)> {
+        let (x, y) = self.tile_at_unchecked(position);
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            None
+        } else {
+            Some((x as u32, y as u32))
+        }
+    }
+
+    /// Same as `tile_at` but returns coordinates
+    /// even if the position is outside of the map.
+    pub fn tile_at_unchecked(&self, position: &Vector2<f32>) -> (i64, i64) {
+        let x = (position.x / TILE_SIZE + 0.5).floor() as i64 + (self.width / 2) as i64;
+        let y = (position.y / TILE_SIZE + 0.5).floor() as i64 + (self.height / 2) as i64;
+        (x, self.height as i64 - 1 - y)
+    }
+
+    /// Returns the solid part of the given tile, `None` if the
+    /// tile can be freely walked on. Everything outside
+    /// of the map is blocked.
+    pub fn collision(&self, column: i64, row: i64) -> Option<TileShape> {
+        if column < 0 || row < 0 || column >= self.width as i64 || row >= self.height as i64 {
+            Some(TileShape::full())
+        } else {
+            self.collisions[(row * self.width as i64 + column) as usize]
+        }
+    }
+}
+
+impl FromStr for GameMap {
+    type Err = MapError;
+
+    fn from_str(s: &str) -> Result<GameMap, MapError> {
+        let mut parser = toml::Parser::new(s);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None => {
+                let errors = parser.errors.iter().map(|e| {
+                    let (line, col) = parser.to_linecol(e.lo);
+                    format!("{}:{}: {}", line + 1, col + 1, e.desc)
+                }).collect::<Vec<_>>();
+                return Err(MapError::Syntax(errors.join("\n")));
+            }
+        };
+        let file = try!(MapFile::decode(&mut toml::Decoder::new(toml::Value::Table(table))));
+        GameMap::from_file(file)
+    }
+}
+
+impl GameMap {
+
+    fn from_file(file: MapFile) -> Result<GameMap, MapError> {
+        if file.width == 0 || file.height == 0 {
+            return Err(MapError::Invalid(format!(
+                "map dimensions must be positive, found {}x{}", file.width, file.height)));
+        }
+        if file.tileset.columns == 0 || file.tileset.rows == 0 {
+            return Err(MapError::Invalid(format!(
+                "tileset dimensions must be positive, found {}x{}",
+                file.tileset.columns, file.tileset.rows)));
+        }
+        if file.layer.is_empty() {
+            return Err(MapError::Invalid("a map needs at least one layer".to_string()));
+        }
+
+        let nb_tiles = file.tileset.columns * file.tileset.rows;
+        let mut layers = Vec::with_capacity(file.layer.len());
+        for layer in file.layer {
+            let tiles = try!(parse_tiles(&layer.tiles, file.width, file.height, nb_tiles)
+                .map_err(|e| MapError::Invalid(format!("layer `{}`: {}", layer.name, e))));
+            let pass = match layer.pass.as_ref().map(|p| &p[..]) {
+                None | Some("below") => LayerPass::BelowPlayers,
+                Some("above") => LayerPass::AbovePlayers,
+                Some(other) => return Err(MapError::Invalid(format!(
+                    "layer `{}`: unknown pass `{}`, expected `below` or `above`",
+                    layer.name, other))),
+            };
+            layers.push(Layer {
+                name: layer.name,
+                pass: pass,
+                tiles: tiles,
+            });
+        }
+
+        let collisions = match file.collision {
+            Some(collision) => try!(parse_collisions(collision, file.width, file.height)
+                .map_err(|e| MapError::Invalid(format!("collision: {}", e)))),
+            None => vec![None; (file.width * file.height) as usize],
+        };
+
+        let mut map = GameMap {
+            tex_id: TextureId(0),
+            width: file.width,
+            height: file.height,
+            tileset: Tileset {
+                path: file.tileset.path,
+                columns: file.tileset.columns,
+                rows: file.tileset.rows,
+            },
+            layers: layers,
+            collisions: collisions,
+            spawns: Vec::new(),
+        };
+
+        for spawn in file.spawn.unwrap_or(Vec::new()) {
+            if spawn.column >= map.width || spawn.row >= map.height {
+                return Err(MapError::Invalid(format!(
+                    "spawn point ({}, {}) is outside of the map", spawn.column, spawn.row)));
+            }
+            let position = map.tile_center(spawn.column, spawn.row);
+            map.spawns.push(position);
+        }
+
+        Ok(map)
+    }
+}
+
+// Parse a grid covering the whole map. Rows are separated by new lines,
+// cells by whitespaces and empty lines are ignored.
+fn parse_grid<T, F>(s: &str, width: u32, height: u32, mut parse_cell: F)
+    -> Result<Vec<T>, String>
+    where F: FnMut(&str) -> Result<T, String>
+{
+    let mut cells = Vec::with_capacity((width * height) as usize);
+    let mut nb_rows = 0;
+
+    for line in s.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        nb_rows += 1;
+        let mut nb_columns = 0;
+        for token in line.split_whitespace() {
+            nb_columns += 1;
+            let cell = try!(parse_cell(token).map_err(|e| format!("row {}: {}", nb_rows, e)));
+            cells.push(cell);
+        }
+        if nb_columns != width {
+            return Err(format!("row {} has {} tiles, expected {}", nb_rows, nb_columns, width));
+        }
+    }
+
+    if nb_rows != height {
+        return Err(format!("found {} rows, expected {}", nb_rows, height));
+    }
+
+    Ok(cells)
+}
+
+// Parse the grid of a layer, `.` stands for an empty tile.
+fn parse_tiles(s: &str, width: u32, height: u32, nb_tiles: u32)
+    -> Result<Vec<Option<TileId>>, String>
+{
+    parse_grid(s, width, height, |token| {
+        if token == "." {
+            return Ok(None);
+        }
+        let tile = try!(token.parse::<TileId>().map_err(|_|
+            format!("`{}` is not a tile id", token)));
+        if tile >= nb_tiles {
+            return Err(format!("tile {} is not in the tileset", tile));
+        }
+        Ok(Some(tile))
+    })
+}
+
+// Parse the collision grid, `.` is a free tile, `#` a blocked
+// one and any other symbol must be defined in `shapes`.
+fn parse_collisions(file: CollisionFile, width: u32, height: u32)
+    -> Result<Vec<Option<TileShape>>, String>
+{
+    let mut shapes = HashMap::new();
+    for shape in file.shape.unwrap_or(Vec::new()) {
+        if shape.symbol == "." || shape.symbol == "#" {
+            return Err(format!("the symbol `{}` is reserved", shape.symbol));
+        }
+        let tile_shape = TileShape {
+            x: shape.x,
+            y: shape.y,
+            width: shape.width,
+            height: shape.height,
+        };
+        if !tile_shape.is_valid() {
+            return Err(format!("the shape `{}` doesn't fit in a tile", shape.symbol));
+        }
+        if shapes.insert(shape.symbol.clone(), tile_shape).is_some() {
+            return Err(format!("the shape `{}` is defined twice", shape.symbol));
+        }
+    }
+
+    parse_grid(&file.tiles, width, height, |token| {
+        match token {
+            "." => Ok(None),
+            "#" => Ok(Some(TileShape::full())),
+            symbol => match shapes.get(symbol) {
+                Some(shape) => Ok(Some(*shape)),
+                None => Err(format!("unknown collision shape `{}`", symbol)),
+            }
+        }
+    })
+}
+
+impl Tileset {
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+}
+
+impl TileShape {
+
+    pub fn full() -> TileShape {
+        TileShape {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.x >= 0.0 && self.y >= 0.0 && self.width > 0.0 && self.height > 0.0 &&
+            self.x + self.width <= 1.0 && self.y + self.height <= 1.0
+    }
+}
+
+impl Layer {
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn pass(&self) -> LayerPass {
+        self.pass
+    }
+
+    /// Tiles of the layer, row by row starting from the top.
+    pub fn tiles(&self) -> &[Option<TileId>] {
+        &self.tiles
+    }
+}
+
+impl From<io::Error> for MapError {
+    fn from(e: io::Error) -> MapError {
+        MapError::Io(e)
+    }
+}
+
+impl From<toml::DecodeError> for MapError {
+    fn from(e: toml::DecodeError) -> MapError {
+        MapError::Decode(e)
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapError::Io(ref e) => write!(f, "{}", e),
+            MapError::Syntax(ref e) => write!(f, "{}", e),
+            MapError::Decode(ref e) => write!(f, "{}", e),
+            MapError::Invalid(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for MapError {
+    fn description(&self) -> &str {
+        match *self {
+            MapError::Io(ref e) => e.description(),
+            MapError::Syntax(_) => "invalid map syntax",
+            MapError::Decode(ref e) => e.description(),
+            MapError::Invalid(_) => "invalid map",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use cgmath::Vector2;
+    use super::{GameMap, MapError, LayerPass, TileShape};
+
+    const SMALL_MAP: &'static str = r#"
+        width = 3
+        height = 2
+
+        [tileset]
+        path = "tiles.png"
+        columns = 30
+        rows = 16
+
+        [[layer]]
+        name = "ground"
+        tiles = """
+        6 7 8
+        7 . 6
+        """
+
+        [[layer]]
+        name = "roofs"
+        pass = "above"
+        tiles = """
+        . . .
+        . 12 .
+        """
+
+        [collision]
+        tiles = """
+        # . .
+        . . v
+        """
+
+        [[collision.shape]]
+        symbol = "v"
+        x = 0.0
+        y = 0.0
+        width = 1.0
+        height = 0.5
+
+        [[spawn]]
+        column = 1
+        row = 0
+    "#;
+
+    #[test]
+    fn parse_small_map() {
+        let map: GameMap = SMALL_MAP.parse().unwrap();
+
+        assert_eq!(map.width(), 3);
+        assert_eq!(map.height(), 2);
+        assert_eq!(map.tileset().path(), "tiles.png");
+        assert_eq!(map.layers().len(), 2);
+        assert_eq!(map.layers()[0].name(), "ground");
+        assert_eq!(map.layers()[0].pass(), LayerPass::BelowPlayers);
+        assert_eq!(map.layers()[0].tiles(),
+                   &[Some(6), Some(7), Some(8), Some(7), None, Some(6)]);
+        assert_eq!(map.layers()[1].name(), "roofs");
+        assert_eq!(map.layers()[1].pass(), LayerPass::AbovePlayers);
+        assert_eq!(map.layers()[1].tiles(),
+                   &[None, None, None, None, Some(12), None]);
+        assert_eq!(map.spawn_points(), &[Vector2::new(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn parse_collisions() {
+        let map: GameMap = SMALL_MAP.parse().unwrap();
+        let half = TileShape { x: 0.0, y: 0.0, width: 1.0, height: 0.5 };
+
+        assert_eq!(map.collision(0, 0), Some(TileShape::full()));
+        assert_eq!(map.collision(1, 0), None);
+        assert_eq!(map.collision(2, 1), Some(half));
+        assert_eq!(map.collision(-1, 0), Some(TileShape::full()));
+        assert_eq!(map.collision(0, 2), Some(TileShape::full()));
+    }
+
+    #[test]
+    fn unknown_collision_shape() {
+        let map = SMALL_MAP.replace(". . v", ". . w");
+        match map.parse::<GameMap>() {
+            Err(MapError::Invalid(_)) => (),
+            _ => panic!("expected an invalid map"),
+        }
+    }
+
+    #[test]
+    fn shape_outside_tile() {
+        let map = SMALL_MAP.replace("height = 0.5", "height = 1.5");
+        match map.parse::<GameMap>() {
+            Err(MapError::Invalid(_)) => (),
+            _ => panic!("expected an invalid map"),
+        }
+    }
+
+    #[test]
+    fn tile_coordinates() {
+        let map: GameMap = SMALL_MAP.parse().unwrap();
+
+        for row in 0..map.height() {
+            for column in 0..map.width() {
+                let center = map.tile_center(column, row);
+                assert_eq!(map.tile_at(&center), Some((column, row)));
+            }
+        }
+        assert_eq!(map.tile_at(&Vector2::new(-10.0, 0.0)), None);
+        assert_eq!(map.tile_at(&Vector2::new(0.0, 10.0)), None);
+    }
+
+    #[test]
+    fn wrong_row_length() {
+        let map = SMALL_MAP.replace("7 . 6", "7 6");
+        match map.parse::<GameMap>() {
+            Err(MapError::Invalid(_)) => (),
+            _ => panic!("expected an invalid map"),
+        }
+    }
+
+    #[test]
+    fn wrong_row_count() {
+        let map = SMALL_MAP.replace("7 . 6", "");
+        match map.parse::<GameMap>() {
+            Err(MapError::Invalid(_)) => (),
+            _ => panic!("expected an invalid map"),
+        }
+    }
+
+    #[test]
+    fn unknown_tile() {
+        let map = SMALL_MAP.replace("6 7 8", "6 7 480");
+        match map.parse::<GameMap>() {
+            Err(MapError::Invalid(_)) => (),
+            _ => panic!("expected an invalid map"),
+        }
+    }
+
+    #[test]
+    fn unknown_pass() {
+        let map = SMALL_MAP.replace("pass = \"above\"", "pass = \"over\"");
+        match map.parse::<GameMap>() {
+            Err(MapError::Invalid(_)) => (),
+            _ => panic!("expected an invalid map"),
+        }
+    }
+
+    #[test]
+    fn spawn_outside_map() {
+        let map = SMALL_MAP.replace("row = 0", "row = 2");
+        match map.parse::<GameMap>() {
+            Err(MapError::Invalid(_)) => (),
+            _ => panic!("expected an invalid map"),
+        }
+    }
+
+    #[test]
+    fn missing_field() {
+        let map = SMALL_MAP.replace("width = 3", "");
+        match map.parse::<GameMap>() {
+            Err(MapError::Decode(_)) => (),
+            _ => panic!("expected a decode error"),
+        }
+    }
+
+    #[test]
+    fn syntax_error() {
+        match "width = = 3".parse::<GameMap>() {
+            Err(MapError::Syntax(_)) => (),
+            _ => panic!("expected a syntax error"),
+        }
+    }
 }
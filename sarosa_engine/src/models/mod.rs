@@ -4,3 +4,6 @@ pub mod map;
 //mod tile;
 pub mod settings;
 pub mod game;
+pub mod prediction;
+pub mod interpolation;
+pub mod chat;
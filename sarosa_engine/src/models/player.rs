@@ -2,11 +2,32 @@ use animation::PlayerAnimator;
 use animation::AnimationManager;
 use animation::TextureId;
 use cgmath::Vector2;
+use models::interpolation::SnapshotBuffer;
 
 pub struct Player {
     pub position: Vector2<f32>,
     pub speed: Vector2<f32>,
     pub animator: PlayerAnimator,
+    /// Updates received from the server, only used for remote players.
+    pub snapshots: SnapshotBuffer,
+    /// Last thing said, shown in a bubble for a while.
+    pub speech: Option<Speech>,
+    pub info: PlayerInfo,
+}
+
+/// What the server tells about a player when it appears.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PlayerInfo {
+    /// Shown above the player, can be empty.
+    pub name: String,
+    /// Health points.
+    pub health: u64,
+}
+
+pub struct Speech {
+    pub text: String,
+    /// Time of the game clock when the bubble disappears.
+    pub until: u64,
 }
 
 pub type PlayerId = u64;
@@ -25,7 +46,10 @@ impl Player {
         Player {
             position: pos,
             speed: speed,
-            animator: PlayerAnimator::new(tex_id, anim_manager)
+            animator: PlayerAnimator::new(tex_id, anim_manager),
+            snapshots: SnapshotBuffer::new(),
+            speech: None,
+            info: PlayerInfo::default(),
         }
     }
 }
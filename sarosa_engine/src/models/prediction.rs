@@ -0,0 +1,320 @@
+use std::collections::VecDeque;
+use cgmath::Vector2;
+use cgmath::Zero;
+use cgmath::InnerSpace;
+
+use events::UserEventType;
+use events::commands::{CommandStates, Direction};
+use models::map::GameMap;
+use physics::PhysicsEngine;
+use physics::WALK_SPEED;
+
+/// Sequence number of an input. It is incremented each time the
+/// walking direction changes, which is also when `UserEventSender`
+/// writes a new `Order::Walk` to the server.
+pub type InputSeq = u32;
+
+// Steps are kept this long at most, should the server never
+// acknowledge an input, such as a walk against a wall.
+const MAX_HISTORY_NS: u64 = 5_000_000_000;
+
+// An input the server hasn't acknowledged yet.
+struct Input {
+    seq: InputSeq,
+    direction: Option<Direction>,
+}
+
+// One fixed update of the local player.
+struct Step {
+    seq: InputSeq,
+    velocity: Vector2<f32>,
+    duration: u64,
+    // Value of the predictor clock at the end of the step.
+    end: u64,
+}
+
+/// Client side prediction for `THIS_PLAYER`.
+///
+/// Movement commands are applied as soon as they are pressed and
+/// every simulated step is kept, numbered by its input, until the
+/// server acknowledges that input. When an authoritative position
+/// arrives, the steps of the inputs still pending are replayed on
+/// top of it.
+///
+/// The protocol doesn't echo the sequence numbers yet, only the speed
+/// the server gives to the player: the oldest pending input walking
+/// that way is the one it has handled, see `reconcile`. Once the
+/// sequence number is echoed, it can be given to `acknowledge`.
+pub struct Predictor {
+    commands: CommandStates,
+    seq: InputSeq,
+    direction: Option<Direction>,
+    velocity: Vector2<f32>,
+    walk_speed: f32,
+    inputs: VecDeque<Input>,
+    history: VecDeque<Step>,
+    clock: u64,
+}
+
+impl Predictor {
+
+    pub fn new() -> Predictor {
+        Predictor {
+            commands: CommandStates::new(),
+            seq: 0,
+            direction: None,
+            velocity: Vector2::zero(),
+            walk_speed: WALK_SPEED,
+            inputs: VecDeque::new(),
+            history: VecDeque::new(),
+            clock: 0,
+        }
+    }
+
+    /// Sequence number of the current input.
+    pub fn seq(&self) -> InputSeq {
+        self.seq
+    }
+
+    /// Velocity predicted for the local player, in game units per second.
+    pub fn velocity(&self) -> Vector2<f32> {
+        self.velocity
+    }
+
+    /// Apply a movement command, other commands are ignored.
+    pub fn push_command(&mut self, command: UserEventType, pressed: bool) {
+        self.commands.inject(command, !pressed);
+        let direction = self.commands.next_direction();
+        if direction != self.direction {
+            self.direction = direction;
+            self.seq = self.seq.wrapping_add(1);
+            self.inputs.push_back(Input {
+                seq: self.seq,
+                direction: direction,
+            });
+        }
+        self.update_velocity();
+    }
+
+    /// Move the local player by `time_elapsed` nanoseconds and record the step.
+    pub fn step(
+        &mut self,
+        physics: &PhysicsEngine,
+        map: &GameMap,
+        position: &mut Vector2<f32>,
+        time_elapsed: u64)
+    {
+        self.clock += time_elapsed;
+        *position = physics.resolve_move(map, position, &self.offset(self.velocity, time_elapsed));
+        // The server already knows what an acknowledged input does.
+        if !self.inputs.is_empty() {
+            self.history.push_back(Step {
+                seq: self.seq,
+                velocity: self.velocity,
+                duration: time_elapsed,
+                end: self.clock,
+            });
+        }
+        let oldest = self.clock.saturating_sub(MAX_HISTORY_NS);
+        while self.history.front().map_or(false, |s| s.end <= oldest) {
+            self.history.pop_front();
+        }
+    }
+
+    /// The server has handled the input `seq`, and the ones before it.
+    pub fn acknowledge(&mut self, seq: InputSeq) {
+        while self.inputs.front().map_or(false, |i| !is_after(i.seq, seq)) {
+            self.inputs.pop_front();
+        }
+        while self.history.front().map_or(false, |s| !is_after(s.seq, seq)) {
+            self.history.pop_front();
+        }
+    }
+
+    /// Returns the predicted position given the authoritative
+    /// position and speed just received from the server.
+    pub fn reconcile(
+        &mut self,
+        physics: &PhysicsEngine,
+        map: &GameMap,
+        server_pos: &Vector2<f32>,
+        server_speed: &Vector2<f32>) -> Vector2<f32>
+    {
+        // The server knows better how fast we can walk.
+        let speed = server_speed.magnitude();
+        if speed > 0.0 && speed != self.walk_speed {
+            self.walk_speed = speed;
+            self.update_velocity();
+        }
+
+        // The way the server moves the player echoes the input it handled.
+        let echo = Direction::of_speed(*server_speed);
+        let acknowledged = self.inputs.iter().find(|i| i.direction == echo).map(|i| i.seq);
+        if let Some(seq) = acknowledged {
+            self.acknowledge(seq);
+        }
+        if let Some(step) = self.history.front() {
+            trace!("Replaying inputs from seq {}", step.seq);
+        }
+
+        let mut position = *server_pos;
+        for step in self.history.iter() {
+            position = physics.resolve_move(map, &position, &self.offset(step.velocity, step.duration));
+        }
+        position
+    }
+
+    fn update_velocity(&mut self) {
+        self.velocity = match self.direction {
+            Some(direction) => direction.to_vector() * self.walk_speed,
+            None => Vector2::zero(),
+        };
+    }
+
+    fn offset(&self, velocity: Vector2<f32>, duration: u64) -> Vector2<f32> {
+        velocity * ((duration as f64 / 1e9) as f32)
+    }
+}
+
+// Whether the input `seq` comes after `other`, they wrap around.
+fn is_after(seq: InputSeq, other: InputSeq) -> bool {
+    (seq.wrapping_sub(other) as i32) > 0
+}
+
+#[cfg(test)]
+mod test {
+
+    use cgmath::Vector2;
+    use cgmath::Zero;
+    use events::UserEventType;
+    use models::map::GameMap;
+    use physics::PhysicsEngine;
+    use physics::WALK_SPEED;
+    use super::Predictor;
+
+    const MAP: &'static str = r#"
+        width = 20
+        height = 20
+
+        [tileset]
+        path = "tiles.png"
+        columns = 1
+        rows = 1
+
+        [[layer]]
+        name = "ground"
+        tiles = """
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        . . . . . . . . . . . . . . . . . . . .
+        """
+    "#;
+
+    const STEP: u64 = 10_000_000;
+
+    #[test]
+    fn commands_move_immediately() {
+        let map: GameMap = MAP.parse().unwrap();
+        let physics = PhysicsEngine::new();
+        let mut predictor = Predictor::new();
+        let mut pos = Vector2::zero();
+
+        predictor.push_command(UserEventType::CmdRight, true);
+        assert_eq!(predictor.seq(), 1);
+        predictor.step(&physics, &map, &mut pos, STEP);
+        assert_relative_eq!(pos.x, WALK_SPEED * 0.01, epsilon = 1e-4);
+
+        // Zoom doesn't change the direction so it isn't a new input.
+        predictor.push_command(UserEventType::ZoomIn, true);
+        assert_eq!(predictor.seq(), 1);
+        assert_eq!(predictor.velocity(), Vector2::new(WALK_SPEED, 0.0));
+    }
+
+    #[test]
+    fn replay_unacknowledged_steps() {
+        let map: GameMap = MAP.parse().unwrap();
+        let physics = PhysicsEngine::new();
+        let mut predictor = Predictor::new();
+        let mut pos = Vector2::zero();
+
+        predictor.push_command(UserEventType::CmdRight, true);
+        for _ in 0..10 {
+            predictor.step(&physics, &map, &mut pos, STEP);
+        }
+        predictor.push_command(UserEventType::CmdUp, true);
+        assert_eq!(predictor.seq(), 2);
+        for _ in 0..5 {
+            predictor.step(&physics, &map, &mut pos, STEP);
+        }
+
+        // The server still walks east: only the walk north
+        // is replayed from the authoritative position.
+        let east = Vector2::new(WALK_SPEED, 0.0);
+        let reconciled = predictor.reconcile(&physics, &map, &Vector2::new(1.0, 0.0), &east);
+        assert_relative_eq!(reconciled.x, 1.0, epsilon = 1e-4);
+        assert_relative_eq!(reconciled.y, WALK_SPEED * 0.05, epsilon = 1e-4);
+
+        // Now everything is acknowledged.
+        let north = Vector2::new(0.0, WALK_SPEED);
+        let reconciled = predictor.reconcile(&physics, &map, &Vector2::new(1.0, 2.0), &north);
+        assert_eq!(reconciled, Vector2::new(1.0, 2.0));
+    }
+
+    #[test]
+    fn acknowledge_by_sequence() {
+        let map: GameMap = MAP.parse().unwrap();
+        let physics = PhysicsEngine::new();
+        let mut predictor = Predictor::new();
+        let mut pos = Vector2::zero();
+
+        predictor.push_command(UserEventType::CmdRight, true);
+        predictor.step(&physics, &map, &mut pos, STEP);
+        predictor.push_command(UserEventType::CmdRight, false);
+        predictor.push_command(UserEventType::CmdDown, true);
+        predictor.step(&physics, &map, &mut pos, STEP);
+        predictor.acknowledge(1);
+
+        // The server stands the player still, the walk south is still pending.
+        let still = Vector2::zero();
+        let reconciled = predictor.reconcile(&physics, &map, &Vector2::new(1.0, 1.0), &still);
+        assert_relative_eq!(reconciled.x, 1.0, epsilon = 1e-4);
+        assert_relative_eq!(reconciled.y, 1.0 - WALK_SPEED * 0.01, epsilon = 1e-4);
+
+        predictor.acknowledge(3);
+        let reconciled = predictor.reconcile(&physics, &map, &Vector2::new(1.0, 1.0), &still);
+        assert_eq!(reconciled, Vector2::new(1.0, 1.0));
+    }
+
+    #[test]
+    fn release_stops_the_player() {
+        let map: GameMap = MAP.parse().unwrap();
+        let physics = PhysicsEngine::new();
+        let mut predictor = Predictor::new();
+        let mut pos = Vector2::zero();
+
+        predictor.push_command(UserEventType::CmdUp, true);
+        predictor.push_command(UserEventType::CmdUp, false);
+        assert_eq!(predictor.seq(), 2);
+        assert_eq!(predictor.velocity(), Vector2::zero());
+
+        predictor.step(&physics, &map, &mut pos, STEP);
+        assert_eq!(pos, Vector2::zero());
+    }
+}
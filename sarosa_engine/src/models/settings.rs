@@ -1,8 +1,18 @@
 use std::rc::Rc;
 use std::ops::Deref;
 use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::collections::hash_map::Entry;
 use std::net::ToSocketAddrs;
 use std::io;
+use std::io::{Read, Write};
+use std::hash::Hash;
+use std::fs::File;
+use std::fmt;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+use toml;
 use std::cell::{
     RefCell,
     Ref,
@@ -12,10 +22,56 @@ use glium::glutin::{
     VirtualKeyCode
 };
 use events::UserEventType;
+use events::keys::{key_from_name, key_name};
+use events::gamepad::{GamepadButton, DEFAULT_DEAD_ZONE};
+use models::interpolation::DEFAULT_RENDER_DELAY_NS;
 
+/// Default location of the settings file.
+pub const SETTINGS_PATH: &'static str = "./settings.toml";
+
+/// Version of the settings file format. It must be incremented
+/// when the meaning of an existing entry changes.
+pub const SETTINGS_VERSION: i64 = 1;
+
+/// Server used when none is given.
+pub const DEFAULT_ADDRESS: &'static str = "localhost:7777";
+
+/// All the settings of the client.
+///
+/// They can be read from and written to a toml file looking like:
+///
+/// ```toml
+/// version = 1
+///
+/// [window]
+/// width = 800
+/// height = 600
+/// fullscreen = false
+/// vsync = false
+///
+/// [network]
+/// address = "localhost:7777"
+/// render_delay_ms = 100
+///
+/// [keyboard]
+/// up = ["Up", "W"]
+/// attack = "Space"
+///
+/// [gamepad]
+/// dead_zone = 0.25
+///
+/// [gamepad.bindings]
+/// attack = "South"
+/// ```
+///
+/// Missing entries keep their default value, the bindings too: an action
+/// that is listed is bound to its inputs only, `[]` to unbind it, and the
+/// other ones keep their default inputs. This way files saved before an
+/// action was added still bind it.
 #[derive(Debug, Clone)]
 pub struct Settings {
     keyboard: Rc<RefCell<KeyboardSettings>>,
+    gamepad: Rc<RefCell<GamepadSettings>>,
     window: Rc<RefCell<WindowSettings>>,
     network: Rc<RefCell<NetworkSettings>>,
 }
@@ -23,31 +79,83 @@ pub struct Settings {
 #[derive(Debug)]
 pub struct KeyboardSettings(HashMap<VirtualKeyCode, UserEventType>);
 
+/// Button bindings of the gamepads. The left stick always
+/// moves the player, as long as it is out of the dead zone.
+#[derive(Debug)]
+pub struct GamepadSettings {
+    dead_zone: f32,
+    bindings: HashMap<GamepadButton, UserEventType>,
+}
+
 #[derive(Debug)]
 pub struct WindowSettings {
     width: u32,
     height: u32,
+    fullscreen: bool,
+    vsync: bool,
+    display_mode: DisplayMode,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DisplayMode {
+    /// A visible window.
+    Windowed,
+    /// An offscreen context, nothing is shown on screen and
+    /// there is no input. Useful to run the renderer without a GPU.
+    Headless,
 }
 
 #[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    /// The file isn't a valid toml file.
+    Syntax(String),
+    /// The content of the file is invalid.
+    Invalid(String),
+}
+
+#[derive(Debug, Clone)]
 pub struct NetworkSettings {
     address: String,
     offline_server: bool,
+    render_delay: u64,
+    record_path: Option<String>,
+    replay_path: Option<String>,
+    replay_speed: f32,
 }
 
 impl Settings {
     pub fn new(addr: String, offline_server: bool) -> Settings {
         Settings {
             keyboard: Rc::new(RefCell::new(KeyboardSettings::new())),
+            gamepad: Rc::new(RefCell::new(GamepadSettings::new())),
             window: Rc::new(RefCell::new(WindowSettings::new())),
             network: Rc::new(RefCell::new(NetworkSettings::new(addr, offline_server))),
         }
     }
 
+    /// Read the settings from a file, see `Settings` for the format.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Settings, SettingsError> {
+        let mut content = String::new();
+        let mut file = try!(File::open(path));
+        try!(file.read_to_string(&mut content));
+        content.parse()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SettingsError> {
+        let mut file = try!(File::create(path));
+        try!(file.write_all(self.to_string().as_bytes()));
+        Ok(())
+    }
+
     pub fn keyboard<'a>(&'a self) -> Ref<'a, KeyboardSettings> {
         self.keyboard.borrow()
     }
 
+    pub fn gamepad<'a>(&'a self) -> Ref<'a, GamepadSettings> {
+        self.gamepad.borrow()
+    }
+
     pub fn network<'a>(&'a self) -> Ref<'a, NetworkSettings> {
         self.network.borrow()
     }
@@ -60,13 +168,80 @@ impl Settings {
         self.keyboard.borrow_mut()
     }
 
+    pub fn gamepad_mut<'a>(&'a mut self) -> RefMut<'a, GamepadSettings> {
+        self.gamepad.borrow_mut()
+    }
+
     pub fn window_mut<'a>(&'a mut self) -> RefMut<'a, WindowSettings> {
         self.window.borrow_mut()
     }
 
+    pub fn network_mut<'a>(&'a mut self) -> RefMut<'a, NetworkSettings> {
+        self.network.borrow_mut()
+    }
+
     pub fn all_mut<'a>(&'a mut self) -> (RefMut<'a, WindowSettings>, RefMut<'a, KeyboardSettings>) {
         (self.window.borrow_mut(), self.keyboard.borrow_mut())
     }
+
+    fn from_toml(table: &toml::Table) -> Result<Settings, SettingsError> {
+        let version = match table.get("version") {
+            Some(&toml::Value::Integer(version)) => version,
+            Some(_) => return Err(invalid("`version` must be an integer")),
+            None => return Err(invalid("missing `version`")),
+        };
+        if version < 1 || version > SETTINGS_VERSION {
+            return Err(invalid(format!(
+                "unsupported settings version {} (expected at most {})", version, SETTINGS_VERSION)));
+        }
+
+        let settings = Settings::default();
+
+        if let Some(window) = try!(section(table, "window")) {
+            try!(settings.window.borrow_mut().merge(window));
+        }
+        if let Some(network) = try!(section(table, "network")) {
+            try!(settings.network.borrow_mut().merge(network));
+        }
+        if let Some(keyboard) = try!(section(table, "keyboard")) {
+            try!(settings.keyboard.borrow_mut().merge(keyboard));
+        }
+        if let Some(gamepad) = try!(section(table, "gamepad")) {
+            try!(settings.gamepad.borrow_mut().merge(gamepad));
+        }
+
+        Ok(settings)
+    }
+
+    fn to_toml(&self) -> toml::Table {
+        let mut table = BTreeMap::new();
+        table.insert("version".to_string(), toml::Value::Integer(SETTINGS_VERSION));
+        table.insert("window".to_string(), toml::Value::Table(self.window().to_toml()));
+        table.insert("network".to_string(), toml::Value::Table(self.network().to_toml()));
+        table.insert("keyboard".to_string(), toml::Value::Table(self.keyboard().to_toml()));
+        table.insert("gamepad".to_string(), toml::Value::Table(self.gamepad().to_toml()));
+        table
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings::new(DEFAULT_ADDRESS.to_string(), false)
+    }
+}
+
+impl FromStr for Settings {
+    type Err = SettingsError;
+
+    fn from_str(s: &str) -> Result<Settings, SettingsError> {
+        Settings::from_toml(&try!(parse_toml(s)))
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", toml::Value::Table(self.to_toml()))
+    }
 }
 
 impl NetworkSettings {
@@ -75,16 +250,85 @@ impl NetworkSettings {
         NetworkSettings {
             address: addr,
             offline_server: offline_server,
+            render_delay: DEFAULT_RENDER_DELAY_NS,
+            record_path: None,
+            replay_path: None,
+            replay_speed: 1.0,
         }
     }
 
+    /// How far in the past remote players are rendered, in nanoseconds.
+    /// Bigger values hide more network jitter but add visible lag.
+    pub fn render_delay(&self) -> u64 {
+        self.render_delay
+    }
+
+    pub fn set_render_delay(&mut self, render_delay: u64) {
+        self.render_delay = render_delay;
+    }
+
     pub fn offline_server(&self) -> bool {
         self.offline_server
     }
 
+    pub fn set_offline_server(&mut self, offline_server: bool) {
+        self.offline_server = offline_server;
+    }
+
     pub fn addr(&self) -> &str {
         self.address.deref()
     }
+
+    pub fn set_addr(&mut self, addr: String) {
+        self.address = addr;
+    }
+
+    /// File where what the server says is recorded, if any.
+    pub fn record_path(&self) -> Option<&str> {
+        self.record_path.as_ref().map(|path| path.deref())
+    }
+
+    pub fn set_record_path(&mut self, path: Option<String>) {
+        self.record_path = path;
+    }
+
+    /// Recording played instead of connecting to a server, if any.
+    pub fn replay_path(&self) -> Option<&str> {
+        self.replay_path.as_ref().map(|path| path.deref())
+    }
+
+    pub fn set_replay_path(&mut self, path: Option<String>) {
+        self.replay_path = path;
+    }
+
+    /// How many times faster than it was recorded a recording is played.
+    pub fn replay_speed(&self) -> f32 {
+        self.replay_speed
+    }
+
+    pub fn set_replay_speed(&mut self, speed: f32) {
+        self.replay_speed = speed;
+    }
+
+    // Whether the server is offline, recorded or replayed is
+    // decided each time the client is started, so it isn't saved.
+    fn merge(&mut self, table: &toml::Table) -> Result<(), SettingsError> {
+        if let Some(address) = try!(get_str(table, "network", "address")) {
+            self.address = address.to_string();
+        }
+        if let Some(delay) = try!(get_u32(table, "network", "render_delay_ms")) {
+            self.render_delay = delay as u64 * 1_000_000;
+        }
+        Ok(())
+    }
+
+    fn to_toml(&self) -> toml::Table {
+        let mut table = BTreeMap::new();
+        table.insert("address".to_string(), toml::Value::String(self.address.clone()));
+        table.insert("render_delay_ms".to_string(),
+                     toml::Value::Integer((self.render_delay / 1_000_000) as i64));
+        table
+    }
 }
 
 impl ToSocketAddrs for NetworkSettings {
@@ -103,8 +347,6 @@ impl KeyboardSettings {
         ///////////////////////////////////////////////////////////////
         // Default bindings for Keyboards Events
         //
-        // TODO(Nemikolh): Read that config from a settings file.
-        //
         hm.insert(VirtualKeyCode::Up, UserEventType::CmdUp);
         hm.insert(VirtualKeyCode::Down, UserEventType::CmdDown);
         hm.insert(VirtualKeyCode::Left, UserEventType::CmdLeft);
@@ -112,11 +354,38 @@ impl KeyboardSettings {
         hm.insert(VirtualKeyCode::I, UserEventType::ZoomIn);
         hm.insert(VirtualKeyCode::O, UserEventType::ZoomOut);
         hm.insert(VirtualKeyCode::Space, UserEventType::Attack);
+        hm.insert(VirtualKeyCode::F12, UserEventType::Screenshot);
+        hm.insert(VirtualKeyCode::Return, UserEventType::Chat);
+        hm.insert(VirtualKeyCode::PageUp, UserEventType::ChatScrollUp);
+        hm.insert(VirtualKeyCode::PageDown, UserEventType::ChatScrollDown);
+        hm.insert(VirtualKeyCode::Escape, UserEventType::Menu);
         //
         ///////////////////////////////////////////////////////////////
         KeyboardSettings(hm)
     }
 
+    fn merge(&mut self, bindings: &toml::Table) -> Result<(), SettingsError> {
+        merge_bindings(&mut self.0, bindings, "key", key_from_name)
+    }
+
+    fn to_toml(&self) -> toml::Table {
+        bindings_to_toml(&self.0, &KeyboardSettings::new().0, key_name)
+    }
+
+    /// Bind `key` to `event`, returns the event the key was bound to.
+    pub fn bind(&mut self, key: VirtualKeyCode, event: UserEventType) -> Option<UserEventType> {
+        self.0.insert(key, event)
+    }
+
+    pub fn unbind(&mut self, key: VirtualKeyCode) -> Option<UserEventType> {
+        self.0.remove(&key)
+    }
+
+    /// All the keys bound to `event`.
+    pub fn keys_for(&self, event: UserEventType) -> Vec<VirtualKeyCode> {
+        self.0.iter().filter(|&(_, e)| *e == event).map(|(k, _)| *k).collect()
+    }
+
     pub fn get(&self, key: Option<VirtualKeyCode>) -> Option<UserEventType> {
         if let Some(k) = key {
             self.0.get(&k).map(|e| *e)
@@ -126,15 +395,100 @@ impl KeyboardSettings {
     }
 }
 
+impl GamepadSettings {
+
+    pub fn new() -> GamepadSettings {
+        let mut hm = HashMap::new();
+        hm.insert(GamepadButton::DPadUp, UserEventType::CmdUp);
+        hm.insert(GamepadButton::DPadDown, UserEventType::CmdDown);
+        hm.insert(GamepadButton::DPadLeft, UserEventType::CmdLeft);
+        hm.insert(GamepadButton::DPadRight, UserEventType::CmdRight);
+        hm.insert(GamepadButton::RightTrigger, UserEventType::ZoomIn);
+        hm.insert(GamepadButton::LeftTrigger, UserEventType::ZoomOut);
+        hm.insert(GamepadButton::South, UserEventType::Attack);
+        hm.insert(GamepadButton::Start, UserEventType::Menu);
+        GamepadSettings {
+            dead_zone: DEFAULT_DEAD_ZONE,
+            bindings: hm,
+        }
+    }
+
+    /// Stick deflection, between 0 and 1, under which
+    /// a stick is considered centered.
+    #[inline]
+    pub fn dead_zone(&self) -> f32 {
+        self.dead_zone
+    }
+
+    pub fn set_dead_zone(&mut self, dead_zone: f32) {
+        self.dead_zone = dead_zone;
+    }
+
+    /// Bind `button` to `event`, returns the event the button was bound to.
+    pub fn bind(&mut self, button: GamepadButton, event: UserEventType) -> Option<UserEventType> {
+        self.bindings.insert(button, event)
+    }
+
+    pub fn unbind(&mut self, button: GamepadButton) -> Option<UserEventType> {
+        self.bindings.remove(&button)
+    }
+
+    pub fn get(&self, button: GamepadButton) -> Option<UserEventType> {
+        self.bindings.get(&button).map(|e| *e)
+    }
+
+    fn merge(&mut self, table: &toml::Table) -> Result<(), SettingsError> {
+        match table.get("dead_zone") {
+            Some(&toml::Value::Float(dead_zone)) if dead_zone >= 0.0 && dead_zone < 1.0 => {
+                self.dead_zone = dead_zone as f32;
+            }
+            Some(_) => return Err(invalid("`gamepad.dead_zone` must be a number in [0, 1)")),
+            None => (),
+        }
+        if let Some(bindings) = try!(section(table, "bindings")) {
+            try!(merge_bindings(&mut self.bindings, bindings, "button", |name| {
+                GamepadButton::from_name(name)
+            }));
+        }
+        Ok(())
+    }
+
+    fn to_toml(&self) -> toml::Table {
+        let mut table = BTreeMap::new();
+        table.insert("dead_zone".to_string(), toml::Value::Float(self.dead_zone as f64));
+        table.insert("bindings".to_string(),
+                     toml::Value::Table(bindings_to_toml(&self.bindings, &GamepadSettings::new().bindings,
+                                                         |b| Some(b.name()))));
+        table
+    }
+}
+
 impl WindowSettings {
 
     pub fn new() -> WindowSettings {
         WindowSettings {
             width: 800,
             height: 600,
+            fullscreen: false,
+            vsync: false,
+            display_mode: DisplayMode::Windowed,
         }
     }
 
+    #[inline]
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display_mode
+    }
+
+    pub fn set_display_mode(&mut self, display_mode: DisplayMode) {
+        self.display_mode = display_mode;
+    }
+
+    pub fn set_dimensions(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
     #[inline]
     pub fn width(&self) -> u32 {
         self.width
@@ -144,4 +498,363 @@ impl WindowSettings {
     pub fn height(&self) -> u32 {
         self.height
     }
+
+    #[inline]
+    pub fn fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+    }
+
+    #[inline]
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.vsync = vsync;
+    }
+
+    // The display mode only makes sense for tests and tools,
+    // so it isn't saved.
+    fn merge(&mut self, table: &toml::Table) -> Result<(), SettingsError> {
+        if let Some(width) = try!(get_u32(table, "window", "width")) {
+            self.width = width;
+        }
+        if let Some(height) = try!(get_u32(table, "window", "height")) {
+            self.height = height;
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(invalid("the window can't be empty"));
+        }
+        if let Some(fullscreen) = try!(get_bool(table, "window", "fullscreen")) {
+            self.fullscreen = fullscreen;
+        }
+        if let Some(vsync) = try!(get_bool(table, "window", "vsync")) {
+            self.vsync = vsync;
+        }
+        Ok(())
+    }
+
+    fn to_toml(&self) -> toml::Table {
+        let mut table = BTreeMap::new();
+        table.insert("width".to_string(), toml::Value::Integer(self.width as i64));
+        table.insert("height".to_string(), toml::Value::Integer(self.height as i64));
+        table.insert("fullscreen".to_string(), toml::Value::Boolean(self.fullscreen));
+        table.insert("vsync".to_string(), toml::Value::Boolean(self.vsync));
+        table
+    }
+}
+
+fn parse_toml(s: &str) -> Result<toml::Table, SettingsError> {
+    let mut parser = toml::Parser::new(s);
+    match parser.parse() {
+        Some(table) => Ok(table),
+        None => {
+            let errors = parser.errors.iter().map(|e| {
+                let (line, col) = parser.to_linecol(e.lo);
+                format!("{}:{}: {}", line + 1, col + 1, e.desc)
+            }).collect::<Vec<_>>();
+            Err(SettingsError::Syntax(errors.join("\n")))
+        }
+    }
+}
+
+// Each action is bound to an input name or to a list of input names,
+// `kind` is used in error messages.
+fn parse_bindings<K, F>(bindings: &toml::Table, kind: &str, from_name: F)
+    -> Result<HashMap<K, UserEventType>, SettingsError>
+    where K: Eq + Hash,
+          F: Fn(&str) -> Option<K>
+{
+    let mut hm = HashMap::new();
+    for (action, inputs) in bindings.iter() {
+        let event = try!(UserEventType::from_name(action).ok_or_else(||
+            invalid(format!("unknown action `{}`", action))));
+        let inputs = match *inputs {
+            toml::Value::String(ref input) => vec![input.clone()],
+            toml::Value::Array(ref inputs) => try!(inputs.iter().map(|i| {
+                i.as_str().map(|i| i.to_string()).ok_or_else(||
+                    invalid(format!("`{}`: {}s must be strings", action, kind)))
+            }).collect()),
+            _ => return Err(invalid(format!(
+                "`{}`: expected a {1} or a list of {1}s", action, kind))),
+        };
+        for name in inputs {
+            let input = try!(from_name(&name).ok_or_else(||
+                invalid(format!("`{}`: unknown {} `{}`", action, kind, name))));
+            match hm.entry(input) {
+                Entry::Occupied(e) => return Err(invalid(format!(
+                    "{} `{}` is bound to both `{}` and `{}`",
+                    kind, name, e.get().name(), action))),
+                Entry::Vacant(e) => { e.insert(event); }
+            }
+        }
+    }
+    Ok(hm)
+}
+
+// The actions listed in `table` lose their current inputs for the ones
+// it gives, the other ones keep them.
+fn merge_bindings<K, F>(bindings: &mut HashMap<K, UserEventType>, table: &toml::Table,
+                        kind: &str, from_name: F) -> Result<(), SettingsError>
+    where K: Eq + Hash,
+          F: Fn(&str) -> Option<K>
+{
+    let saved = try!(parse_bindings(table, kind, from_name));
+    let listed: Vec<UserEventType> = table.keys()
+        .filter_map(|action| UserEventType::from_name(action))
+        .collect();
+    let kept: HashMap<K, UserEventType> = bindings.drain()
+        .filter(|&(_, event)| !listed.contains(&event))
+        .collect();
+    *bindings = kept;
+    bindings.extend(saved);
+    Ok(())
+}
+
+// The actions bound by default but not anymore are
+// written with no input, not to be bound again.
+fn bindings_to_toml<K, F>(bindings: &HashMap<K, UserEventType>, defaults: &HashMap<K, UserEventType>, name: F)
+    -> toml::Table
+    where K: Eq + Hash + Copy,
+          F: Fn(K) -> Option<&'static str>
+{
+    let mut actions: BTreeMap<&'static str, Vec<&'static str>> = BTreeMap::new();
+    for event in defaults.values() {
+        actions.insert(event.name(), Vec::new());
+    }
+    for (&input, event) in bindings.iter() {
+        if let Some(name) = name(input) {
+            actions.entry(event.name()).or_insert(Vec::new()).push(name);
+        }
+    }
+
+    actions.into_iter().map(|(action, mut inputs)| {
+        inputs.sort();
+        let inputs = inputs.into_iter().map(|i| toml::Value::String(i.to_string())).collect();
+        (action.to_string(), toml::Value::Array(inputs))
+    }).collect()
+}
+
+fn section<'a>(table: &'a toml::Table, name: &str) -> Result<Option<&'a toml::Table>, SettingsError> {
+    match table.get(name) {
+        Some(&toml::Value::Table(ref section)) => Ok(Some(section)),
+        Some(_) => Err(invalid(format!("`{}` must be a table", name))),
+        None => Ok(None),
+    }
+}
+
+fn get_str<'a>(table: &'a toml::Table, section: &str, key: &str)
+    -> Result<Option<&'a str>, SettingsError>
+{
+    match table.get(key) {
+        Some(&toml::Value::String(ref value)) => Ok(Some(&value[..])),
+        Some(_) => Err(invalid(format!("`{}.{}` must be a string", section, key))),
+        None => Ok(None),
+    }
+}
+
+fn get_u32(table: &toml::Table, section: &str, key: &str) -> Result<Option<u32>, SettingsError> {
+    match table.get(key) {
+        Some(&toml::Value::Integer(value)) if value >= 0 && value <= ::std::u32::MAX as i64 => {
+            Ok(Some(value as u32))
+        }
+        Some(_) => Err(invalid(format!("`{}.{}` must be a positive integer", section, key))),
+        None => Ok(None),
+    }
+}
+
+fn get_bool(table: &toml::Table, section: &str, key: &str) -> Result<Option<bool>, SettingsError> {
+    match table.get(key) {
+        Some(&toml::Value::Boolean(value)) => Ok(Some(value)),
+        Some(_) => Err(invalid(format!("`{}.{}` must be a boolean", section, key))),
+        None => Ok(None),
+    }
+}
+
+fn invalid<S: Into<String>>(reason: S) -> SettingsError {
+    SettingsError::Invalid(reason.into())
+}
+
+impl From<io::Error> for SettingsError {
+    fn from(e: io::Error) -> SettingsError {
+        SettingsError::Io(e)
+    }
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SettingsError::Io(ref e) => write!(f, "{}", e),
+            SettingsError::Syntax(ref e) => write!(f, "{}", e),
+            SettingsError::Invalid(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SettingsError {
+    fn description(&self) -> &str {
+        match *self {
+            SettingsError::Io(ref e) => e.description(),
+            SettingsError::Syntax(_) => "invalid settings syntax",
+            SettingsError::Invalid(_) => "invalid settings",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use glium::glutin::VirtualKeyCode;
+    use events::UserEventType;
+    use events::gamepad::GamepadButton;
+    use super::{Settings, SettingsError, DEFAULT_ADDRESS};
+
+    fn assert_invalid(content: &str) {
+        match content.parse::<Settings>() {
+            Err(SettingsError::Invalid(_)) => (),
+            other => panic!("expected invalid settings, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_bindings() {
+        let settings: Settings = r#"
+            version = 1
+            [keyboard]
+            up = ["Up", "W"]
+            attack = "Space"
+        "#.parse().unwrap();
+
+        let keyboard = settings.keyboard();
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::Up)), Some(UserEventType::CmdUp));
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::W)), Some(UserEventType::CmdUp));
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::Space)), Some(UserEventType::Attack));
+        // Not listed, bound by default.
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::Down)), Some(UserEventType::CmdDown));
+    }
+
+    #[test]
+    fn merge_bindings() {
+        // Saved before the chat and the menu had a key.
+        let settings: Settings = r#"
+            version = 1
+            [keyboard]
+            up = "W"
+            down = "Down"
+            zoom_in = []
+            attack = "Return"
+            [gamepad.bindings]
+            attack = "East"
+        "#.parse().unwrap();
+
+        let keyboard = settings.keyboard();
+        assert_eq!(keyboard.keys_for(UserEventType::CmdUp), vec![VirtualKeyCode::W]);
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::Up)), None);
+        assert_eq!(keyboard.keys_for(UserEventType::ZoomIn), vec![]);
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::Escape)), Some(UserEventType::Menu));
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::PageUp)), Some(UserEventType::ChatScrollUp));
+        // What is listed wins over the defaults.
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::Return)), Some(UserEventType::Attack));
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::Space)), None);
+
+        let gamepad = settings.gamepad();
+        assert_eq!(gamepad.get(GamepadButton::East), Some(UserEventType::Attack));
+        assert_eq!(gamepad.get(GamepadButton::South), None);
+        assert_eq!(gamepad.get(GamepadButton::Start), Some(UserEventType::Menu));
+    }
+
+    #[test]
+    fn invalid_bindings() {
+        assert_invalid("version = 1\n[keyboard]\nup = [\"Up\", \"W\"]\nattack = \"W\"");
+        assert_invalid("version = 1\n[keyboard]\nup = \"Foo\"");
+        assert_invalid("version = 1\n[keyboard]\njump = \"Up\"");
+    }
+
+    #[test]
+    fn parse_gamepad() {
+        let settings: Settings = r#"
+            version = 1
+            [gamepad]
+            dead_zone = 0.4
+            [gamepad.bindings]
+            attack = ["South", "RightTrigger2"]
+        "#.parse().unwrap();
+
+        let gamepad = settings.gamepad();
+        assert_relative_eq!(gamepad.dead_zone(), 0.4);
+        assert_eq!(gamepad.get(GamepadButton::South), Some(UserEventType::Attack));
+        assert_eq!(gamepad.get(GamepadButton::RightTrigger2), Some(UserEventType::Attack));
+        assert_eq!(gamepad.get(GamepadButton::DPadUp), Some(UserEventType::CmdUp));
+
+        assert_invalid("version = 1\n[gamepad]\ndead_zone = 1.5");
+        assert_invalid("version = 1\n[gamepad.bindings]\nattack = \"A\"");
+    }
+
+    #[test]
+    fn missing_entries_keep_defaults() {
+        let settings: Settings = r#"
+            version = 1
+            [window]
+            width = 1024
+        "#.parse().unwrap();
+
+        assert_eq!(settings.window().width(), 1024);
+        assert_eq!(settings.window().height(), 600);
+        assert_eq!(settings.network().addr(), DEFAULT_ADDRESS);
+        assert_eq!(settings.keyboard().get(Some(VirtualKeyCode::Up)), Some(UserEventType::CmdUp));
+    }
+
+    #[test]
+    fn versions() {
+        assert_invalid("[window]\nwidth = 1024");
+        assert_invalid("version = 2");
+        assert_invalid("version = \"1\"");
+        assert!("version = 1".parse::<Settings>().is_ok());
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_invalid("version = 1\n[window]\nwidth = -3");
+        assert_invalid("version = 1\n[window]\nheight = 0");
+        assert_invalid("version = 1\n[window]\nvsync = 1");
+        assert_invalid("version = 1\nnetwork = 3");
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut settings = Settings::new("example.org:7000".to_string(), false);
+        {
+            let mut window = settings.window_mut();
+            window.set_dimensions(1280, 720);
+            window.set_fullscreen(true);
+            window.set_vsync(true);
+        }
+        settings.network_mut().set_render_delay(150_000_000);
+        settings.keyboard_mut().bind(VirtualKeyCode::W, UserEventType::CmdUp);
+        settings.keyboard_mut().unbind(VirtualKeyCode::I);
+        settings.gamepad_mut().set_dead_zone(0.5);
+        settings.gamepad_mut().bind(GamepadButton::Start, UserEventType::Screenshot);
+
+        let saved: Settings = settings.to_string().parse().unwrap();
+        assert_eq!(saved.window().width(), 1280);
+        assert_eq!(saved.window().height(), 720);
+        assert!(saved.window().fullscreen());
+        assert!(saved.window().vsync());
+        assert_eq!(saved.network().addr(), "example.org:7000");
+        assert_eq!(saved.network().render_delay(), 150_000_000);
+        assert_relative_eq!(saved.gamepad().dead_zone(), 0.5);
+        assert_eq!(saved.gamepad().get(GamepadButton::Start), Some(UserEventType::Screenshot));
+        assert_eq!(saved.gamepad().get(GamepadButton::South), Some(UserEventType::Attack));
+
+        let keyboard = saved.keyboard();
+        let mut keys = keyboard.keys_for(UserEventType::CmdUp);
+        keys.sort_by_key(|k| *k as u32);
+        assert_eq!(keys, vec![VirtualKeyCode::W, VirtualKeyCode::Up]);
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::I)), None);
+        assert_eq!(keyboard.get(Some(VirtualKeyCode::O)), Some(UserEventType::ZoomOut));
+    }
 }
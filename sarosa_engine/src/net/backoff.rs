@@ -0,0 +1,65 @@
+use std::cmp;
+use std::time::Duration;
+
+// Delay before the first attempt, in milliseconds.
+const FIRST_DELAY_MS: u64 = 500;
+const MAX_DELAY_MS: u64 = 16_000;
+// About two minutes with the delays above.
+const MAX_ATTEMPTS: u32 = 10;
+
+/// Delays between the attempts to reconnect to the server:
+/// doubled after each failure, up to a maximum, and for a
+/// limited number of attempts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backoff {
+    attempt: u32,
+    delay_ms: u64,
+}
+
+impl Backoff {
+
+    pub fn new() -> Backoff {
+        Backoff {
+            attempt: 0,
+            delay_ms: FIRST_DELAY_MS,
+        }
+    }
+
+    /// Number of attempts started so far.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Delay before the next attempt, `None` once they have all been made.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempt == MAX_ATTEMPTS {
+            return None;
+        }
+        self.attempt += 1;
+        let delay = self.delay_ms;
+        self.delay_ms = cmp::min(delay * 2, MAX_DELAY_MS);
+        Some(Duration::from_millis(delay))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+    use super::{Backoff, MAX_ATTEMPTS};
+
+    #[test]
+    fn delays() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<Duration> = (0..7).filter_map(|_| backoff.next_delay()).collect();
+        let expected: Vec<Duration> = [500, 1000, 2000, 4000, 8000, 16000, 16000].iter()
+            .map(|&ms| Duration::from_millis(ms))
+            .collect();
+        assert_eq!(delays, expected);
+        assert_eq!(backoff.attempt(), 7);
+
+        while backoff.next_delay().is_some() {}
+        assert_eq!(backoff.attempt(), MAX_ATTEMPTS);
+        assert_eq!(backoff.next_delay(), None);
+    }
+}
@@ -3,6 +3,7 @@ extern crate rand;
 use std::time::Duration;
 use std::thread;
 use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 use std::iter;
@@ -13,47 +14,73 @@ use cgmath::{Zero, ElementWise};
 use cgmath::Vector2;
 
 use animation::TextureId;
-use models::player::THIS_PLAYER;
+use models::player::{PlayerInfo, THIS_PLAYER};
+use models::map::GameMap;
+use physics::PhysicsEngine;
+use physics::WALK_SPEED;
 use events::{
     UserEventType,
+    UserEventState,
     UserEvent,
 };
-use events::UserEventType::*;
-use events::UserEventState::*;
+use events::commands::CommandStates;
 
 use super::ServerEvent;
+use super::ClientEvent;
 use super::RemoteServerHandle;
+use super::stats::{Ack, StatsRecorder};
+
+const OFFLINE_PLAYER_NAME: &'static str = "Player";
+const OFFLINE_PLAYER_HEALTH: u64 = 100;
 
 struct FakeServerForReal {
     current_player_pos: Vector2<f32>,
     speed: Vector2<f32>,
     first_event: bool,
+    commands: CommandStates,
+    map: GameMap,
+    physics: PhysicsEngine,
+    // Messages to say back, as the real server broadcasts them.
+    chat: Vec<String>,
 }
 
 impl FakeServerForReal {
 
     fn event_update(&mut self, user_event: UserEvent) {
-        let speed = match user_event {
-            UserEvent { state: Start, kind: CmdUp }     => Vector2::new(0f32,  1f32),
-            UserEvent { state: Start, kind: CmdDown }   => Vector2::new(0f32, -1f32),
-            UserEvent { state: Start, kind: CmdLeft }   => Vector2::new(-1f32, 0f32),
-            UserEvent { state: Start, kind: CmdRight }  => Vector2::new( 1f32, 0f32),
-            _ => Vector2::zero(),
+        // Same rules as a `Order::Walk` on the real server.
+        self.commands.inject(user_event.kind, user_event.state != UserEventState::Start);
+        self.speed = match self.commands.next_direction() {
+            Some(direction) => direction.to_vector() * WALK_SPEED,
+            None => Vector2::zero(),
         };
-        let factor = Vector2::new(0.5f32, 0.5f32);
-        self.speed = &self.speed + &factor.mul_element_wise(speed);
+    }
+
+    fn chat_update(&mut self, message: String) {
+        self.chat.push(message);
     }
 
     fn event_iter(&mut self) -> Once<ServerEvent> {
         let approx_dt = Vector2::new(0.02, 0.02);
-        self.current_player_pos = &self.current_player_pos + &approx_dt.mul_element_wise(self.speed);
+        self.current_player_pos = self.physics.resolve_move(
+            &self.map,
+            &self.current_player_pos,
+            &approx_dt.mul_element_wise(self.speed));
 
-        if self.first_event {
+        if !self.chat.is_empty() {
+            iter::once(ServerEvent::Chat {
+                id: THIS_PLAYER,
+                message: self.chat.remove(0),
+            })
+        } else if self.first_event {
             self.first_event = false;
             iter::once(ServerEvent::NewPlayer {
                 initial_pos: self.current_player_pos,
                 id: THIS_PLAYER,
                 tex_id: TextureId(rand::random::<u32>() % 3),
+                info: PlayerInfo {
+                    name: OFFLINE_PLAYER_NAME.to_string(),
+                    health: OFFLINE_PLAYER_HEALTH,
+                },
             })
         } else {
             iter::once(ServerEvent::Position {
@@ -73,48 +100,64 @@ impl RemoteServerHandle for RemoteServer {}
 
 impl RemoteServer {
 
-    pub fn new() -> RemoteServer {
+    pub fn new(map: GameMap) -> RemoteServer {
+        let physics = PhysicsEngine::new();
+        let spawn = map.spawn_points().first().cloned().unwrap_or(Vector2::zero());
         RemoteServer {
             data: Arc::new(Mutex::new(
                 FakeServerForReal {
-                    current_player_pos: Vector2::zero(),
+                    current_player_pos: physics.closest_free_position(&map, &spawn),
                     speed: Vector2::zero(),
                     first_event: true,
+                    commands: CommandStates::new(),
+                    map: map,
+                    physics: physics,
+                    chat: Vec::new(),
                 }
             ))
         }
     }
 
-    pub fn start_writer_thread(&mut self, rx_user: Receiver<UserEvent>, _: Sender<()>) {
+    // Nothing goes through a socket, the messages are counted without their size.
+    pub fn start_writer_thread(&mut self, rx_user: Receiver<ClientEvent>, _: Sender<()>, stats: StatsRecorder) {
 
         let arc_mutex_crazy_frog = self.data.clone();
         thread::Builder::new()
             .name("NetworkFake - Writer".to_string())
             .spawn(move|| {
 
-            'run: loop {
-
-                // Receive user events:
-                {
-                    let mut server = arc_mutex_crazy_frog.lock().unwrap();
-                    while let Ok(ue) = rx_user.try_recv() {
-                        match ue.kind {
-                            UserEventType::Quit => {
-                                break 'run;
+            // Only a change of direction is acknowledged.
+            let mut last_walk = None;
+
+            // Wait for user events, until the game stops.
+            for event in rx_user.iter() {
+                let mut server = arc_mutex_crazy_frog.lock().unwrap();
+                let ack = match event {
+                    ClientEvent::User(ue) => match ue.kind {
+                        UserEventType::Quit => break,
+                        _ => {
+                            server.event_update(ue);
+                            let direction = server.commands.next_direction();
+                            if last_walk == Some(direction) {
+                                None
+                            } else {
+                                last_walk = Some(direction);
+                                Some(Ack::Walk(direction))
                             }
-                            _ => {
-                                server.event_update(ue);
-                            },
                         }
+                    },
+                    ClientEvent::Chat(message) => {
+                        let ack = Ack::Say(message.clone());
+                        server.chat_update(message);
+                        Some(ack)
                     }
-                }
-
-                thread::sleep(Duration::from_millis(30));
+                };
+                stats.sent(0, ack);
             }
         }).expect("Couldn't start thread");
     }
 
-    pub fn start_reader_thread(&mut self, tx_serv: Sender<ServerEvent>, _: Receiver<()>) {
+    pub fn start_reader_thread(&mut self, tx_serv: Sender<ServerEvent>, rx_error_reader: Receiver<()>, stats: StatsRecorder) {
 
         let arc_mutex_crazy_frog = self.data.clone();
         thread::Builder::new()
@@ -126,6 +169,7 @@ impl RemoteServer {
                 {
                     let mut server = arc_mutex_crazy_frog.lock().unwrap();
                     for server_event in server.event_iter() {
+                        stats.received(0, Ack::of(&server_event));
                         match tx_serv.send(server_event) {
                             Err(_) => break 'run,
                             _ => (),
@@ -133,8 +177,63 @@ impl RemoteServer {
                     }
                 }
 
-                thread::sleep(Duration::from_millis(20));
+                // Until told to stop: by `Server::disconnect` or when it is dropped.
+                match rx_error_reader.recv_timeout(Duration::from_millis(20)) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    _ => break 'run,
+                }
             }
         }).expect("Couldn't start thread");
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::mpsc::channel;
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::{Duration, Instant};
+
+    use models::map::GameMap;
+    use net::stats::StatsRecorder;
+    use super::RemoteServer;
+
+    const MAP: &'static str = r#"
+        width = 3
+        height = 3
+
+        [tileset]
+        path = "tiles.png"
+        columns = 1
+        rows = 1
+
+        [[layer]]
+        name = "ground"
+        tiles = """
+        . . .
+        . . .
+        . . .
+        """
+    "#;
+
+    #[test]
+    fn reader_stops() {
+        let mut server = RemoteServer::new(MAP.parse::<GameMap>().unwrap());
+        let (tx_serv, rx_serv) = channel();
+        let (tx_error, rx_error) = channel();
+        server.start_reader_thread(tx_serv, rx_error, StatsRecorder::new());
+        assert!(rx_serv.recv_timeout(Duration::from_secs(1)).is_ok());
+
+        // As `Server::disconnect` does, the channel is then kept open.
+        tx_error.send(()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(1);
+        loop {
+            match rx_serv.recv_timeout(Duration::from_secs(1)) {
+                Ok(_) => assert!(Instant::now() < deadline, "the reader is still running"),
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => panic!("the reader is still running"),
+            }
+        }
+        drop(tx_error);
+    }
+}
@@ -1,37 +1,138 @@
+use std::error::Error;
+use std::fmt;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
+use std::sync::mpsc::TryRecvError;
 use std::mem;
-use std::ops::Deref;
+use std::thread;
+use std::time::{Duration, Instant};
 use cgmath::Vector2;
 
 use events::EventSystem;
 use core::GameInstance;
-use models::player::PlayerId;
+use models::player::{PlayerId, PlayerInfo};
+use models::map::{GameMap, DEFAULT_MAP_PATH};
 use animation::TextureId;
 use events::UserEvent;
+use models::settings::NetworkSettings;
 use Settings;
 
+use self::backoff::Backoff;
+use self::real::record::Recorder;
+use self::stats::StatsRecorder;
+
+pub use self::stats::{NetStats, Rate};
+
 mod real;
 mod fake;
+mod replay;
+mod backoff;
+mod stats;
+
+// How often the statistics of the connection are logged.
+const STATS_LOG_INTERVAL_S: u64 = 10;
 
 trait RemoteServerHandle {}
 
+/// What the game sends to the network threads.
+pub enum ClientEvent {
+    User(UserEvent),
+    /// A message typed in the chat.
+    Chat(String),
+}
+
 struct NullServerHandle;
 impl RemoteServerHandle for NullServerHandle {}
 
 pub struct Server {
-    tx: Sender<UserEvent>,
+    tx: Sender<ClientEvent>,
     rx: Receiver<ServerEvent>,
     tx_error: Sender<()>,
     rx_error: Receiver<()>,
     remote_server: Box<RemoteServerHandle>,
     settings: Settings,
+    reconnection: Option<Reconnection>,
+    // Until `poll_connect` tells the result of `connect`.
+    connection: Option<PendingConnection>,
+    stats: StatsRecorder,
+    stats_logged: Instant,
+    // Shared by the connections, once the first one is made.
+    recorder: Option<Recorder>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Why the game isn't, or is no longer, connected to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ServerError {
+    /// The connection was lost.
     Disconnected,
+    /// The address of the server couldn't be resolved.
+    DnsFailure(String),
+    /// Nothing listens at the address, or the connection was denied.
+    Refused(String),
+    /// The connection failed otherwise, the server may be unreachable.
+    Io(String),
+    /// The server didn't answer in time.
+    Timeout,
+    /// The server doesn't speak the protocol of this client.
+    ProtocolMismatch,
+    /// The recording to replay couldn't be read.
+    Replay(String),
+    /// The map of the offline server couldn't be loaded.
+    Map(String),
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ServerError::Disconnected => write!(f, "The connection to the server was lost"),
+            ServerError::DnsFailure(ref e) => write!(f, "Couldn't find the server: {}", e),
+            ServerError::Refused(ref e) => write!(f, "The server refused the connection: {}", e),
+            ServerError::Io(ref e) => write!(f, "Couldn't connect to the server: {}", e),
+            ServerError::Timeout => write!(f, "The server didn't answer in time"),
+            ServerError::ProtocolMismatch => write!(f, "The server doesn't speak the same protocol"),
+            ServerError::Replay(ref e) => write!(f, "Couldn't read the recording {}", e),
+            ServerError::Map(ref e) => write!(f, "Couldn't load the map {}", e),
+        }
+    }
+}
+
+impl Error for ServerError {
+    fn description(&self) -> &str {
+        match *self {
+            ServerError::Disconnected => "disconnected from the server",
+            ServerError::DnsFailure(_) => "couldn't resolve the address of the server",
+            ServerError::Refused(_) => "connection refused",
+            ServerError::Io(_) => "connection failed",
+            ServerError::Timeout => "connection timed out",
+            ServerError::ProtocolMismatch => "protocol mismatch",
+            ServerError::Replay(_) => "couldn't read the recording",
+            ServerError::Map(_) => "couldn't load the map",
+        }
+    }
+}
+
+/// How the game is connected to the server, see `Server::status`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Connected,
+    /// The connection was lost, `attempt` counts the tries to get it back.
+    Reconnecting { attempt: u32 },
+}
+
+// Attempts to connect again after the connection was lost,
+// each one is made in another thread not to block the game.
+struct Reconnection {
+    backoff: Backoff,
+    next_attempt: Instant,
+    pending: Option<Receiver<Result<real::RemoteServer, ServerError>>>,
+}
+
+// A connection started by `Server::connect`. Only the real server
+// is connected to in another thread, the other ones are local.
+enum PendingConnection {
+    Done(Result<(), ServerError>),
+    Remote(Receiver<Result<real::RemoteServer, ServerError>>),
 }
 
 // This shouldn't needed to be pub.
@@ -44,6 +145,7 @@ pub enum ServerEvent {
         initial_pos: Vector2<f32>,
         id: PlayerId,
         tex_id: TextureId,
+        info: PlayerInfo,
     },
     Position {
         pos: Vector2<f32>,
@@ -51,6 +153,10 @@ pub enum ServerEvent {
         id: PlayerId
     },
     PlayerHasQuit(PlayerId),
+    Chat {
+        id: PlayerId,
+        message: String,
+    },
 }
 
 impl Server {
@@ -67,56 +173,114 @@ impl Server {
             tx_error: tx_error,
             remote_server: Box::new(NullServerHandle),
             settings: settings,
+            reconnection: None,
+            connection: None,
+            stats: StatsRecorder::new(),
+            stats_logged: Instant::now(),
+            recorder: None,
         }
     }
 
     pub fn disconnect(&mut self) {
         let _ = self.tx_error.send(());
+        self.reconnection = None;
+        // The thread connecting drops the connection once it's done.
+        self.connection = None;
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        match self.reconnection {
+            Some(ref reconnection) => ConnectionStatus::Reconnecting {
+                attempt: reconnection.backoff.attempt(),
+            },
+            None => ConnectionStatus::Connected,
+        }
+    }
+
+    pub fn is_reconnecting(&self) -> bool {
+        self.reconnection.is_some()
+    }
+
+    /// Quality of the connection to the server. The offline
+    /// server is measured too, but without bytes.
+    pub fn stats(&self) -> NetStats {
+        self.stats.stats()
     }
 
+    /// Start connecting to the server of the network settings,
+    /// or start the offline one, or replay a recording. The game
+    /// isn't blocked meanwhile, see `poll_connect`.
     pub fn connect(&mut self) {
         let offline_server = self.settings.network().offline_server();
-        if offline_server {
+        let replay = self.settings.network().replay_path().map(|path| path.to_string());
+        let connection = if let Some(path) = replay {
+            info!("Replaying `{}`", path);
+            PendingConnection::Done(self.connect_replay(&path))
+        } else if offline_server {
             info!("Starting in Offline mode");
-            self.connect_offline();
+            PendingConnection::Done(self.connect_offline())
         } else {
-            {
-                let network = self.settings.network();
-                let address = network.addr();
-                info!("Connecting to server `{}`", address);
-            }
-            self.connect_real();
-        }
+            let network = self.settings.network();
+            info!("Connecting to server `{}`", network.addr());
+            PendingConnection::Remote(start_attempt(&network))
+        };
+        self.connection = Some(connection);
     }
 
-    fn connect_offline(&mut self) {
+    /// The result of `connect`, once it is known.
+    pub fn poll_connect(&mut self) -> Option<Result<(), ServerError>> {
+        let result = match self.connection.take() {
+            Some(PendingConnection::Done(result)) => result,
+            Some(PendingConnection::Remote(rx)) => match rx.try_recv() {
+                Ok(Ok(remote_server)) => {
+                    self.connected(remote_server);
+                    Ok(())
+                }
+                Ok(Err(e)) => Err(e),
+                Err(TryRecvError::Empty) => {
+                    self.connection = Some(PendingConnection::Remote(rx));
+                    return None;
+                }
+                Err(TryRecvError::Disconnected) => Err(ServerError::Disconnected),
+            },
+            None => return None,
+        };
+        Some(result)
+    }
 
-        let mut remote_server = fake::RemoteServer::new();
+    fn connect_offline(&mut self) -> Result<(), ServerError> {
+
+        let map = try!(GameMap::load(DEFAULT_MAP_PATH).map_err(|e| {
+            ServerError::Map(format!("`{}`: {}", DEFAULT_MAP_PATH, e))
+        }));
+        let mut remote_server = fake::RemoteServer::new(map);
 
         // Main channels for communication
-        let (tx_user, rx_user): (Sender<UserEvent>, Receiver<UserEvent>) = channel();
+        let (tx_user, rx_user): (Sender<ClientEvent>, Receiver<ClientEvent>) = channel();
         let (tx_serv, rx_serv): (Sender<ServerEvent>, Receiver<ServerEvent>) = channel();
 
         // Channels for errors
         let (tx_error_reader, rx_error_reader): (Sender<()>, Receiver<()>) = channel();
         let (tx_error_writer, rx_error_writer): (Sender<()>, Receiver<()>) = channel();
 
-        remote_server.start_writer_thread(rx_user, tx_error_writer);
-        remote_server.start_reader_thread(tx_serv, rx_error_reader);
+        remote_server.start_writer_thread(rx_user, tx_error_writer, self.stats.clone());
+        remote_server.start_reader_thread(tx_serv, rx_error_reader, self.stats.clone());
 
         self.remote_server = Box::new(remote_server) as Box<RemoteServerHandle>;
         self.rx = rx_serv;
         self.rx_error = rx_error_writer;
         self.tx_error = tx_error_reader;
         let _ = mem::replace(&mut self.tx, tx_user);
+        Ok(())
     }
 
-    fn connect_real(&mut self) {
+    fn connect_replay(&mut self, path: &str) -> Result<(), ServerError> {
 
-        let mut remote_server = real::RemoteServer::new(self.settings.network().deref());
+        let speed = self.settings.network().replay_speed();
+        let mut remote_server = try!(replay::RemoteServer::load(path, speed));
 
         // Main channels for communication
-        let (tx_user, rx_user): (Sender<UserEvent>, Receiver<UserEvent>) = channel();
+        let (tx_user, rx_user): (Sender<ClientEvent>, Receiver<ClientEvent>) = channel();
         let (tx_serv, rx_serv): (Sender<ServerEvent>, Receiver<ServerEvent>) = channel();
 
         // Channels for errors
@@ -126,6 +290,44 @@ impl Server {
         remote_server.start_writer_thread(rx_user, tx_error_writer);
         remote_server.start_reader_thread(tx_serv, rx_error_reader);
 
+        self.remote_server = Box::new(remote_server) as Box<RemoteServerHandle>;
+        self.rx = rx_serv;
+        self.rx_error = rx_error_writer;
+        self.tx_error = tx_error_reader;
+        let _ = mem::replace(&mut self.tx, tx_user);
+        Ok(())
+    }
+
+    fn connected(&mut self, remote_server: real::RemoteServer) {
+
+        if self.recorder.is_none() {
+            let path = self.settings.network().record_path().map(|path| path.to_string());
+            if let Some(path) = path {
+                match Recorder::create(&path) {
+                    Ok(recorder) => {
+                        info!("Recording the server to `{}`", path);
+                        self.recorder = Some(recorder);
+                    }
+                    Err(e) => warn!("Couldn't record the server to `{}`: {}", path, e),
+                }
+            }
+        }
+        self.start_real(remote_server);
+    }
+
+    fn start_real(&mut self, mut remote_server: real::RemoteServer) {
+
+        // Main channels for communication
+        let (tx_user, rx_user): (Sender<ClientEvent>, Receiver<ClientEvent>) = channel();
+        let (tx_serv, rx_serv): (Sender<ServerEvent>, Receiver<ServerEvent>) = channel();
+
+        // Channels for errors
+        let (tx_error_reader, rx_error_reader): (Sender<()>, Receiver<()>) = channel();
+        let (tx_error_writer, rx_error_writer): (Sender<()>, Receiver<()>) = channel();
+
+        remote_server.start_writer_thread(rx_user, tx_error_writer, self.stats.clone());
+        remote_server.start_reader_thread(tx_serv, rx_error_reader, self.stats.clone(), self.recorder.clone());
+
         self.remote_server = Box::new(remote_server) as Box<RemoteServerHandle>;
         self.rx = rx_serv;
         self.rx_error = rx_error_writer;
@@ -135,33 +337,123 @@ impl Server {
 
     pub fn event_update(&mut self, event_sys: &EventSystem) {
         for &e in event_sys.iter() {
-            let _ = self.tx.send(e);
+            let _ = self.tx.send(ClientEvent::User(e));
+        }
+        for message in event_sys.chat_messages() {
+            let _ = self.tx.send(ClientEvent::Chat(message.clone()));
         }
     }
 
+    /// Give what the server said to the game. When the connection is
+    /// lost, the server is reconnected to as long as it returns `Ok`.
     pub fn remote_update(&mut self, game_instance: &mut GameInstance) -> Result<(), ServerError> {
         use self::ServerEvent::NewPlayer;
         use self::ServerEvent::Position;
         use self::ServerEvent::PlayerHasQuit;
         use self::ServerEvent::DisconnectedFromServer;
+        use self::ServerEvent::Chat;
+
+        if self.stats_logged.elapsed() >= Duration::from_secs(STATS_LOG_INTERVAL_S) {
+            self.stats_logged = Instant::now();
+            debug!("Network: {}", self.stats());
+        }
+
+        if self.reconnection.is_some() {
+            return self.reconnect_update(game_instance);
+        }
 
         let mut game_data = game_instance.proxy_add();
 
         if let Ok(_) = self.rx_error.try_recv() {
-            return Err(ServerError::Disconnected);
+            return self.connection_lost();
         }
 
         while let Ok(server_event) = self.rx.try_recv() {
             match server_event {
-                NewPlayer { initial_pos, tex_id, id }   => game_data.add_player(id, initial_pos, tex_id),
-                Position { pos, speed, id }             => game_data.update_player(id, pos, speed),
-                PlayerHasQuit(id)                       => game_data.remove_player(id),
-                DisconnectedFromServer                  => return Err(ServerError::Disconnected),
+                NewPlayer { initial_pos, tex_id, id, info } => game_data.add_player(id, initial_pos, tex_id, info),
+                Position { pos, speed, id }                 => game_data.update_player(id, pos, speed),
+                PlayerHasQuit(id)                           => game_data.remove_player(id),
+                Chat { id, message }                        => game_data.add_chat_message(id, message),
+                DisconnectedFromServer                      => return self.connection_lost(),
             }
         }
 
         Ok(())
     }
+
+    fn connection_lost(&mut self) -> Result<(), ServerError> {
+        // Nothing to reconnect to.
+        let local = {
+            let network = self.settings.network();
+            network.offline_server() || network.replay_path().is_some()
+        };
+        if local {
+            return Err(ServerError::Disconnected);
+        }
+        warn!("Connection to the server lost, reconnecting");
+        // Stop the threads that are left.
+        let _ = self.tx_error.send(());
+        let mut backoff = Backoff::new();
+        let delay = backoff.next_delay().unwrap_or_default();
+        self.reconnection = Some(Reconnection {
+            backoff: backoff,
+            next_attempt: Instant::now() + delay,
+            pending: None,
+        });
+        Ok(())
+    }
+
+    fn reconnect_update(&mut self, game_instance: &mut GameInstance) -> Result<(), ServerError> {
+        let result = match self.reconnection {
+            Some(Reconnection { pending: Some(ref rx), .. }) => match rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => Err(ServerError::Disconnected),
+            },
+            Some(ref mut reconnection) => {
+                if Instant::now() >= reconnection.next_attempt {
+                    reconnection.pending = Some(start_attempt(&self.settings.network()));
+                }
+                return Ok(());
+            }
+            None => return Ok(()),
+        };
+
+        match result {
+            Ok(remote_server) => {
+                info!("Reconnected to the server");
+                self.reconnection = None;
+                self.start_real(remote_server);
+                // The server tells again who is there.
+                game_instance.proxy_add().clear_players();
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Couldn't reconnect: {}", e);
+                let reconnection = self.reconnection.as_mut().unwrap();
+                reconnection.pending = None;
+                match reconnection.backoff.next_delay() {
+                    Some(delay) => {
+                        reconnection.next_attempt = Instant::now() + delay;
+                        Ok(())
+                    }
+                    None => Err(ServerError::Disconnected),
+                }
+            }
+        }
+    }
+}
+
+// Connect in another thread, the result is sent once known.
+fn start_attempt(settings: &NetworkSettings) -> Receiver<Result<real::RemoteServer, ServerError>> {
+    let (tx, rx) = channel();
+    let settings = settings.clone();
+    thread::Builder::new()
+        .name("Network - Connect".to_string())
+        .spawn(move|| {
+            let _ = tx.send(real::RemoteServer::new(&settings));
+        }).expect("Couldn't start thread");
+    rx
 }
 
 impl Drop for Server {
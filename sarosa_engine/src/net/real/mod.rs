@@ -1,8 +1,13 @@
+use std::io;
 use std::mem;
+use std::net::ToSocketAddrs;
 use std::sync::atomic::AtomicUsize;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::sync::mpsc::channel;
 use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::TryRecvError;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 
@@ -14,6 +19,7 @@ use sarosa_net::net::{
     NetworkError,
 };
 use sarosa_net::messages::Order;
+use sarosa_net::messages::Notification;
 use events::{
     UserEvent,
     UserEventType
@@ -21,36 +27,98 @@ use events::{
 use models::settings;
 use sarosa_net::messages::EntityOrder;
 use net::{
+    ClientEvent,
     RemoteServerHandle,
+    ServerError,
     ServerEvent
 };
+use net::stats::{Ack, StatsRecorder};
 
 use self::reader::ServerEventReader;
+use self::record::Recorder;
 use self::sender::UserEventSender;
-mod reader;
+pub mod reader;
+pub mod record;
 mod sender;
 
+// For the connection and the handshake together.
+const CONNECT_TIMEOUT_MS: u64 = 10_000;
+// Events coming this soon after the first one are sent together,
+// the orders of a frame are pushed at once.
+const BATCH_WINDOW_MS: u64 = 2;
+// Between two reads when nothing is received, see `RemoteServer`.
+const READ_INTERVAL_MS: u64 = 8;
+// `sarosa_net` doesn't tell how much it reads and writes, the size
+// of the messages is estimated from their fields, in bytes.
+const HEADER_SIZE: usize = 4;
+const ENTITY_SIZE: usize = 8;
+const VEC_SIZE: usize = 8;
+const STRING_SIZE: usize = 4;
+
+/// A connection to the real server, used by a writer and a reader thread.
+///
+/// The writer is event-driven: it blocks on the orders of the game and
+/// sends them as soon as they come. The reader is not, it polls every
+/// `READ_INTERVAL_MS`, and so does the handshake. `sarosa_net` never
+/// gives out its `TcpStream`, so there's no socket to block on with a
+/// read timeout, nor a clone to shut down to wake a blocked reader.
+/// Making the reader event-driven needs `sarosa_net` to expose one.
 pub struct RemoteServer {
     writer: Option<NetworkWriter>,
     reader: Option<NetworkReader>,
     this_player_id: Arc<AtomicUsize>,
+    // The `ThisIsYou` read while connecting.
+    handshake: Option<Notification>,
 }
 
 impl RemoteServerHandle for RemoteServer {}
 
 impl RemoteServer {
 
-    pub fn new(settings: &settings::NetworkSettings) -> RemoteServer {
-        let sets = NetworkSettings::new(settings).unwrap();
-        let (reader, writer) = connect(&sets).unwrap();
-        RemoteServer {
+    /// Connect to the server and wait for it to tell which entity
+    /// is the player, or give up after `CONNECT_TIMEOUT_MS`.
+    pub fn new(settings: &settings::NetworkSettings) -> Result<RemoteServer, ServerError> {
+        let settings = settings.clone();
+        let (tx, rx) = channel();
+        // The system may take a lot longer to give up.
+        thread::Builder::new()
+            .name("Network - Connect".to_string())
+            .spawn(move|| {
+                let timeout = Duration::from_millis(CONNECT_TIMEOUT_MS);
+                let _ = tx.send(RemoteServer::connect(&settings, timeout));
+            }).expect("Couldn't start thread");
+
+        match rx.recv_timeout(Duration::from_millis(CONNECT_TIMEOUT_MS)) {
+            Ok(result) => result,
+            Err(_) => Err(ServerError::Timeout),
+        }
+    }
+
+    fn connect(settings: &settings::NetworkSettings, timeout: Duration) -> Result<RemoteServer, ServerError> {
+        let deadline = Instant::now() + timeout;
+
+        // Resolved first to tell the errors apart.
+        match settings.to_socket_addrs() {
+            Ok(mut addrs) => if addrs.next().is_none() {
+                return Err(ServerError::DnsFailure(format!("no address for `{}`", settings.addr())));
+            },
+            Err(e) => return Err(ServerError::DnsFailure(e.to_string())),
+        }
+        let sets = try!(NetworkSettings::new(settings).map_err(|e| {
+            ServerError::DnsFailure(e.to_string())
+        }));
+        let (mut reader, writer) = try!(connect(&sets).map_err(connect_error));
+        let handshake = try!(handshake(&mut reader, deadline));
+
+        Ok(RemoteServer {
             writer: Some(writer),
             reader: Some(reader),
             this_player_id: Arc::new(AtomicUsize::new(0)),
-        }
+            handshake: Some(handshake),
+        })
     }
 
-    pub fn start_writer_thread(&mut self, rx_user: Receiver<UserEvent>, tx_error_writer: Sender<()>) {
+    pub fn start_writer_thread(&mut self, rx_user: Receiver<ClientEvent>, tx_error_writer: Sender<()>, stats: StatsRecorder) {
 
         let player_id = self.this_player_id.clone();
         if let Some(mut writer) = mem::replace(&mut self.writer, None) {
@@ -62,17 +130,32 @@ impl RemoteServer {
 
                 'run: loop {
 
-                    // Receive user events:
+                    // Wait for user events, the channel is closed
+                    // when the game stops or connects again.
+                    let events = match wait_batch(&rx_user) {
+                        Some(events) => events,
+                        None => break 'run,
+                    };
                     let mut converter = sender.prepare_event_consumer();
 
-                    while let Ok(ue) = rx_user.try_recv() {
-                        if let UserEventType::Quit = ue.kind {
-                            break 'run;
-                        }
-                        let order = if let UserEventType::Attack = ue.kind {
-                            Order::Attack
-                        } else {
-                            converter.consume_event(ue)
+                    for event in events {
+                        let order = match event {
+                            ClientEvent::User(UserEvent { kind: UserEventType::Quit, .. }) => {
+                                break 'run;
+                            }
+                            ClientEvent::User(UserEvent { kind: UserEventType::Attack, .. }) => {
+                                Order::Attack
+                            }
+                            ClientEvent::User(ue) => match converter.consume_event(ue) {
+                                Some(order) => order,
+                                None => continue,
+                            },
+                            ClientEvent::Chat(message) => converter.consume_message(message),
+                        };
+                        let ack = match order {
+                            Order::Walk(_) => Some(Ack::Walk(converter.direction())),
+                            Order::Say(ref message) => Some(Ack::Say(message.clone())),
+                            _ => None,
                         };
 
                         let order_event = EntityOrder {
@@ -84,7 +167,7 @@ impl RemoteServer {
                                 debug!("io::Error {}", e);
                                 break 'run;
                             },
-                            _ => (),
+                            _ => stats.sent(order_size(&order_event.order), ack),
                         }
                     }
 
@@ -96,8 +179,6 @@ impl RemoteServer {
                         },
                         _ => (),
                     }
-
-                    thread::sleep(Duration::from_millis(30));
                 }
 
                 // Tell main thread we're going to shutdown.
@@ -107,31 +188,46 @@ impl RemoteServer {
         }
     }
 
-    pub fn start_reader_thread(&mut self, tx_serv: Sender<ServerEvent>, rx_error_reader: Receiver<()>) {
+    pub fn start_reader_thread(
+        &mut self,
+        tx_serv: Sender<ServerEvent>,
+        rx_error_reader: Receiver<()>,
+        stats: StatsRecorder,
+        mut recorder: Option<Recorder>)
+    {
 
         let player_id = self.this_player_id.clone();
+        let handshake = self.handshake.take();
         if let Some(mut reader) = mem::replace(&mut self.reader, None) {
             thread::Builder::new()
                 .name("Network - Reader".to_string())
                 .spawn(move|| {
 
                 let mut converter = ServerEventReader::new(player_id);
+                if let Some(notification) = handshake {
+                    stats.received(notification_size(&notification), None);
+                    record(&mut recorder, &notification);
+                    converter.consume_event(notification);
+                }
 
                 'run: loop {
 
-                    if let Ok(_) = rx_error_reader.try_recv() {
-                        break 'run;
-                    }
-
                     // Lookup for remote events
                     'events: loop {
                         match reader.read() {
-                            Ok(notification) => match converter.consume_event(notification) {
-                                Some(server_event) => match tx_serv.send(server_event) {
-                                    Err(_) => break 'run,
-                                    _ => ()
-                                },
-                                None => (),
+                            Ok(notification) => {
+                                let size = notification_size(&notification);
+                                record(&mut recorder, &notification);
+                                match converter.consume_event(notification) {
+                                    Some(server_event) => {
+                                        stats.received(size, Ack::of(&server_event));
+                                        match tx_serv.send(server_event) {
+                                            Err(_) => break 'run,
+                                            _ => ()
+                                        }
+                                    }
+                                    None => stats.received(size, None),
+                                }
                             },
                             Err(network_error) => match network_error {
                                 NetworkError::DisconnectedFromServer => break 'run,
@@ -140,7 +236,17 @@ impl RemoteServer {
                         }
                     }
 
-                    thread::sleep(Duration::from_millis(8));
+                    if let Some(Err(e)) = recorder.as_ref().map(|r| r.flush()) {
+                        warn!("Couldn't write the recording, it is stopped: {}", e);
+                        recorder = None;
+                    }
+
+                    // `NetworkReader` doesn't block, wait for more data until
+                    // told to stop: by `Server::disconnect` or when it is dropped.
+                    match rx_error_reader.recv_timeout(Duration::from_millis(READ_INTERVAL_MS)) {
+                        Err(RecvTimeoutError::Timeout) => (),
+                        _ => break 'run,
+                    }
                 }
 
                 let _ = tx_serv.send(ServerEvent::DisconnectedFromServer);
@@ -149,3 +255,199 @@ impl RemoteServer {
         }
     }
 }
+
+// Block until an event comes, then take the ones following it within
+// `BATCH_WINDOW_MS`. Returns `None` once the channel is closed.
+fn wait_batch(rx_user: &Receiver<ClientEvent>) -> Option<Vec<ClientEvent>> {
+    let mut events = match rx_user.recv() {
+        Ok(event) => vec![event],
+        Err(_) => return None,
+    };
+    let deadline = Instant::now() + Duration::from_millis(BATCH_WINDOW_MS);
+    loop {
+        let now = Instant::now();
+        let event = if now < deadline {
+            match rx_user.recv_timeout(deadline - now) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Some(events),
+            }
+        } else {
+            match rx_user.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Some(events),
+            }
+        };
+        events.push(event);
+    }
+    Some(events)
+}
+
+// Stop recording on the first error, not to report it for each message.
+fn record(recorder: &mut Option<Recorder>, notification: &Notification) {
+    if let Some(Err(e)) = recorder.as_ref().map(|r| r.record(notification)) {
+        warn!("Couldn't write the recording, it is stopped: {}", e);
+        *recorder = None;
+    }
+}
+
+fn order_size(order: &Order) -> usize {
+    HEADER_SIZE + ENTITY_SIZE + 1 + match *order {
+        Order::Walk(_) => 1,
+        Order::Say(ref message) => STRING_SIZE + message.len(),
+        _ => 0,
+    }
+}
+
+fn notification_size(notification: &Notification) -> usize {
+    HEADER_SIZE + 1 + match *notification {
+        Notification::ThisIsYou { .. } | Notification::EntityHasQuit { .. } => ENTITY_SIZE,
+        // With the skin and the health.
+        Notification::NewEntity { ref pseudo, .. } => {
+            ENTITY_SIZE + VEC_SIZE + 8 + STRING_SIZE + pseudo.len() + 8
+        }
+        Notification::Say { ref message, .. } => ENTITY_SIZE + STRING_SIZE + message.len(),
+        Notification::Position { .. } => ENTITY_SIZE + 2 * VEC_SIZE,
+        _ => 0,
+    }
+}
+
+fn connect_error(e: io::Error) -> ServerError {
+    match e.kind() {
+        io::ErrorKind::TimedOut => ServerError::Timeout,
+        io::ErrorKind::ConnectionRefused => ServerError::Refused(e.to_string()),
+        _ => ServerError::Io(e.to_string()),
+    }
+}
+
+// The server first tells which entity is the player, anything
+// else means that it doesn't speak the same protocol.
+fn handshake(reader: &mut NetworkReader, deadline: Instant) -> Result<Notification, ServerError> {
+    loop {
+        match reader.read() {
+            Ok(notification @ Notification::ThisIsYou { .. }) => return Ok(notification),
+            Err(ref e) if nothing_read(e) => {
+                if Instant::now() >= deadline {
+                    return Err(ServerError::Timeout);
+                }
+                thread::sleep(Duration::from_millis(READ_INTERVAL_MS));
+            }
+            // Another message, one that couldn't be decoded, or
+            // a server closing the connection right away.
+            Ok(_) | Err(_) => return Err(ServerError::ProtocolMismatch),
+        }
+    }
+}
+
+// `NetworkReader` doesn't block, it fails with `WouldBlock`
+// as long as no whole message has been received.
+// TODO: `sarosa_net` couldn't be fetched when this was written,
+// check that `IoError` is the variant it wraps io errors in.
+fn nothing_read(e: &NetworkError) -> bool {
+    match *e {
+        NetworkError::IoError(ref e) => e.kind() == io::ErrorKind::WouldBlock,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io;
+    use std::net::TcpListener;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use models::settings::NetworkSettings;
+    use net::{ClientEvent, ServerError};
+    use super::{connect_error, wait_batch, RemoteServer, BATCH_WINDOW_MS};
+
+    fn messages(events: Vec<ClientEvent>) -> Vec<String> {
+        events.into_iter().map(|event| match event {
+            ClientEvent::Chat(message) => message,
+            ClientEvent::User(_) => panic!("expected a message"),
+        }).collect()
+    }
+
+    #[test]
+    fn batches() {
+        let (tx, rx) = channel();
+        let start = Instant::now();
+        thread::spawn(move|| {
+            thread::sleep(Duration::from_millis(50));
+            tx.send(ClientEvent::Chat("a".to_string())).unwrap();
+            tx.send(ClientEvent::Chat("b".to_string())).unwrap();
+            // Well after the window of the first one.
+            thread::sleep(Duration::from_millis(BATCH_WINDOW_MS * 25));
+            tx.send(ClientEvent::Chat("c".to_string())).unwrap();
+        });
+
+        // Blocked until the first event.
+        let events = wait_batch(&rx).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(messages(events), vec!["a", "b"]);
+        assert_eq!(messages(wait_batch(&rx).unwrap()), vec!["c"]);
+        // The thread is done, the channel is closed.
+        assert!(wait_batch(&rx).is_none());
+    }
+
+    #[test]
+    fn batch_before_close() {
+        let (tx, rx) = channel();
+        tx.send(ClientEvent::Chat("a".to_string())).unwrap();
+        drop(tx);
+        // What was sent is still given before the end.
+        assert_eq!(messages(wait_batch(&rx).unwrap()), vec!["a"]);
+        assert!(wait_batch(&rx).is_none());
+    }
+
+    // What connecting to `address` ends with, it must fail.
+    fn connect(address: String, timeout_ms: u64) -> ServerError {
+        let settings = NetworkSettings::new(address, false);
+        match RemoteServer::connect(&settings, Duration::from_millis(timeout_ms)) {
+            Ok(_) => panic!("connected"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn connect_errors() {
+        let timed_out = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        assert_eq!(connect_error(timed_out), ServerError::Timeout);
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        assert_eq!(connect_error(refused), ServerError::Refused("refused".to_string()));
+        let unreachable = io::Error::new(io::ErrorKind::Other, "network is unreachable");
+        assert_eq!(connect_error(unreachable), ServerError::Io("network is unreachable".to_string()));
+        let reset = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+        assert_eq!(connect_error(reset), ServerError::Io("reset".to_string()));
+    }
+
+    #[test]
+    fn dns_failure() {
+        // The `invalid` domain never resolves.
+        match connect("nowhere.invalid:7777".to_string(), 1_000) {
+            ServerError::DnsFailure(_) => (),
+            e => panic!("expected a dns failure, got: {}", e),
+        }
+    }
+
+    #[test]
+    fn refused() {
+        // Nothing listens on the port anymore.
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        match connect(address.to_string(), 1_000) {
+            ServerError::Refused(_) => (),
+            e => panic!("expected a refusal, got: {}", e),
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        // Connected by the system, but nothing is ever said.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        assert_eq!(connect(address.to_string(), 100), ServerError::Timeout);
+    }
+}
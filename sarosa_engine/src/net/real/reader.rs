@@ -4,7 +4,7 @@ use std::sync::atomic::Ordering;
 
 use cgmath::Vector2;
 use animation::TextureId;
-use models::player::THIS_PLAYER;
+use models::player::{PlayerInfo, THIS_PLAYER};
 use sarosa_net::messages::Vec2d;
 use sarosa_net::messages::Notification::*;
 use sarosa_net::messages::Notification;
@@ -35,20 +35,26 @@ impl ServerEventReader  {
                 }
                 None
             }
-            NewEntity { entity, position: Vec2d { x, y }, skin, .. } => {
-                debug!("New  player: {}", entity);
+            NewEntity { entity, position: Vec2d { x, y }, skin, pseudo, pv } => {
+                debug!("New  player: {} ({})", entity, pseudo);
+                let info = PlayerInfo {
+                    name: pseudo,
+                    health: pv,
+                };
                 if let &Some(me) = &self.local_copy_player_id {
                     if me == entity {
                         Some(ServerEvent::NewPlayer {
                             initial_pos: Vector2::new(x, y),
                             id: THIS_PLAYER,
                             tex_id: TextureId((skin % 3) as u32),
+                            info: info,
                         })
                     } else {
                         Some(ServerEvent::NewPlayer {
                             initial_pos: Vector2::new(x, y),
                             id: entity,
                             tex_id: TextureId((skin % 3) as u32),
+                            info: info,
                         })
                     }
                 } else {
@@ -61,7 +67,14 @@ impl ServerEventReader  {
             }
             Say { entity, message } => {
                 debug!("Player {} says: {}", entity, message);
-                None
+                let id = match self.local_copy_player_id {
+                    Some(me) if me == entity => THIS_PLAYER,
+                    _ => entity,
+                };
+                Some(ServerEvent::Chat {
+                    id: id,
+                    message: message,
+                })
             }
             Position { entity, position, speed, .. } => {
                 let xf = position.x;
@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use rustc_serialize::json;
+
+use sarosa_net::messages::Notification;
+use sarosa_net::messages::Vec2d;
+
+/// A notification of the server, `time_ms` after the start of the recording.
+///
+/// Recordings are text files with one record per line, in json.
+#[derive(Debug, Clone, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct Record {
+    pub time_ms: u64,
+    pub notification: Recorded,
+}
+
+/// The notifications understood by `ServerEventReader`, the other ones
+/// aren't kept: a replay goes through the same reader, which would drop
+/// them anyway. The format doesn't change with the one of `sarosa_net`.
+#[derive(Debug, Clone, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum Recorded {
+    ThisIsYou { entity: u64 },
+    NewEntity { entity: u64, x: f32, y: f32, skin: u64, pseudo: String, pv: u64 },
+    EntityHasQuit { entity: u64 },
+    Say { entity: u64, message: String },
+    Position { entity: u64, x: f32, y: f32, speed_x: f32, speed_y: f32, pv: u64 },
+}
+
+impl Recorded {
+
+    pub fn from_notification(notification: &Notification) -> Option<Recorded> {
+        let recorded = match *notification {
+            Notification::ThisIsYou { entity } => Recorded::ThisIsYou { entity: entity },
+            Notification::NewEntity { entity, ref position, skin, ref pseudo, pv } => Recorded::NewEntity {
+                entity: entity,
+                x: position.x,
+                y: position.y,
+                skin: skin as u64,
+                pseudo: pseudo.clone(),
+                pv: pv,
+            },
+            Notification::EntityHasQuit { entity } => Recorded::EntityHasQuit { entity: entity },
+            Notification::Say { entity, ref message } => Recorded::Say {
+                entity: entity,
+                message: message.clone(),
+            },
+            Notification::Position { entity, ref position, ref speed, pv } => Recorded::Position {
+                entity: entity,
+                x: position.x,
+                y: position.y,
+                speed_x: speed.x,
+                speed_y: speed.y,
+                pv: pv,
+            },
+            // Whatever `ServerEventReader::consume_event` ignores, the
+            // game can't tell them apart from the ones not recorded.
+            _ => return None,
+        };
+        Some(recorded)
+    }
+
+    pub fn to_notification(&self) -> Notification {
+        match *self {
+            Recorded::ThisIsYou { entity } => Notification::ThisIsYou { entity: entity },
+            Recorded::NewEntity { entity, x, y, skin, ref pseudo, pv } => Notification::NewEntity {
+                entity: entity,
+                position: Vec2d { x: x, y: y },
+                skin: skin as _,
+                pseudo: pseudo.clone(),
+                pv: pv,
+            },
+            Recorded::EntityHasQuit { entity } => Notification::EntityHasQuit { entity: entity },
+            Recorded::Say { entity, ref message } => Notification::Say {
+                entity: entity,
+                message: message.clone(),
+            },
+            Recorded::Position { entity, x, y, speed_x, speed_y, pv } => Notification::Position {
+                entity: entity,
+                position: Vec2d { x: x, y: y },
+                speed: Vec2d { x: speed_x, y: speed_y },
+                pv: pv,
+            },
+        }
+    }
+}
+
+/// Writes the notifications read from the server to a file,
+/// shared by the reader threads of each connection.
+#[derive(Clone)]
+pub struct Recorder {
+    inner: Arc<Mutex<RecorderInner>>,
+}
+
+struct RecorderInner {
+    file: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+
+    /// Start a recording at `path`, replacing the file if any.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Recorder> {
+        let file = try!(File::create(path));
+        Ok(Recorder {
+            inner: Arc::new(Mutex::new(RecorderInner {
+                file: BufWriter::new(file),
+                start: Instant::now(),
+            })),
+        })
+    }
+
+    pub fn record(&self, notification: &Notification) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let recorded = match Recorded::from_notification(notification) {
+            Some(recorded) => recorded,
+            None => return Ok(()),
+        };
+        let elapsed = inner.start.elapsed();
+        let record = Record {
+            time_ms: elapsed.as_secs() * 1_000 + (elapsed.subsec_nanos() / 1_000_000) as u64,
+            notification: recorded,
+        };
+        write_record(&mut inner.file, &record)
+    }
+
+    /// Make sure what was recorded is in the file,
+    /// should the game stop unexpectedly.
+    pub fn flush(&self) -> io::Result<()> {
+        self.inner.lock().unwrap().file.flush()
+    }
+}
+
+/// Read the recording at `path`.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Record>> {
+    let file = try!(File::open(path));
+    read_records(BufReader::new(file))
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &Record) -> io::Result<()> {
+    let line = try!(json::encode(record).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string())));
+    writeln!(writer, "{}", line)
+}
+
+fn read_records<R: BufRead>(reader: R) -> io::Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = try!(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = try!(json::decode(&line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number + 1, e))
+        }));
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use sarosa_net::messages::Notification;
+    use sarosa_net::messages::Vec2d;
+    use super::{read_records, write_record, Record, Recorded};
+
+    #[test]
+    fn notifications() {
+        let new_entity = Notification::NewEntity {
+            entity: 3,
+            position: Vec2d { x: 1.5, y: -2.0 },
+            skin: 2,
+            pseudo: "Bob".to_string(),
+            pv: 100,
+        };
+        let recorded = Recorded::from_notification(&new_entity).unwrap();
+        assert_eq!(recorded, Recorded::NewEntity {
+            entity: 3, x: 1.5, y: -2.0, skin: 2, pseudo: "Bob".to_string(), pv: 100,
+        });
+        let back = Recorded::from_notification(&recorded.to_notification());
+        assert_eq!(back, Some(recorded));
+    }
+
+    #[test]
+    fn records() {
+        let records = vec![
+            Record { time_ms: 0, notification: Recorded::ThisIsYou { entity: 3 } },
+            Record { time_ms: 40, notification: Recorded::Say {
+                entity: 3,
+                message: "hi\n\"there\"".to_string(),
+            }},
+            Record { time_ms: 75, notification: Recorded::Position {
+                entity: 3, x: 1.0, y: 2.0, speed_x: 0.0, speed_y: -1.0, pv: 100,
+            }},
+        ];
+        let mut file = Vec::new();
+        for record in &records {
+            write_record(&mut file, record).unwrap();
+        }
+        // One line per record, even with line breaks in the messages.
+        assert_eq!(file.iter().filter(|&&b| b == b'\n').count(), 3);
+        assert_eq!(read_records(Cursor::new(file)).unwrap(), records);
+
+        let error = read_records(Cursor::new(b"\n{\"time_ms\": 1}\n".to_vec())).unwrap_err();
+        assert!(error.to_string().starts_with("line 2:"));
+    }
+}
@@ -1,4 +1,3 @@
-use std::cmp::max;
 use std::sync::Arc;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
@@ -8,79 +7,14 @@ use events::{
     UserEventState,
     UserEvent,
 };
+use events::commands;
+use events::commands::CommandStates;
 use sarosa_net::messages::Order;
 
-struct CommandStates {
-    up: u8,
-    down: u8,
-    left: u8,
-    right: u8,
-}
-
-impl CommandStates {
-    pub fn new() -> CommandStates {
-        CommandStates {
-            up: 0,
-            down: 0,
-            left: 0,
-            right: 0,
-        }
-    }
-
-    pub fn inject(&mut self, command: UserEventType, reset: bool) {
-        let max_value = max(max(max(self.up, self.down), self.left), self.right);
-        if reset {
-            let old_value = match command {
-                UserEventType::CmdUp => {
-                    let old_value = self.up;
-                    self.up = 0;
-                    old_value
-                }
-                UserEventType::CmdDown => {
-                    let old_value = self.down;
-                    self.down = 0;
-                    old_value
-                }
-                UserEventType::CmdLeft => {
-                    let old_value = self.left;
-                    self.left = 0;
-                    old_value
-                }
-                UserEventType::CmdRight => {
-                    let old_value = self.right;
-                    self.right = 0;
-                    old_value
-                }
-                _ => return,
-            };
-
-            if self.up    > old_value { self.up    = self.up    - 1 }
-            if self.down  > old_value { self.down  = self.down  - 1 }
-            if self.left  > old_value { self.left  = self.left  - 1 }
-            if self.right > old_value { self.right = self.right - 1 }
-        } else {
-            match command {
-                UserEventType::CmdUp => self.up = max_value + 1,
-                UserEventType::CmdDown => self.down = max_value + 1,
-                UserEventType::CmdLeft => self.left = max_value + 1,
-                UserEventType::CmdRight => self.right = max_value + 1,
-                _ => return,
-            }
-        }
-    }
-
-    pub fn next_direction(&self) -> Option<Direction> {
-        let max_value = max(max(max(self.up, self.down), self.left), self.right);
-        if max_value == 0 { None }
-        else if self.up == max_value { Some(Direction::North) }
-        else if self.down == max_value { Some(Direction::South) }
-        else if self.left == max_value { Some(Direction::West) }
-        else { Some(Direction::East) }
-    }
-}
-
 pub struct UserEventSender {
     commands_states: CommandStates,
+    // Way the server was last told to walk.
+    direction: Option<commands::Direction>,
     this_player_id: Arc<AtomicUsize>,
 }
 
@@ -90,6 +24,7 @@ impl UserEventSender {
         UserEventSender {
             this_player_id: player_id,
             commands_states: CommandStates::new(),
+            direction: None,
         }
     }
 
@@ -109,13 +44,71 @@ pub struct UserEventConsumer<'a> {
 
 impl<'a> UserEventConsumer<'a> {
 
-    pub fn consume_event(&mut self, ue: UserEvent) -> Order {
+    /// The walk order to send after `ue`, if the direction changed.
+    /// Other events than the movement commands are ignored.
+    pub fn consume_event(&mut self, ue: UserEvent) -> Option<Order> {
         let reset = ue.state != UserEventState::Start;
         let command = match ue.kind {
             UserEventType::Quit => unreachable!(),
             kind => kind,
         };
         self.s.commands_states.inject(command, reset);
-        Order::Walk(self.s.commands_states.next_direction())
+        let direction = self.s.commands_states.next_direction();
+        if direction == self.s.direction {
+            return None;
+        }
+        self.s.direction = direction;
+        Some(Order::Walk(direction.map(|d| match d {
+            commands::Direction::North => Direction::North,
+            commands::Direction::South => Direction::South,
+            commands::Direction::East  => Direction::East,
+            commands::Direction::West  => Direction::West,
+        })))
+    }
+
+    /// Way the player walks after the events consumed so far.
+    pub fn direction(&self) -> Option<commands::Direction> {
+        self.s.commands_states.next_direction()
+    }
+
+    pub fn consume_message(&mut self, message: String) -> Order {
+        Order::Say(message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use sarosa_net::messages::{Direction, Order};
+    use events::{UserEvent, UserEventState, UserEventType};
+    use super::UserEventSender;
+
+    fn event(kind: UserEventType, state: UserEventState) -> UserEvent {
+        UserEvent {
+            state: state,
+            kind: kind,
+        }
+    }
+
+    #[test]
+    fn walk_on_change_only() {
+        let mut sender = UserEventSender::new(Arc::new(AtomicUsize::new(0)));
+        let mut converter = sender.prepare_event_consumer();
+
+        for &kind in &[UserEventType::ZoomIn, UserEventType::Chat, UserEventType::Menu, UserEventType::Screenshot] {
+            assert!(converter.consume_event(event(kind, UserEventState::Start)).is_none());
+        }
+        match converter.consume_event(event(UserEventType::CmdUp, UserEventState::Start)) {
+            Some(Order::Walk(Some(Direction::North))) => (),
+            _ => panic!("expected a walk north"),
+        }
+        // Still walking north.
+        assert!(converter.consume_event(event(UserEventType::ChatScrollUp, UserEventState::Start)).is_none());
+        match converter.consume_event(event(UserEventType::CmdUp, UserEventState::Stop)) {
+            Some(Order::Walk(None)) => (),
+            _ => panic!("expected to stop"),
+        }
     }
 }
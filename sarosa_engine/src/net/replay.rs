@@ -0,0 +1,159 @@
+use std::mem;
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use events::UserEventType;
+use super::real::reader::ServerEventReader;
+use super::real::record::{self, Record};
+use super::ClientEvent;
+use super::RemoteServerHandle;
+use super::ServerError;
+use super::ServerEvent;
+
+/// Plays a recording of `net::real` back, as if the server was sending it.
+///
+/// What the player does is ignored, the recording already tells what
+/// happened. At the end of it the world stays as it is.
+pub struct RemoteServer {
+    records: Vec<Record>,
+    speed: f32,
+}
+
+impl RemoteServerHandle for RemoteServer {}
+
+impl RemoteServer {
+
+    /// Play `records` `speed` times faster than they were recorded.
+    pub fn new(records: Vec<Record>, speed: f32) -> RemoteServer {
+        RemoteServer {
+            records: records,
+            speed: speed,
+        }
+    }
+
+    /// Read the recording at `path`.
+    pub fn load(path: &str, speed: f32) -> Result<RemoteServer, ServerError> {
+        let records = try!(record::load(path).map_err(|e| {
+            ServerError::Replay(format!("`{}`: {}", path, e))
+        }));
+        Ok(RemoteServer::new(records, speed))
+    }
+
+    pub fn start_writer_thread(&mut self, rx_user: Receiver<ClientEvent>, _: Sender<()>) {
+
+        thread::Builder::new()
+            .name("NetworkReplay - Writer".to_string())
+            .spawn(move|| {
+
+            for event in rx_user.iter() {
+                if let ClientEvent::User(ue) = event {
+                    if ue.kind == UserEventType::Quit {
+                        break;
+                    }
+                }
+            }
+        }).expect("Couldn't start thread");
+    }
+
+    pub fn start_reader_thread(&mut self, tx_serv: Sender<ServerEvent>, rx_error_reader: Receiver<()>) {
+
+        let records = mem::replace(&mut self.records, Vec::new());
+        let speed = self.speed;
+        thread::Builder::new()
+            .name("NetworkReplay - Reader".to_string())
+            .spawn(move|| {
+
+            let mut converter = ServerEventReader::new(Arc::new(AtomicUsize::new(0)));
+            let start = Instant::now();
+
+            for record in records {
+                // Wait until the notification was read, unless told to stop.
+                let due = start + scaled(record.time_ms, speed);
+                let now = Instant::now();
+                if due > now {
+                    match rx_error_reader.recv_timeout(due - now) {
+                        Err(RecvTimeoutError::Timeout) => (),
+                        _ => return,
+                    }
+                }
+                if let Some(server_event) = converter.consume_event(record.notification.to_notification()) {
+                    if tx_serv.send(server_event).is_err() {
+                        return;
+                    }
+                }
+            }
+
+            info!("End of the replay");
+            let _ = rx_error_reader.recv();
+        }).expect("Couldn't start thread");
+    }
+}
+
+// Time of a record once played at `speed`.
+fn scaled(time_ms: u64, speed: f32) -> Duration {
+    let nanos = (time_ms as f64 * 1_000_000.0 / speed as f64) as u64;
+    Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::mpsc::channel;
+    use std::time::{Duration, Instant};
+
+    use models::player::THIS_PLAYER;
+    use net::ServerEvent;
+    use net::real::record::{Record, Recorded};
+    use super::{scaled, RemoteServer};
+
+    #[test]
+    fn replay() {
+        let records = vec![
+            Record { time_ms: 0, notification: Recorded::ThisIsYou { entity: 3 } },
+            Record { time_ms: 0, notification: Recorded::NewEntity {
+                entity: 3, x: 1.0, y: 2.0, skin: 0, pseudo: "Bob".to_string(), pv: 100,
+            }},
+            Record { time_ms: 1_000, notification: Recorded::Position {
+                entity: 3, x: 1.0, y: 3.0, speed_x: 0.0, speed_y: 1.0, pv: 100,
+            }},
+            Record { time_ms: 2_000, notification: Recorded::Say { entity: 4, message: "hi".to_string() } },
+        ];
+        let mut server = RemoteServer::new(records, 100.0);
+        let (tx_serv, rx_serv) = channel();
+        let (tx_error, rx_error) = channel();
+        let start = Instant::now();
+        server.start_reader_thread(tx_serv, rx_error);
+
+        let timeout = Duration::from_secs(5);
+        match rx_serv.recv_timeout(timeout).unwrap() {
+            ServerEvent::NewPlayer { id, ref info, .. } => {
+                assert_eq!(id, THIS_PLAYER);
+                assert_eq!(info.name, "Bob");
+            }
+            _ => panic!("expected a new player"),
+        }
+        match rx_serv.recv_timeout(timeout).unwrap() {
+            ServerEvent::Position { id, pos, .. } => assert_eq!((id, pos.y), (THIS_PLAYER, 3.0)),
+            _ => panic!("expected a position"),
+        }
+        match rx_serv.recv_timeout(timeout).unwrap() {
+            ServerEvent::Chat { id, ref message } => assert_eq!((id, &message[..]), (4, "hi")),
+            _ => panic!("expected a message"),
+        }
+        // Played a hundred times faster.
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        let _ = tx_error.send(());
+    }
+
+    #[test]
+    fn speed() {
+        assert_eq!(scaled(1_500, 1.0), Duration::from_millis(1_500));
+        assert_eq!(scaled(1_500, 2.0), Duration::from_millis(750));
+        assert_eq!(scaled(1_500, 0.5), Duration::from_millis(3_000));
+    }
+}
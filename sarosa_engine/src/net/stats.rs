@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use events::commands::Direction;
+use models::player::THIS_PLAYER;
+use net::ServerEvent;
+
+// Rates are measured over this long.
+const RATE_WINDOW_MS: u64 = 1_000;
+// Orders the server didn't acknowledge by then are forgotten,
+// it may not answer some of them, such as a walk against a wall.
+const ACK_TIMEOUT_MS: u64 = 5_000;
+
+/// What is known of the quality of the connection, see `Server::stats`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct NetStats {
+    /// Smoothed round trip time, measured with the
+    /// orders acknowledged by the server.
+    pub rtt: Option<Duration>,
+    /// From the client to the server.
+    pub sent: Rate,
+    /// From the server to the client.
+    pub received: Rate,
+    /// Time since the server last said something.
+    pub since_last_message: Option<Duration>,
+}
+
+/// Traffic in one direction, per second.
+///
+/// `sarosa_net` doesn't tell how much it reads and writes,
+/// the bytes are estimated from the fields of the messages.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Rate {
+    pub messages: f32,
+    pub bytes: f32,
+}
+
+impl fmt::Display for NetStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.rtt {
+            Some(rtt) => try!(write!(f, "rtt {} ms", millis(rtt))),
+            None => try!(write!(f, "rtt unknown")),
+        }
+        try!(write!(f, ", sent {:.1} msg/s ({:.0} B/s)", self.sent.messages, self.sent.bytes));
+        try!(write!(f, ", received {:.1} msg/s ({:.0} B/s)", self.received.messages, self.received.bytes));
+        match self.since_last_message {
+            Some(since) => write!(f, ", last message {} ms ago", millis(since)),
+            None => write!(f, ", no message yet"),
+        }
+    }
+}
+
+/// An order whose effect the server tells back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ack {
+    /// The player walks that way, or stands still.
+    Walk(Option<Direction>),
+    /// The server broadcasts what the player says.
+    Say(String),
+}
+
+impl Ack {
+
+    /// The order acknowledged by an event from the server, if any.
+    pub fn of(event: &ServerEvent) -> Option<Ack> {
+        match *event {
+            ServerEvent::Position { id, speed, .. } if id == THIS_PLAYER => Some(Ack::Walk(Direction::of_speed(speed))),
+            ServerEvent::Chat { id, ref message } if id == THIS_PLAYER => Some(Ack::Say(message.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Counts the traffic of the network threads, shared with them.
+#[derive(Clone)]
+pub struct StatsRecorder {
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl StatsRecorder {
+
+    pub fn new() -> StatsRecorder {
+        StatsRecorder {
+            counters: Arc::new(Mutex::new(Counters::new(Instant::now()))),
+        }
+    }
+
+    /// A message of `bytes` was sent, the server is expected
+    /// to acknowledge it if `ack` is given.
+    pub fn sent(&self, bytes: usize, ack: Option<Ack>) {
+        // The time is taken once locked to keep it in order.
+        let mut counters = self.counters.lock().unwrap();
+        counters.sent(Instant::now(), bytes, ack);
+    }
+
+    /// A message of `bytes` was received, acknowledging `ack` if given.
+    pub fn received(&self, bytes: usize, ack: Option<Ack>) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.received(Instant::now(), bytes, ack);
+    }
+
+    pub fn stats(&self) -> NetStats {
+        let mut counters = self.counters.lock().unwrap();
+        counters.stats(Instant::now())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct Traffic {
+    messages: u64,
+    bytes: u64,
+}
+
+impl Traffic {
+
+    fn add(&mut self, bytes: usize) {
+        self.messages += 1;
+        self.bytes += bytes as u64;
+    }
+
+    fn rate(&self, elapsed: Duration) -> Rate {
+        let secs = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1e9;
+        Rate {
+            messages: self.messages as f32 / secs,
+            bytes: self.bytes as f32 / secs,
+        }
+    }
+}
+
+struct Counters {
+    // Traffic since the start of the window.
+    window_start: Instant,
+    sent: Traffic,
+    received: Traffic,
+    // Rates of the last full window.
+    sent_rate: Rate,
+    received_rate: Rate,
+    last_received: Option<Instant>,
+    rtt: Option<Duration>,
+    // Orders not yet acknowledged, oldest first.
+    pending: VecDeque<(Ack, Instant)>,
+}
+
+impl Counters {
+
+    fn new(now: Instant) -> Counters {
+        Counters {
+            window_start: now,
+            sent: Traffic::default(),
+            received: Traffic::default(),
+            sent_rate: Rate::default(),
+            received_rate: Rate::default(),
+            last_received: None,
+            rtt: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn sent(&mut self, now: Instant, bytes: usize, ack: Option<Ack>) {
+        self.roll(now);
+        self.sent.add(bytes);
+        if let Some(ack) = ack {
+            self.pending.push_back((ack, now));
+        }
+    }
+
+    fn received(&mut self, now: Instant, bytes: usize, ack: Option<Ack>) {
+        self.roll(now);
+        self.received.add(bytes);
+        self.last_received = Some(now);
+
+        let timeout = Duration::from_millis(ACK_TIMEOUT_MS);
+        while self.pending.front().map_or(false, |&(_, sent)| now.duration_since(sent) > timeout) {
+            self.pending.pop_front();
+        }
+        let index = match ack {
+            Some(ack) => self.pending.iter().position(|&(ref pending, _)| *pending == ack),
+            None => None,
+        };
+        if let Some(index) = index {
+            let sample = now.duration_since(self.pending[index].1);
+            // The orders sent before have been handled too.
+            for _ in 0..index + 1 {
+                self.pending.pop_front();
+            }
+            // As TCP does, a sample only moves the estimate by an eighth.
+            self.rtt = Some(match self.rtt {
+                Some(rtt) => (rtt * 7 + sample) / 8,
+                None => sample,
+            });
+        }
+    }
+
+    fn stats(&mut self, now: Instant) -> NetStats {
+        self.roll(now);
+        NetStats {
+            rtt: self.rtt,
+            sent: self.sent_rate,
+            received: self.received_rate,
+            since_last_message: self.last_received.map(|last| now.duration_since(last)),
+        }
+    }
+
+    // Start a new window once the current one is over.
+    fn roll(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed >= Duration::from_millis(RATE_WINDOW_MS) {
+            self.sent_rate = self.sent.rate(elapsed);
+            self.received_rate = self.received.rate(elapsed);
+            self.sent = Traffic::default();
+            self.received = Traffic::default();
+            self.window_start = now;
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::{Duration, Instant};
+    use cgmath::Vector2;
+
+    use events::commands::Direction;
+    use models::player::THIS_PLAYER;
+    use net::ServerEvent;
+    use super::{Ack, Counters, Rate, ACK_TIMEOUT_MS};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn rates() {
+        let start = Instant::now();
+        let mut counters = Counters::new(start);
+        for i in 0..10 {
+            counters.sent(start + ms(i * 100), 20, None);
+        }
+        counters.received(start + ms(500), 100, None);
+        // Nothing is known before the end of the first window.
+        assert_eq!(counters.stats(start + ms(900)).sent, Rate::default());
+
+        let stats = counters.stats(start + ms(2_000));
+        assert_eq!(stats.sent, Rate { messages: 5.0, bytes: 100.0 });
+        assert_eq!(stats.received, Rate { messages: 0.5, bytes: 50.0 });
+        assert_eq!(stats.since_last_message, Some(ms(1_500)));
+        assert_eq!(stats.rtt, None);
+    }
+
+    #[test]
+    fn round_trip_time() {
+        let start = Instant::now();
+        let mut counters = Counters::new(start);
+        counters.sent(start, 10, Some(Ack::Walk(Some(Direction::North))));
+        counters.sent(start + ms(20), 10, Some(Ack::Walk(None)));
+        counters.sent(start + ms(30), 10, Some(Ack::Say("hi".to_string())));
+
+        // Only what was asked for acknowledges an order.
+        counters.received(start + ms(50), 10, Some(Ack::Walk(Some(Direction::East))));
+        assert_eq!(counters.stats(start + ms(50)).rtt, None);
+        // The walk north is skipped, the server must have handled it.
+        counters.received(start + ms(100), 10, Some(Ack::Walk(None)));
+        assert_eq!(counters.stats(start + ms(100)).rtt, Some(ms(80)));
+        counters.received(start + ms(190), 10, Some(Ack::Say("hi".to_string())));
+        assert_eq!(counters.stats(start + ms(190)).rtt, Some(ms(90)));
+        assert!(counters.pending.is_empty());
+
+        // Too late to be measured.
+        counters.sent(start + ms(200), 10, Some(Ack::Walk(None)));
+        counters.received(start + ms(201 + ACK_TIMEOUT_MS), 10, Some(Ack::Walk(None)));
+        assert_eq!(counters.stats(start + ms(201 + ACK_TIMEOUT_MS)).rtt, Some(ms(90)));
+    }
+
+    #[test]
+    fn acknowledgements() {
+        let position = |x, y| ServerEvent::Position {
+            pos: Vector2::new(0.0, 0.0),
+            speed: Vector2::new(x, y),
+            id: THIS_PLAYER,
+        };
+        assert_eq!(Ack::of(&position(0.0, 0.0)), Some(Ack::Walk(None)));
+        assert_eq!(Ack::of(&position(0.0, 2.0)), Some(Ack::Walk(Some(Direction::North))));
+        assert_eq!(Ack::of(&position(-2.0, 0.1)), Some(Ack::Walk(Some(Direction::West))));
+        let other = ServerEvent::Chat { id: THIS_PLAYER + 1, message: "hi".to_string() };
+        assert_eq!(Ack::of(&other), None);
+    }
+}
@@ -1,19 +1,340 @@
-
-use models::Player;
 use cgmath::Vector2;
 
-struct PhysicsEngine;
+use models::player::Player;
+use models::map::GameMap;
+use unit::TILE_SIZE;
+
+// Gap kept between a moving box and the obstacle that stopped it,
+// so that rounding errors never make them overlap.
+const SKIN: f32 = 1e-4;
+
+/// Speed of a walking player, in game units per second.
+pub const WALK_SPEED: f32 = 8.0;
+
+/// Axis-aligned bounding box, in game units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+impl Aabb {
 
+    pub fn new(min: Vector2<f32>, max: Vector2<f32>) -> Aabb {
+        Aabb {
+            min: min,
+            max: max,
+        }
+    }
+
+    pub fn translate(&self, offset: &Vector2<f32>) -> Aabb {
+        Aabb::new(self.min + *offset, self.max + *offset)
+    }
+
+    /// Returns true if the two boxes overlap. Boxes
+    /// that are only touching do not intersect.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.overlaps_x(other) && self.overlaps_y(other)
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    fn overlaps_x(&self, other: &Aabb) -> bool {
+        self.min.x < other.max.x && other.min.x < self.max.x
+    }
+
+    fn overlaps_y(&self, other: &Aabb) -> bool {
+        self.min.y < other.max.y && other.min.y < self.max.y
+    }
+
+    fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            Vector2::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            Vector2::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        )
+    }
+}
+
+/// Resolves player movements against the collisions of a map.
+///
+/// Only pure computations are done here so that the same rules
+/// can be used by the client and by the offline server.
+pub struct PhysicsEngine {
+    // Hitbox relative to the position of a player.
+    hitbox: Aabb,
+}
 
 impl PhysicsEngine {
 
-    pub fn move_player(&self, player: &mut Player, offset: &Vector2<f32>) {
-        // TODO check possibility of move.
-        player.position = player.position + *offset;
+    pub fn new() -> PhysicsEngine {
+        // Only the feet of the player collide, so that
+        // the head can overlap what is drawn behind.
+        PhysicsEngine::with_hitbox(Aabb::new(Vector2::new(-0.75, -2.0), Vector2::new(0.75, -1.0)))
+    }
+
+    pub fn with_hitbox(hitbox: Aabb) -> PhysicsEngine {
+        PhysicsEngine {
+            hitbox: hitbox,
+        }
+    }
+
+    pub fn move_player(&self, map: &GameMap, player: &mut Player, offset: &Vector2<f32>) {
+        player.position = self.resolve_move(map, &player.position, offset);
+    }
+
+    pub fn set_player_at(&self, map: &GameMap, player: &mut Player, position: &Vector2<f32>) {
+        player.position = self.closest_free_position(map, position);
+    }
+
+    /// Returns true if a player can stand at the given position.
+    pub fn is_free(&self, map: &GameMap, position: &Vector2<f32>) -> bool {
+        let hitbox = self.hitbox.translate(position);
+        self.solids(map, &hitbox).iter().all(|solid| !solid.intersects(&hitbox))
+    }
+
+    /// Returns the position reached when moving from `position` by `offset`.
+    ///
+    /// The movement is done along x and then along y, each axis stopping
+    /// at the first obstacle met. As a result, a player pushing diagonally
+    /// against a wall slides along it.
+    pub fn resolve_move(&self, map: &GameMap, position: &Vector2<f32>, offset: &Vector2<f32>)
+        -> Vector2<f32>
+    {
+        let mut position = *position;
+        position.x += self.sweep(map, &position, offset.x, Axis::X);
+        position.y += self.sweep(map, &position, offset.y, Axis::Y);
+        position
+    }
+
+    /// Returns `position` if a player can stand there, otherwise
+    /// the closest position on a free tile.
+    pub fn closest_free_position(&self, map: &GameMap, position: &Vector2<f32>) -> Vector2<f32> {
+        if self.is_free(map, position) {
+            return *position;
+        }
+
+        let (column, row) = map.tile_at_unchecked(position);
+        let max_radius = map.width().max(map.height()) as i64;
+        let hitbox_center = self.hitbox.center();
+
+        for radius in 1..max_radius + 1 {
+            let mut best: Option<(f32, Vector2<f32>)> = None;
+
+            for r in row - radius..row + radius + 1 {
+                for c in column - radius..column + radius + 1 {
+                    // Only look at the border of the square.
+                    if (r - row).abs() != radius && (c - column).abs() != radius {
+                        continue;
+                    }
+                    let candidate = map.tile_center_unchecked(c, r) - hitbox_center;
+                    if !self.is_free(map, &candidate) {
+                        continue;
+                    }
+                    let delta = candidate - *position;
+                    let distance = delta.x * delta.x + delta.y * delta.y;
+                    if best.map_or(true, |(d, _)| distance < d) {
+                        best = Some((distance, candidate));
+                    }
+                }
+            }
+
+            if let Some((_, candidate)) = best {
+                return candidate;
+            }
+        }
+
+        warn!("No free position found on the map near ({}, {})", position.x, position.y);
+        *position
+    }
+
+    // Returns how far the hitbox can go along the axis
+    // before hitting something, up to `delta`.
+    fn sweep(&self, map: &GameMap, position: &Vector2<f32>, delta: f32, axis: Axis) -> f32 {
+        if delta == 0.0 {
+            return 0.0;
+        }
+
+        let start = self.hitbox.translate(position);
+        let end = start.translate(&axis.vector(delta));
+        let mut delta = delta;
+
+        for solid in self.solids(map, &start.union(&end)) {
+            // Solids that are not on the way or that already overlap
+            // the player are ignored. The latter allows to get out of
+            // a wall if a player ended up there.
+            let (on_the_way, gap) = match axis {
+                Axis::X if delta > 0.0 => (start.overlaps_y(&solid), solid.min.x - start.max.x),
+                Axis::X                => (start.overlaps_y(&solid), start.min.x - solid.max.x),
+                Axis::Y if delta > 0.0 => (start.overlaps_x(&solid), solid.min.y - start.max.y),
+                Axis::Y                => (start.overlaps_x(&solid), start.min.y - solid.max.y),
+            };
+            if !on_the_way || gap < -SKIN {
+                continue;
+            }
+            let allowed = (gap - SKIN).max(0.0);
+            delta = if delta > 0.0 { delta.min(allowed) } else { delta.max(-allowed) };
+        }
+
+        delta
     }
 
-    pub fn set_player_at(&self, player: &mut Player, position: &Vector2<f32>) {
-        // TODO check the position / set the closest one.
-        player.position = *position;
+    // All the solid parts of the tiles covered by `area`.
+    fn solids(&self, map: &GameMap, area: &Aabb) -> Vec<Aabb> {
+        let (min_column, max_row) = map.tile_at_unchecked(&area.min);
+        let (max_column, min_row) = map.tile_at_unchecked(&area.max);
+        let mut solids = Vec::new();
+
+        for row in min_row..max_row + 1 {
+            for column in min_column..max_column + 1 {
+                if let Some(shape) = map.collision(column, row) {
+                    let corner = map.tile_center_unchecked(column, row)
+                        - Vector2::new(TILE_SIZE / 2.0, TILE_SIZE / 2.0);
+                    let min = corner + Vector2::new(shape.x, shape.y) * TILE_SIZE;
+                    let size = Vector2::new(shape.width, shape.height) * TILE_SIZE;
+                    solids.push(Aabb::new(min, min + size));
+                }
+            }
+        }
+
+        solids
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Axis {
+    X,
+    Y,
+}
+
+impl Axis {
+    fn vector(&self, value: f32) -> Vector2<f32> {
+        match *self {
+            Axis::X => Vector2::new(value, 0.0),
+            Axis::Y => Vector2::new(0.0, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use cgmath::Vector2;
+    use models::map::GameMap;
+    use super::{Aabb, PhysicsEngine};
+
+    // Tile (column, row) is centered on ((column - 2) * 2, (2 - row) * 2).
+    const MAP: &'static str = r#"
+        width = 5
+        height = 5
+
+        [tileset]
+        path = "tiles.png"
+        columns = 30
+        rows = 16
+
+        [[layer]]
+        name = "ground"
+        tiles = """
+        7 7 7 7 7
+        7 7 7 7 7
+        7 7 7 7 7
+        7 7 7 7 7
+        7 7 7 7 7
+        """
+
+        [collision]
+        tiles = """
+        # # # # #
+        # . . . #
+        # . # . #
+        # . . v #
+        # # # # #
+        """
+
+        [[collision.shape]]
+        symbol = "v"
+        x = 0.0
+        y = 0.0
+        width = 1.0
+        height = 0.5
+    "#;
+
+    fn engine() -> PhysicsEngine {
+        PhysicsEngine::with_hitbox(Aabb::new(Vector2::new(-0.5, -0.5), Vector2::new(0.5, 0.5)))
+    }
+
+    fn map() -> GameMap {
+        MAP.parse().unwrap()
+    }
+
+    #[test]
+    fn free_move() {
+        let pos = engine().resolve_move(&map(), &Vector2::new(-2.0, 2.0), &Vector2::new(1.0, -0.25));
+        assert_relative_eq!(pos.x, -1.0);
+        assert_relative_eq!(pos.y, 1.75);
+    }
+
+    #[test]
+    fn stop_against_wall() {
+        let pos = engine().resolve_move(&map(), &Vector2::new(-2.0, 2.0), &Vector2::new(10.0, 0.0));
+        assert_relative_eq!(pos.x, 2.5, epsilon = 1e-3);
+        assert_relative_eq!(pos.y, 2.0);
+    }
+
+    #[test]
+    fn no_tunneling_through_obstacle() {
+        // The center tile is blocked, a big step must not jump over it.
+        let pos = engine().resolve_move(&map(), &Vector2::new(-2.0, 0.0), &Vector2::new(4.0, 0.0));
+        assert_relative_eq!(pos.x, -1.5, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn slide_along_wall() {
+        // Going up-right while touching the top wall keeps moving right.
+        let pos = engine().resolve_move(&map(), &Vector2::new(-2.0, 2.5), &Vector2::new(1.0, 1.0));
+        assert_relative_eq!(pos.x, -1.0);
+        assert_relative_eq!(pos.y, 2.5, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn partial_shape() {
+        // The bottom half of tile (3, 3) is blocked, its top half is free.
+        let map = map();
+        let engine = engine();
+        assert!(engine.is_free(&map, &Vector2::new(2.0, -1.4)));
+        assert!(!engine.is_free(&map, &Vector2::new(2.0, -2.0)));
+
+        let pos = engine.resolve_move(&map, &Vector2::new(2.0, 2.0), &Vector2::new(0.0, -10.0));
+        assert_relative_eq!(pos.y, -1.5, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn map_border_is_blocked() {
+        let map = map();
+        let engine = engine();
+        assert!(!engine.is_free(&map, &Vector2::new(-10.0, 0.0)));
+    }
+
+    #[test]
+    fn get_out_of_wall() {
+        // A player stuck in the center tile can walk out of it.
+        let pos = engine().resolve_move(&map(), &Vector2::new(0.0, 0.0), &Vector2::new(2.0, 0.0));
+        assert_relative_eq!(pos.x, 2.0);
+    }
+
+    #[test]
+    fn closest_free_position() {
+        let map = map();
+        let engine = engine();
+
+        let free = Vector2::new(-2.0, 2.0);
+        assert_eq!(engine.closest_free_position(&map, &free), free);
+
+        let pos = engine.closest_free_position(&map, &Vector2::new(0.2, 0.0));
+        assert!(engine.is_free(&map, &pos));
+        assert_relative_eq!(pos.x, 2.0);
+        assert_relative_eq!(pos.y, 0.0);
     }
 }
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::Read;
+use std::fs::File;
+use std::fmt;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Font used for all the text of the game.
+pub const DEFAULT_FONT_PATH: &'static str = "./assets/fonts/dejavu_sans_mono_12.fnt";
+
+// Drawn in place of the characters missing from the font.
+const REPLACEMENT_CHAR: char = '?';
+
+/// Position of a glyph in the atlas and how to place it, in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Glyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Offset from the pen position to the left of the glyph.
+    pub x_offset: i32,
+    /// Offset from the top of the line to the top of the glyph.
+    pub y_offset: i32,
+    pub x_advance: i32,
+}
+
+/// A bitmap font described with the BMFont text format.
+///
+/// All the glyphs must be in a single texture, the atlas.
+#[derive(Debug)]
+pub struct Font {
+    line_height: u32,
+    atlas: PathBuf,
+    atlas_size: (u32, u32),
+    glyphs: HashMap<char, Glyph>,
+    kernings: HashMap<(char, char), i32>,
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(io::Error),
+    Invalid(String),
+}
+
+impl Font {
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Font, FontError> {
+        let mut content = String::new();
+        let mut file = try!(File::open(path.as_ref()));
+        try!(file.read_to_string(&mut content));
+        let mut font: Font = try!(content.parse());
+
+        // The atlas is relative to the font description.
+        if let Some(dir) = path.as_ref().parent() {
+            font.atlas = dir.join(&font.atlas);
+        }
+        Ok(font)
+    }
+
+    /// Path of the texture containing the glyphs.
+    pub fn atlas(&self) -> &Path {
+        &self.atlas
+    }
+
+    /// Size of the atlas in pixels.
+    pub fn atlas_size(&self) -> (u32, u32) {
+        self.atlas_size
+    }
+
+    /// Distance between two lines, in pixels.
+    pub fn line_height(&self) -> u32 {
+        self.line_height
+    }
+
+    /// Returns the glyph of `c`, or a replacement glyph
+    /// if the font doesn't have it.
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&REPLACEMENT_CHAR))
+    }
+
+    /// Adjustment of the advance between `first` and `second`, in pixels.
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        self.kernings.get(&(first, second)).cloned().unwrap_or(0)
+    }
+
+    /// Horizontal position of each character of `text` relative
+    /// to the start of the line, followed by the width of the line.
+    pub fn advances(&self, text: &str) -> Vec<i32> {
+        let mut advances = Vec::with_capacity(text.len() + 1);
+        let mut pen = 0;
+        let mut previous = None;
+        for c in text.chars() {
+            if let Some(p) = previous {
+                pen += self.kerning(p, c);
+            }
+            advances.push(pen);
+            pen += self.glyph(c).map_or(0, |g| g.x_advance);
+            previous = Some(c);
+        }
+        advances.push(pen);
+        advances
+    }
+
+    /// Width of the widest line of `text`, in pixels.
+    pub fn width(&self, text: &str) -> u32 {
+        text.lines()
+            .map(|line| *self.advances(line).last().unwrap())
+            .max()
+            .unwrap_or(0)
+            .max(0) as u32
+    }
+
+    /// Height of `text`, in pixels.
+    pub fn height(&self, text: &str) -> u32 {
+        text.lines().count() as u32 * self.line_height
+    }
+
+    /// Split `text` in lines no wider than `max_width` pixels.
+    ///
+    /// Lines are broken between words when possible, words
+    /// longer than a line are broken anywhere.
+    pub fn wrap(&self, text: &str, max_width: u32) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+
+        for word in text.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if self.width(&candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(line);
+                line = String::new();
+            }
+            for c in word.chars() {
+                line.push(c);
+                if self.width(&line) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(line);
+                    line = c.to_string();
+                }
+            }
+        }
+
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+impl FromStr for Font {
+    type Err = FontError;
+
+    fn from_str(s: &str) -> Result<Font, FontError> {
+        let mut line_height = None;
+        let mut atlas = None;
+        let mut atlas_size = None;
+        let mut glyphs = HashMap::new();
+        let mut kernings = HashMap::new();
+
+        for (n, line) in s.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let tag = match words.next() {
+                Some(tag) => tag,
+                None => continue,
+            };
+            let attributes = try!(parse_attributes(n, words));
+            let get = |name: &str| -> Result<i32, FontError> {
+                attributes.get(name)
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| invalid(n, format!("missing or invalid `{}`", name)))
+            };
+
+            match tag {
+                "common" => {
+                    if try!(get("pages")) != 1 {
+                        return Err(invalid(n, "only fonts with a single page are supported"));
+                    }
+                    line_height = Some(try!(get("lineHeight")) as u32);
+                    atlas_size = Some((try!(get("scaleW")) as u32, try!(get("scaleH")) as u32));
+                }
+                "page" => {
+                    let file = try!(attributes.get("file").ok_or_else(|| invalid(n, "missing `file`")));
+                    atlas = Some(PathBuf::from(file.trim_matches('"')));
+                }
+                "char" => {
+                    let id = try!(get("id")) as u32;
+                    let c = try!(::std::char::from_u32(id).ok_or_else(||
+                        invalid(n, format!("invalid character {}", id))));
+                    glyphs.insert(c, Glyph {
+                        x: try!(get("x")) as u32,
+                        y: try!(get("y")) as u32,
+                        width: try!(get("width")) as u32,
+                        height: try!(get("height")) as u32,
+                        x_offset: try!(get("xoffset")),
+                        y_offset: try!(get("yoffset")),
+                        x_advance: try!(get("xadvance")),
+                    });
+                }
+                "kerning" => {
+                    let first = try!(get("first")) as u32;
+                    let second = try!(get("second")) as u32;
+                    match (::std::char::from_u32(first), ::std::char::from_u32(second)) {
+                        (Some(first), Some(second)) => {
+                            kernings.insert((first, second), try!(get("amount")));
+                        }
+                        _ => return Err(invalid(n, "invalid kerning pair")),
+                    }
+                }
+                // `info`, `chars` and `kernings` are not needed.
+                _ => (),
+            }
+        }
+
+        Ok(Font {
+            line_height: try!(line_height.ok_or_else(|| invalid(0, "missing `common` line"))),
+            atlas: try!(atlas.ok_or_else(|| invalid(0, "missing `page` line"))),
+            atlas_size: atlas_size.unwrap(),
+            glyphs: glyphs,
+            kernings: kernings,
+        })
+    }
+}
+
+// Attributes look like `name=value`, quoted values can't contain spaces.
+fn parse_attributes<'a, I>(n: usize, words: I) -> Result<HashMap<&'a str, &'a str>, FontError>
+    where I: Iterator<Item=&'a str>
+{
+    let mut attributes = HashMap::new();
+    for word in words {
+        let mut parts = word.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => { attributes.insert(name, value); }
+            _ => return Err(invalid(n, format!("invalid attribute `{}`", word))),
+        }
+    }
+    Ok(attributes)
+}
+
+fn invalid<S: Into<String>>(line: usize, reason: S) -> FontError {
+    FontError::Invalid(format!("line {}: {}", line + 1, reason.into()))
+}
+
+impl From<io::Error> for FontError {
+    fn from(e: io::Error) -> FontError {
+        FontError::Io(e)
+    }
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FontError::Io(ref e) => write!(f, "{}", e),
+            FontError::Invalid(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for FontError {
+    fn description(&self) -> &str {
+        match *self {
+            FontError::Io(ref e) => e.description(),
+            FontError::Invalid(_) => "invalid font",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Font, FontError};
+
+    // A tiny monospace font: every glyph is 4 pixels wide.
+    const FONT: &'static str = r#"
+info face="test" size=6 bold=0 italic=0
+common lineHeight=8 base=6 scaleW=64 scaleH=32 pages=1 packed=0
+page id=0 file="test.png"
+chars count=5
+char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4 page=0 chnl=15
+char id=63 x=1 y=1 width=3 height=6 xoffset=0 yoffset=1 xadvance=4 page=0 chnl=15
+char id=97 x=5 y=1 width=3 height=4 xoffset=0 yoffset=3 xadvance=4 page=0 chnl=15
+char id=98 x=9 y=1 width=3 height=6 xoffset=0 yoffset=1 xadvance=4 page=0 chnl=15
+char id=233 x=13 y=1 width=3 height=6 xoffset=0 yoffset=1 xadvance=4 page=0 chnl=15
+kernings count=1
+kerning first=97 second=98 amount=-1
+"#;
+
+    #[test]
+    fn parse_font() {
+        let font: Font = FONT.parse().unwrap();
+        assert_eq!(font.line_height(), 8);
+        assert_eq!(font.atlas_size(), (64, 32));
+        assert_eq!(font.atlas().to_str(), Some("test.png"));
+
+        let glyph = font.glyph('a').unwrap();
+        assert_eq!((glyph.x, glyph.y, glyph.width, glyph.height), (5, 1, 3, 4));
+        assert_eq!(glyph.y_offset, 3);
+    }
+
+    #[test]
+    fn utf8_and_replacement() {
+        let font: Font = FONT.parse().unwrap();
+        assert_eq!(font.glyph('é').unwrap().x, 13);
+        assert_eq!(font.glyph('€'), font.glyph('?'));
+        assert_eq!(font.width("bé€"), 12);
+    }
+
+    #[test]
+    fn kerning_and_lines() {
+        let font: Font = FONT.parse().unwrap();
+        assert_eq!(font.kerning('a', 'b'), -1);
+        assert_eq!(font.kerning('b', 'a'), 0);
+        assert_eq!(font.advances("aba"), vec![0, 3, 7, 11]);
+        assert_eq!(font.width("ab\naaaa"), 16);
+        assert_eq!(font.height("ab\naaaa"), 16);
+    }
+
+    #[test]
+    fn wrap_words() {
+        let font: Font = FONT.parse().unwrap();
+        assert_eq!(font.wrap("ab ab  ba", 20), vec!["ab ab", "ba"]);
+        assert_eq!(font.wrap("aaaaaaa b", 12), vec!["aaa", "aaa", "a b"]);
+        assert!(font.wrap("   ", 12).is_empty());
+    }
+
+    #[test]
+    fn invalid_fonts() {
+        match "common lineHeight=8 pages=2".parse::<Font>() {
+            Err(FontError::Invalid(_)) => (),
+            _ => panic!("multiple pages should be rejected"),
+        }
+        match "char id=97 x".parse::<Font>() {
+            Err(FontError::Invalid(_)) => (),
+            _ => panic!("invalid attribute should be rejected"),
+        }
+    }
+}
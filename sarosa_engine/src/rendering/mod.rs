@@ -1,12 +1,21 @@
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use image;
 use cgmath::{self, Matrix4};
 use glium::glutin;
 use glium::DisplayBuild;
 use glium::backend::glutin_backend::GlutinFacade;
+use glium::Frame;
+use glium::texture::RawImage2d;
 use glium::glutin::{
     Event,
     ElementState,
+    VirtualKeyCode,
 };
 use events::{
+    MenuKey,
     PushEvent,
     UserEvent,
     UserEventType
@@ -15,44 +24,94 @@ use events::UserEventState::{
     Start,
     Stop
 };
+use events::gamepad::Gamepads;
+use models::chat::MAX_MESSAGE_LENGTH;
 use Settings;
+use models::settings::DisplayMode;
 
-pub use self::renderer::GameRenderer;
+pub use self::renderer::{GameRenderer, MenuRenderer, Textures, PLAYER_SKINS};
 
 pub mod scene;
+pub mod font;
 
 // Private modules
 mod renderer;
 mod camera;
 
 /// Window sarosa object.
+///
+/// Depending on the `DisplayMode` from the settings, this is
+/// either a real window or an offscreen context.
 pub struct Window {
     display: GlutinFacade,
     settings: Settings,
     projection: Matrix4<f32>,
+    dimensions: (u32, u32),
+    headless: bool,
+    gamepads: Option<Gamepads>,
+    // Message being typed, keys are not mapped to commands meanwhile.
+    text_input: Option<String>,
+    // Held to go back with tab in the menus.
+    shift_pressed: bool,
 }
 
 impl Window {
 
     pub fn new<T: ToString>(settings: Settings, window_title: T) -> Window {
-        let width = settings.window().width();
-        let height = settings.window().height();
+        let mut width = settings.window().width();
+        let mut height = settings.window().height();
+        let headless = settings.window().display_mode() == DisplayMode::Headless;
 
-        let display = glutin::WindowBuilder::new()
-            .with_visibility(true)
-            .with_title(window_title.to_string())
-            .with_dimensions(width, height)
-            .build_glium()
-            .unwrap();
+        let display = if headless {
+            glutin::HeadlessRendererBuilder::new(width, height)
+                .build_glium()
+                .unwrap()
+        } else {
+            let mut builder = glutin::WindowBuilder::new()
+                .with_visibility(true)
+                .with_title(window_title.to_string())
+                .with_dimensions(width, height);
+            if settings.window().fullscreen() {
+                builder = builder.with_fullscreen(glutin::get_primary_monitor());
+            }
+            if settings.window().vsync() {
+                builder = builder.with_vsync();
+            }
+            builder.build_glium().unwrap()
+        };
 
+        // The window manager might not give us the requested size.
+        if let Some((w, h)) = display.get_window().and_then(|w| w.get_inner_size_pixels()) {
+            width = w;
+            height = h;
+        }
 
         Window {
             display: display,
             settings: settings,
-            projection: Window::ortho(width, height)
+            projection: Window::ortho(width, height),
+            dimensions: (width, height),
+            headless: headless,
+            gamepads: if headless { None } else { Some(Gamepads::new()) },
+            text_input: None,
+            shift_pressed: false,
         }
     }
 
+    /// The chat message being typed, if any.
+    pub fn text_input(&self) -> Option<&str> {
+        self.text_input.as_ref().map(|t| &t[..])
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.headless
+    }
+
+    /// Size of the drawable area in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
     fn ortho(width: u32, height: u32) -> Matrix4<f32> {
         let w = width as f32;
         let h = height as f32;
@@ -64,6 +123,21 @@ impl Window {
         &self.projection
     }
 
+    /// Start drawing the next frame, it is shown once finished.
+    pub fn draw(&self) -> Frame {
+        self.display.draw()
+    }
+
+    /// Read back the last frame displayed.
+    pub fn capture_frame(&self) -> image::RgbaImage {
+        let (width, height) = self.dimensions;
+        let raw: RawImage2d<u8> = self.display.read_front_buffer();
+        let image = image::ImageBuffer::from_raw(width, height, raw.data.into_owned()).unwrap();
+
+        // OpenGL starts from the bottom left corner.
+        image::imageops::flip_vertical(&image)
+    }
+
     pub fn set_title(&self, title: &str) {
         if let Some(win) = self.display.get_window() {
             win.set_title(title);
@@ -71,6 +145,11 @@ impl Window {
     }
 
     pub fn poll_events(&mut self, event_sys: &mut PushEvent) {
+        // There is no event source without a window.
+        if self.headless {
+            return;
+        }
+        let mut resized = None;
         let keyboard = self.settings.keyboard();
         for event in self.display.poll_events() {
             let e = match event {
@@ -80,6 +159,18 @@ impl Window {
                 }),
                 Event::Resized(width, height) => {
                     self.projection  = Window::ortho(width, height);
+                    self.dimensions = (width, height);
+                    resized = Some((width, height));
+                    None
+                }
+                Event::ReceivedCharacter(c) => {
+                    if let Some(ref mut text) = self.text_input {
+                        if !c.is_control() && text.chars().count() < MAX_MESSAGE_LENGTH {
+                            text.push(c);
+                        }
+                    } else if event_sys.menu_open() && !c.is_control() {
+                        event_sys.push_char(c);
+                    }
                     None
                 }
                 //KeyboardInput(ElementState, u8, Option<VirtualKeyCode>)
@@ -88,7 +179,38 @@ impl Window {
                         ElementState::Pressed => Start,
                         ElementState::Released => Stop,
                     };
-                    if let Some(kind) = keyboard.get(key) {
+                    if key == Some(VirtualKeyCode::LShift) || key == Some(VirtualKeyCode::RShift) {
+                        self.shift_pressed = s == Start;
+                    }
+                    if event_sys.menu_open() && self.text_input.is_none() && s == Start {
+                        // The menus don't depend on the bindings.
+                        if let Some(key) = key.and_then(|k| menu_key(k, self.shift_pressed)) {
+                            event_sys.push_menu_key(key);
+                        }
+                        None
+                    } else if self.text_input.is_some() && s == Start {
+                        // Releases still go through so that a key held
+                        // before typing doesn't stay pressed forever.
+                        match key {
+                            Some(VirtualKeyCode::Return) => {
+                                let text = self.text_input.take().unwrap();
+                                if !text.trim().is_empty() {
+                                    event_sys.push_chat(text.trim().to_string());
+                                }
+                            }
+                            Some(VirtualKeyCode::Escape) => self.text_input = None,
+                            Some(VirtualKeyCode::Back) => {
+                                if let Some(ref mut text) = self.text_input {
+                                    text.pop();
+                                }
+                            }
+                            _ => (),
+                        }
+                        None
+                    } else if let Some(kind) = keyboard.get(key) {
+                        if kind == UserEventType::Chat && s == Start {
+                            self.text_input = Some(String::new());
+                        }
                         Some(UserEvent {
                             state: s,
                             kind: kind,
@@ -103,5 +225,46 @@ impl Window {
                 event_sys.push(e);
             }
         }
+
+        if let Some(ref mut gamepads) = self.gamepads {
+            gamepads.poll_events(&self.settings.gamepad(), event_sys);
+        }
+
+        // Remember the size chosen by the user for the next time,
+        // the size of a fullscreen window is given by the monitor.
+        if let Some((width, height)) = resized {
+            event_sys.push_resize(width, height);
+            let mut settings = self.settings.clone();
+            let mut window = settings.window_mut();
+            if !window.fullscreen() {
+                window.set_dimensions(width, height);
+            }
+        }
     }
 }
+
+fn menu_key(key: VirtualKeyCode, shift_pressed: bool) -> Option<MenuKey> {
+    match key {
+        VirtualKeyCode::Up => Some(MenuKey::Up),
+        VirtualKeyCode::Down => Some(MenuKey::Down),
+        VirtualKeyCode::Left => Some(MenuKey::Left),
+        VirtualKeyCode::Right => Some(MenuKey::Right),
+        VirtualKeyCode::Tab if shift_pressed => Some(MenuKey::Previous),
+        VirtualKeyCode::Tab => Some(MenuKey::Next),
+        VirtualKeyCode::Return => Some(MenuKey::Activate),
+        VirtualKeyCode::Back => Some(MenuKey::Erase),
+        VirtualKeyCode::Escape => Some(MenuKey::Back),
+        _ => None,
+    }
+}
+
+/// Save `image` as a PNG file in `directory`, named after the current
+/// time. Returns the path of the file written.
+pub fn save_screenshot<P: AsRef<Path>>(image: &image::RgbaImage, directory: P) -> io::Result<PathBuf> {
+    try!(fs::create_dir_all(directory.as_ref()));
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+    let name = format!("sarosa-{}-{:03}.png", now.as_secs(), now.subsec_nanos() / 1_000_000);
+    let path = directory.as_ref().join(name);
+    try!(image.save(&path));
+    Ok(path)
+}
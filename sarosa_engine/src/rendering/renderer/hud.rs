@@ -0,0 +1,121 @@
+use models::chat::ChatLog;
+use models::player::{PlayerId, THIS_PLAYER};
+use rendering::font::Font;
+use rendering::renderer::text::TextBatch;
+
+// Chat box in the bottom left corner, in pixels.
+const CHAT_MAX_WIDTH: u32 = 400;
+const CHAT_MARGIN: f32 = 4.0;
+const CHAT_PADDING: f32 = 2.0;
+// Number of lines of messages shown.
+const CHAT_LINES: usize = 8;
+const CHAT_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.4];
+const CHAT_TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const CHAT_OWN_TEXT_COLOR: [f32; 4] = [1.0, 0.9, 0.5, 1.0];
+const CHAT_PROMPT: &'static str = "> ";
+
+/// Add the chat log and the message being typed, if any,
+/// `batch` must be in screen space.
+pub fn push_chat(
+    batch: &mut TextBatch,
+    font: &Font,
+    chat: &ChatLog,
+    text_input: Option<&str>,
+    dimensions: (u32, u32))
+{
+    let (width, height) = dimensions;
+    let max_width = chat_width(width);
+    let line_height = font.line_height() as f32;
+
+    // Lines from the bottom to the top.
+    let mut lines: Vec<(String, [f32; 4])> = Vec::new();
+    if let Some(input) = text_input {
+        let prompt = format!("{}{}_", CHAT_PROMPT, input);
+        // Show the end of what is being typed.
+        let wrapped = font.wrap(&prompt, max_width);
+        if let Some(last) = wrapped.last() {
+            lines.push((last.clone(), CHAT_TEXT_COLOR));
+        }
+    }
+    for message in chat.visible(CHAT_LINES) {
+        let color = if message.author == THIS_PLAYER { CHAT_OWN_TEXT_COLOR } else { CHAT_TEXT_COLOR };
+        let text = format!("<{}> {}", author_name(message.author, &message.name), message.text);
+        for line in font.wrap(&text, max_width).into_iter().rev() {
+            lines.push((line, color));
+        }
+    }
+    lines.truncate(CHAT_LINES + 1);
+
+    if lines.is_empty() {
+        return;
+    }
+
+    let left = CHAT_MARGIN;
+    let bottom = height as f32 - CHAT_MARGIN;
+    let top = bottom - lines.len() as f32 * line_height - 2.0 * CHAT_PADDING;
+    // The background is only shown while typing to keep the view clear.
+    if text_input.is_some() {
+        batch.push_rect(
+            [left, top],
+            [left + max_width as f32 + 2.0 * CHAT_PADDING, bottom],
+            CHAT_COLOR);
+    }
+
+    let mut line_top = bottom - CHAT_PADDING - line_height;
+    for &(ref line, color) in lines.iter() {
+        batch.push_text(font, line, [left + CHAT_PADDING, line_top], color);
+        line_top -= line_height;
+    }
+}
+
+fn chat_width(window_width: u32) -> u32 {
+    let available = window_width as f32 - 2.0 * (CHAT_MARGIN + CHAT_PADDING);
+    ::std::cmp::min(CHAT_MAX_WIDTH, available.max(0.0) as u32)
+}
+
+// Players the server didn't name are shown with their id.
+fn author_name(author: PlayerId, name: &str) -> String {
+    if !name.is_empty() {
+        name.to_string()
+    } else if author == THIS_PLAYER {
+        "me".to_string()
+    } else {
+        format!("player {}", author)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use models::chat::ChatLog;
+    use rendering::font::Font;
+    use rendering::renderer::text::{TextBatch, TextSpace};
+    use super::push_chat;
+
+    const FONT: &'static str = r#"
+common lineHeight=8 base=6 scaleW=64 scaleH=32 pages=1 packed=0
+page id=0 file="test.png"
+char id=63 x=1 y=1 width=3 height=6 xoffset=0 yoffset=1 xadvance=4 page=0 chnl=15
+"#;
+
+    #[test]
+    fn chat_at_the_bottom() {
+        let font: Font = FONT.parse().unwrap();
+        let mut chat = ChatLog::new();
+        let mut batch = TextBatch::new(TextSpace::Screen);
+
+        push_chat(&mut batch, &font, &chat, None, (200, 100));
+        assert_eq!(batch.quads(), 0);
+
+        chat.push(1, "b".to_string(), "x".to_string());
+        push_chat(&mut batch, &font, &chat, Some("y"), (200, 100));
+
+        // The background, then the prompt at the bottom and the message above.
+        let positions = batch.vertex_positions();
+        assert_eq!(positions[3], [4.0 + 200.0 - 12.0 + 4.0, 96.0]);
+        assert_eq!(positions[0][1], 96.0 - 2.0 * 8.0 - 4.0);
+        let prompt_quads = "> y_".len();
+        assert_eq!(positions[4][1], 96.0 - 2.0 - 8.0 + 1.0);
+        assert_eq!(positions[4 + 4 * prompt_quads][1], 96.0 - 2.0 - 16.0 + 1.0);
+    }
+}
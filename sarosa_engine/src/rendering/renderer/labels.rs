@@ -0,0 +1,128 @@
+use models::game::GameData;
+use models::player::THIS_PLAYER;
+use rendering::font::Font;
+use rendering::renderer::text::TextBatch;
+use unit::GAME_UNIT_TO_PX;
+
+// Speech bubbles, in pixels.
+const BUBBLE_MAX_WIDTH: u32 = 160;
+const BUBBLE_PADDING: f32 = 3.0;
+// Distance between the position of a player and the top of its sprite.
+const SPRITE_TOP: f32 = 16.0;
+const BUBBLE_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const BUBBLE_TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// Name plates, in pixels.
+const PLATE_PADDING: f32 = 1.0;
+const PLATE_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.4];
+const PLATE_TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// The local player stands out from the others.
+const OWN_PLATE_COLOR: [f32; 4] = [0.2, 0.15, 0.0, 0.6];
+const OWN_PLATE_TEXT_COLOR: [f32; 4] = [1.0, 0.9, 0.5, 1.0];
+
+/// Add the text attached to the players, `batch` must be in world space.
+pub fn push_labels(batch: &mut TextBatch, font: &Font, game_data: &GameData) {
+    for (&id, player) in game_data.iter_players_with_id() {
+        let mut anchor = [
+            player.position.x * GAME_UNIT_TO_PX,
+            player.position.y * GAME_UNIT_TO_PX + SPRITE_TOP,
+        ];
+        if !player.info.name.is_empty() {
+            anchor[1] = push_name_plate(batch, font, &player.info.name, anchor, id == THIS_PLAYER);
+        }
+        if let Some(ref speech) = player.speech {
+            push_bubble(batch, font, &speech.text, anchor);
+        }
+    }
+}
+
+// Name centered above `anchor`, returns the top of the plate.
+fn push_name_plate(
+    batch: &mut TextBatch,
+    font: &Font,
+    name: &str,
+    anchor: [f32; 2],
+    this_player: bool) -> f32
+{
+    let (color, text_color) = if this_player {
+        (OWN_PLATE_COLOR, OWN_PLATE_TEXT_COLOR)
+    } else {
+        (PLATE_COLOR, PLATE_TEXT_COLOR)
+    };
+
+    let width = font.width(name) as f32;
+    let left = (anchor[0] - width / 2.0).round();
+    let bottom = anchor[1];
+    let top = bottom + font.line_height() as f32 + 2.0 * PLATE_PADDING;
+
+    batch.push_rect(
+        [left - PLATE_PADDING, top],
+        [left + width + PLATE_PADDING, bottom],
+        color);
+    batch.push_text(font, name, [left, top - PLATE_PADDING], text_color);
+    top
+}
+
+// Bubble centered above `anchor`, lines are wrapped to keep it narrow.
+fn push_bubble(batch: &mut TextBatch, font: &Font, text: &str, anchor: [f32; 2]) {
+    let lines = font.wrap(text, BUBBLE_MAX_WIDTH);
+    if lines.is_empty() {
+        return;
+    }
+    let text = lines.join("\n");
+
+    let width = font.width(&text) as f32;
+    let height = font.height(&text) as f32;
+    let left = (anchor[0] - width / 2.0).round();
+    let bottom = anchor[1] + BUBBLE_PADDING;
+    let top = bottom + height + 2.0 * BUBBLE_PADDING;
+
+    batch.push_rect(
+        [left - BUBBLE_PADDING, top],
+        [left + width + BUBBLE_PADDING, bottom],
+        BUBBLE_COLOR);
+    batch.push_text(font, &text, [left, top - BUBBLE_PADDING], BUBBLE_TEXT_COLOR);
+}
+
+#[cfg(test)]
+mod test {
+
+    use rendering::font::Font;
+    use rendering::renderer::text::{TextBatch, TextSpace};
+    use super::{push_bubble, push_name_plate, OWN_PLATE_COLOR, PLATE_COLOR};
+
+    const FONT: &'static str = r#"
+common lineHeight=8 base=6 scaleW=64 scaleH=32 pages=1 packed=0
+page id=0 file="test.png"
+char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4 page=0 chnl=15
+char id=97 x=5 y=1 width=3 height=4 xoffset=1 yoffset=3 xadvance=4 page=0 chnl=15
+"#;
+
+    #[test]
+    fn bubble_above_anchor() {
+        let font: Font = FONT.parse().unwrap();
+        let mut batch = TextBatch::new(TextSpace::World);
+        push_bubble(&mut batch, &font, "aa", [0.0, 16.0]);
+
+        // The background and the two glyphs.
+        assert_eq!(batch.quads(), 3);
+        let background = batch.vertex_positions();
+        assert_eq!(background[0], [-4.0 - 3.0, 16.0 + 3.0 + 8.0 + 2.0 * 3.0]);
+        assert_eq!(background[3], [4.0 + 3.0, 16.0 + 3.0]);
+    }
+
+    #[test]
+    fn name_plates() {
+        let font: Font = FONT.parse().unwrap();
+        let mut batch = TextBatch::new(TextSpace::World);
+        let top = push_name_plate(&mut batch, &font, "a", [10.0, 16.0], false);
+        assert_eq!(top, 16.0 + 8.0 + 2.0);
+        assert_eq!(batch.quads(), 2);
+        assert_eq!(batch.vertex_positions()[0], [8.0 - 1.0, top]);
+        assert_eq!(batch.vertex_colors()[0], PLATE_COLOR);
+
+        batch.clear();
+        push_name_plate(&mut batch, &font, "a", [10.0, 16.0], true);
+        assert_eq!(batch.vertex_colors()[0], OWN_PLATE_COLOR);
+    }
+}
@@ -1,7 +1,4 @@
-extern crate rand;
-
-use self::rand::distributions::{IndependentSample, Range};
-use image;
+use image::RgbaImage;
 use cgmath::Matrix4;
 use glium::Surface;
 use glium::program::Program;
@@ -11,47 +8,34 @@ use glium::index::{
 };
 use glium::draw_parameters::DrawParameters;
 use glium::texture::Texture2dArray;
-use glium::texture::RawImage2d;
 use glium::VertexBuffer;
-use glium::Frame;
 
 use models::game::GameData;
+use models::map::LayerPass;
 use animation::TextureId;
 use rendering::renderer::shaders;
+use rendering::renderer::textures;
 use Window;
 use unit::GAME_UNIT_TO_PX;
+use unit::TILE_SIZE;
 
 pub struct MapRenderer {
     program: Program,
     vertex_buffer: VertexBuffer<shaders::map::Vertex>,
-    index_buffer: IndexBuffer<u32>,
+    // One index buffer per layer, in drawing order.
+    layers: Vec<(LayerPass, IndexBuffer<u32>)>,
     texture: Texture2dArray,
+    tileset_columns: u32,
+    tileset_rows: u32,
 }
 
-// TODO(Nemikolh): Clean up that (should be read from a map file format)
-// Same should apply for the FRAMES_PER_TEXTURE constant in animation/mod.rs
-const TILES_PER_TEXTURE:u32 = 30 * 16;
-
 impl MapRenderer {
 
-    pub fn new(window: &Window) -> MapRenderer {
+    /// The tileset must be the one of the maps given to `initialize_gpu_mem`.
+    pub fn new(window: &Window, tileset: RgbaImage) -> MapRenderer {
 
         let ref display = window.display;
 
-        let texture = {
-            // TODO(Nemikolh): Use a ResourceManager to load
-            // them before being here and do something clever with it.
-            let images = vec![
-                {
-                    let img = image::open("./assets/maps/tiles.png").unwrap().to_rgba();
-                    let dims = img.dimensions();
-                    RawImage2d::from_raw_rgba_reversed(img.into_raw(), dims)
-                }
-            ];
-
-            Texture2dArray::new(display, images).unwrap()
-        };
-
         let program = program!(display,
             140 => {
                 vertex: shaders::map::VERTEX_140,
@@ -61,92 +45,122 @@ impl MapRenderer {
 
         MapRenderer {
             program: program,
-            texture: texture,
+            texture: textures::texture_array(display, vec![tileset]),
+            tileset_columns: 1,
+            tileset_rows: 1,
             vertex_buffer: VertexBuffer::empty(display, 0).unwrap(),
-            index_buffer: IndexBuffer::empty(display, PrimitiveType::TrianglesList, 0).unwrap(),
+            layers: Vec::new(),
         }
     }
 
     pub fn initialize_gpu_mem(&mut self, game_data: &GameData, window: &Window) {
 
         let ref display = window.display;
-        let width = game_data.get_map().width();
-        let height = game_data.get_map().height();
-        let TextureId(tex_id) = game_data.get_map().tex_id();
-        let nb_tiles = (width * height) as usize;
+        let map = game_data.get_map();
+        let tileset = map.tileset();
+        let TextureId(tex_id) = map.tex_id();
+        let tiles_per_texture = tileset.columns() * tileset.rows();
 
-        let (vertex_buffer, index_buffer) = {
+        self.tileset_columns = tileset.columns();
+        self.tileset_rows = tileset.rows();
+
+        let nb_tiles = map.layers().iter()
+            .map(|layer| layer.tiles().iter().filter(|t| t.is_some()).count())
+            .fold(0, |acc, n| acc + n);
+
+        let (vertex_buffer, layers) = {
             let mut vb: VertexBuffer<shaders::map::Vertex> =
                 VertexBuffer::empty(display, nb_tiles * 4).unwrap();
 
-            let mut ib_data = Vec::with_capacity(nb_tiles * 6);
-
-            let mut rng = rand::thread_rng();
-            let pick = Range::new(0f64, 1.);
-
-            for (num, sprite) in vb.map().chunks_mut(4).enumerate() {
-
-                let value = pick.ind_sample(&mut rng);
-                let absolute_tex_id: u32 = if value  < 0.25 {
-                    tex_id * TILES_PER_TEXTURE + 6
-                } else if value < 0.75 {
-                    tex_id * TILES_PER_TEXTURE + 7
-                } else {
-                    tex_id * TILES_PER_TEXTURE + 8
-                };
-
-                let num = num as u32;
-                let half_tile = GAME_UNIT_TO_PX;
-                let x = (num % width) as i64 - (width / 2) as i64;
-                let y = (num / width) as i64 - (height / 2) as i64;
-                let position: (f32, f32) = (x as f32 * 16., y as f32 * 16.);
-
-                sprite[0].i_position[0] = position.0 - half_tile;
-                sprite[0].i_position[1] = position.1 + half_tile;
-                sprite[0].i_tex_id = absolute_tex_id;
-                sprite[1].i_position[0] = position.0 + half_tile;
-                sprite[1].i_position[1] = position.1 + half_tile;
-                sprite[1].i_tex_id = absolute_tex_id;
-                sprite[2].i_position[0] = position.0 - half_tile;
-                sprite[2].i_position[1] = position.1 - half_tile;
-                sprite[2].i_tex_id = absolute_tex_id;
-                sprite[3].i_position[0] = position.0 + half_tile;
-                sprite[3].i_position[1] = position.1 - half_tile;
-                sprite[3].i_tex_id = absolute_tex_id;
-
-                ib_data.push(num * 4);
-                ib_data.push(num * 4 + 1);
-                ib_data.push(num * 4 + 2);
-                ib_data.push(num * 4 + 1);
-                ib_data.push(num * 4 + 3);
-                ib_data.push(num * 4 + 2);
+            let mut layers = Vec::with_capacity(map.layers().len());
+
+            {
+                let mut mapping = vb.map();
+                let mut sprites = mapping.chunks_mut(4);
+                let mut num = 0u32;
+
+                // Layers are drawn in the order they appear in the map.
+                for layer in map.layers() {
+                    let mut ib_data = Vec::new();
+
+                    for (index, tile) in layer.tiles().iter().enumerate() {
+
+                        let tile = match *tile {
+                            Some(tile) => tile,
+                            None => continue,
+                        };
+                        let sprite = sprites.next().unwrap();
+                        let absolute_tex_id = tex_id * tiles_per_texture + tile;
+
+                        let index = index as u32;
+                        let half_tile = TILE_SIZE * GAME_UNIT_TO_PX / 2.0;
+                        let center = map.tile_center(index % map.width(), index / map.width());
+                        let position = (center.x * GAME_UNIT_TO_PX, center.y * GAME_UNIT_TO_PX);
+
+                        sprite[0].i_position[0] = position.0 - half_tile;
+                        sprite[0].i_position[1] = position.1 + half_tile;
+                        sprite[0].i_tex_id = absolute_tex_id;
+                        sprite[1].i_position[0] = position.0 + half_tile;
+                        sprite[1].i_position[1] = position.1 + half_tile;
+                        sprite[1].i_tex_id = absolute_tex_id;
+                        sprite[2].i_position[0] = position.0 - half_tile;
+                        sprite[2].i_position[1] = position.1 - half_tile;
+                        sprite[2].i_tex_id = absolute_tex_id;
+                        sprite[3].i_position[0] = position.0 + half_tile;
+                        sprite[3].i_position[1] = position.1 - half_tile;
+                        sprite[3].i_tex_id = absolute_tex_id;
+
+                        ib_data.push(num * 4);
+                        ib_data.push(num * 4 + 1);
+                        ib_data.push(num * 4 + 2);
+                        ib_data.push(num * 4 + 1);
+                        ib_data.push(num * 4 + 3);
+                        ib_data.push(num * 4 + 2);
+                        num += 1;
+                    }
+
+                    let ib = IndexBuffer::new(display, PrimitiveType::TrianglesList, &ib_data);
+                    layers.push((layer.pass(), ib.unwrap()));
+                }
             }
 
-            (vb, IndexBuffer::new(display, PrimitiveType::TrianglesList, &ib_data).unwrap())
+            (vb, layers)
         };
 
         self.vertex_buffer = vertex_buffer;
-        self.index_buffer = index_buffer;
+        self.layers = layers;
     }
 
-    pub fn render(&self, target: &mut Frame, mvp: &Matrix4<f32>, draw_parameters: &DrawParameters) {
+    /// Draw all the layers of the given pass, in the order
+    /// defined by the map.
+    pub fn render<S: Surface>(
+        &self,
+        target: &mut S,
+        mvp: &Matrix4<f32>,
+        draw_parameters: &DrawParameters,
+        pass: LayerPass)
+    {
 
         use glium::uniforms::MagnifySamplerFilter::Nearest;
         use glium::uniforms::MinifySamplerFilter::NearestMipmapNearest;
 
         let uniforms = uniform! {
             mvp: Into::<[[f32; 4]; 4]>::into(*mvp),
+            tiles_x: self.tileset_columns,
+            tiles_y: self.tileset_rows,
             tex: self.texture.sampled()
                 .minify_filter(NearestMipmapNearest)
                 .magnify_filter(Nearest)
         };
 
-        target.draw(
-            &self.vertex_buffer,
-            &self.index_buffer,
-            &self.program,
-            &uniforms,
-            &draw_parameters
-        ).unwrap();
+        for &(_, ref index_buffer) in self.layers.iter().filter(|&&(p, _)| p == pass) {
+            target.draw(
+                &self.vertex_buffer,
+                index_buffer,
+                &self.program,
+                &uniforms,
+                &draw_parameters
+            ).unwrap();
+        }
     }
 }
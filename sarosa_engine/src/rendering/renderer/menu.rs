@@ -0,0 +1,134 @@
+use rendering::font::Font;
+use rendering::renderer::text::TextBatch;
+use ui::{Content, Document, LayoutBox, Rect, Tag};
+
+// Drawn when the style doesn't give a background.
+const INPUT_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const PROGRESS_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+// Frame around the element with the focus, in pixels.
+const FOCUS_COLOR: [f32; 4] = [1.0, 0.9, 0.5, 1.0];
+const FOCUS_WIDTH: f32 = 2.0;
+const CURSOR: &'static str = "_";
+
+/// Add the view shown by `document`, with its focus.
+pub fn push_document(batch: &mut TextBatch, font: &Font, document: &mut Document) {
+    let focused = document.focused();
+    if let Some(layout) = document.layout() {
+        push_menu(batch, font, layout, focused);
+    }
+}
+
+/// Add the boxes of a view, parents below their children,
+/// `batch` must be in screen space.
+pub fn push_menu(batch: &mut TextBatch, font: &Font, layout: &LayoutBox, focused: Option<usize>) {
+    for b in layout.boxes() {
+        let is_focused = focused.is_some() && b.element == focused;
+        let rect = b.rect;
+        let background = match b.tag {
+            Some(Tag::LineInput) => b.style.background_color.or(Some(INPUT_COLOR)),
+            _ => b.style.background_color,
+        };
+        if let Some(color) = background {
+            push_rect(batch, rect, color);
+        }
+
+        let inner = Rect {
+            x: rect.x + b.style.padding,
+            y: rect.y + b.style.padding,
+            width: rect.width - 2.0 * b.style.padding,
+            height: rect.height - 2.0 * b.style.padding,
+        };
+        match b.content {
+            Content::Text(ref text) => {
+                let scale = b.style.font_size.map_or(1.0, |size| size / font.line_height() as f32);
+                let text = if is_focused && b.tag == Some(Tag::LineInput) {
+                    format!("{}{}", text, CURSOR)
+                } else {
+                    text.clone()
+                };
+                batch.push_scaled_text(font, &text, [inner.x, inner.y], scale, b.style.color);
+            }
+            Content::Progress(progress) => {
+                push_rect(batch, inner, PROGRESS_COLOR);
+                push_rect(batch, Rect { width: inner.width * progress, .. inner }, b.style.color);
+            }
+            Content::None => (),
+        }
+
+        if is_focused {
+            push_frame(batch, rect);
+        }
+    }
+}
+
+fn push_rect(batch: &mut TextBatch, rect: Rect, color: [f32; 4]) {
+    batch.push_rect([rect.x, rect.y], [rect.x + rect.width, rect.y + rect.height], color);
+}
+
+// Lines around `rect`, inside of it.
+fn push_frame(batch: &mut TextBatch, rect: Rect) {
+    let (left, top) = (rect.x, rect.y);
+    let (right, bottom) = (rect.x + rect.width, rect.y + rect.height);
+    let w = FOCUS_WIDTH;
+    batch.push_rect([left, top], [right, top + w], FOCUS_COLOR);
+    batch.push_rect([left, bottom - w], [right, bottom], FOCUS_COLOR);
+    batch.push_rect([left, top], [left + w, bottom], FOCUS_COLOR);
+    batch.push_rect([right - w, top], [right, bottom], FOCUS_COLOR);
+}
+
+#[cfg(test)]
+mod test {
+
+    use rendering::font::Font;
+    use rendering::renderer::text::{TextBatch, TextSpace};
+    use ui::{Content, LayoutBox, Rect, Style, Tag};
+    use super::{push_menu, FOCUS_COLOR};
+
+    const FONT: &'static str = r#"
+common lineHeight=8 base=6 scaleW=64 scaleH=32 pages=1 packed=0
+page id=0 file="test.png"
+char id=97 x=5 y=1 width=3 height=4 xoffset=1 yoffset=3 xadvance=4 page=0 chnl=15
+char id=95 x=9 y=1 width=4 height=1 xoffset=0 yoffset=7 xadvance=4 page=0 chnl=15
+"#;
+
+    fn button(element: usize, y: f32) -> LayoutBox {
+        LayoutBox {
+            rect: Rect { x: 10.0, y: y, width: 100.0, height: 20.0 },
+            element: Some(element),
+            tag: Some(Tag::Button),
+            content: Content::None,
+            style: Style { background_color: Some([0.0, 0.0, 1.0, 1.0]), .. Style::default() },
+            children: vec![LayoutBox {
+                rect: Rect { x: 10.0, y: y, width: 100.0, height: 8.0 },
+                element: None,
+                tag: None,
+                content: Content::Text("a".to_string()),
+                style: Style::default(),
+                children: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn focused_button() {
+        let font: Font = FONT.parse().unwrap();
+        let root = LayoutBox {
+            rect: Rect { x: 0.0, y: 0.0, width: 200.0, height: 100.0 },
+            element: Some(0),
+            tag: Some(Tag::View),
+            content: Content::None,
+            style: Style::default(),
+            children: vec![button(1, 0.0), button(2, 40.0)],
+        };
+        let mut batch = TextBatch::new(TextSpace::Screen);
+        push_menu(&mut batch, &font, &root, Some(2));
+
+        // Two backgrounds, two labels and the frame of the focus.
+        assert_eq!(batch.quads(), 2 + 2 + 4);
+        let colors = batch.vertex_colors();
+        assert_eq!(colors.iter().filter(|&&c| c == FOCUS_COLOR).count(), 4 * 4);
+        // The frame is around the second button, after its background.
+        let positions = batch.vertex_positions();
+        assert_eq!(positions[3 * 4], [10.0, 40.0]);
+    }
+}
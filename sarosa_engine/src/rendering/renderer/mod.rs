@@ -1,29 +1,56 @@
-use glium::Surface;
+use image;
+use glium::{Frame, Surface};
 use glium::draw_parameters::DrawParameters;
 use glium::draw_parameters::Blend;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::Texture2d;
+use glium::texture::RawImage2d;
 
 use Window;
 use models::game::GameData;
+use models::map::LayerPass;
+use rendering::font::Font;
 use rendering::scene::WorldScene;
+use ui::Document;
 use self::map::MapRenderer;
 use self::players::PlayersRenderer;
+use self::text::{TextRenderer, TextSpace};
+
+pub use self::textures::{Textures, PLAYER_SKINS};
 
 mod shaders;
 mod map;
 mod players;
+mod text;
+mod labels;
+mod hud;
+mod menu;
+mod textures;
 
 pub struct GameRenderer {
     players_renderer: PlayersRenderer,
     map_renderer: MapRenderer,
+    text_renderer: TextRenderer,
 }
 
 impl GameRenderer {
 
-    pub fn new(window: &Window) -> GameRenderer {
+    pub fn new(window: &Window, textures: Textures) -> GameRenderer {
 
         GameRenderer {
-            players_renderer: PlayersRenderer::new(window),
-            map_renderer: MapRenderer::new(window),
+            players_renderer: PlayersRenderer::new(window, textures.skins),
+            map_renderer: MapRenderer::new(window, textures.tileset),
+            text_renderer: TextRenderer::new(window),
+        }
+    }
+
+    /// Write the labels with `font` rather than the default one.
+    pub fn with_font(window: &Window, textures: Textures, font: Font) -> GameRenderer {
+
+        GameRenderer {
+            players_renderer: PlayersRenderer::new(window, textures.skins),
+            map_renderer: MapRenderer::new(window, textures.tileset),
+            text_renderer: TextRenderer::with_font(window, font),
         }
     }
 
@@ -35,16 +62,49 @@ impl GameRenderer {
     pub fn update_gpu_mem(&mut self, game_data: &GameData) {
 
         self.players_renderer.update_gpu_mem(game_data);
+        self.text_renderer.update(TextSpace::World, |batch, font| {
+            labels::push_labels(batch, font, game_data);
+        });
+    }
+
+    /// Rebuild what is drawn on top of the world, it depends on
+    /// the window and changes every frame.
+    pub fn update_hud(&mut self, game_data: &GameData, window: &Window) {
+
+        self.text_renderer.update(TextSpace::Screen, |batch, font| {
+            hud::push_chat(batch, font, game_data.chat(), window.text_input(), window.dimensions());
+        });
+    }
+
+    pub fn render(&self, target: &mut Frame, world_scene: &WorldScene, window: &Window) {
+
+        self.draw(target, world_scene, window);
+    }
+
+    /// Draw the scene into an offscreen texture of the size
+    /// of the window and read it back.
+    pub fn render_to_image(&self, world_scene: &WorldScene, window: &Window) -> image::RgbaImage {
+
+        let (width, height) = window.dimensions();
+        let texture = Texture2d::empty(&window.display, width, height).unwrap();
+
+        {
+            let mut target = SimpleFrameBuffer::new(&window.display, &texture).unwrap();
+            self.draw(&mut target, world_scene, window);
+        }
+
+        let raw: RawImage2d<u8> = texture.read();
+        let image = image::ImageBuffer::from_raw(width, height, raw.data.into_owned()).unwrap();
+
+        // OpenGL starts from the bottom left corner.
+        image::imageops::flip_vertical(&image)
     }
 
-    pub fn render(&self, world_scene: &WorldScene, window: &mut Window) {
+    fn draw<S: Surface>(&self, target: &mut S, world_scene: &WorldScene, window: &Window) {
 
         // Compute the projection matrix:
         let transform = window.projection() * world_scene.transform();
 
-        // Preparing the frame
-        let mut target = window.display.draw();
-
         let draw_parameters = DrawParameters {
             blend: Blend::alpha_blending(),
             .. Default::default()
@@ -53,12 +113,203 @@ impl GameRenderer {
         // Clear the screen
         target.clear_color(0.0, 0.0, 0.0, 1.0); //target.clear_color(0.11, 0.31, 0.11, 1.0);
 
-        // Draw the map
-        self.map_renderer.render(&mut target, &transform, &draw_parameters);
+        // Draw the map layers below the players
+        self.map_renderer.render(target, &transform, &draw_parameters, LayerPass::BelowPlayers);
         // Draw the players
-        self.players_renderer.render(&mut target, &transform, &draw_parameters);
+        self.players_renderer.render(target, &transform, &draw_parameters);
+        // Draw the map layers hiding the players
+        self.map_renderer.render(target, &transform, &draw_parameters, LayerPass::AbovePlayers);
+        // Draw the speech bubbles over the world
+        self.text_renderer.render(TextSpace::World, target, &transform, &draw_parameters);
+        // And the chat over everything
+        let (width, height) = window.dimensions();
+        let screen = text::screen_projection(width, height);
+        self.text_renderer.render(TextSpace::Screen, target, &screen, &draw_parameters);
+    }
+}
+
+/// Draws the menus shown outside of the game, without the world.
+pub struct MenuRenderer {
+    text_renderer: TextRenderer,
+}
+
+impl MenuRenderer {
+
+    pub fn new(window: &Window) -> MenuRenderer {
+
+        MenuRenderer {
+            text_renderer: TextRenderer::new(window),
+        }
+    }
+
+    pub fn update(&mut self, document: &mut Document) {
+
+        self.text_renderer.update(TextSpace::Screen, |batch, font| {
+            menu::push_document(batch, font, document);
+        });
+    }
+
+    /// Draw the menu over what is already in `target`.
+    pub fn render(&self, target: &mut Frame, window: &Window) {
+
+        let draw_parameters = DrawParameters {
+            blend: Blend::alpha_blending(),
+            .. Default::default()
+        };
+        let (width, height) = window.dimensions();
+        let screen = text::screen_projection(width, height);
+
+        self.text_renderer.render(TextSpace::Screen, target, &screen, &draw_parameters);
+    }
+}
+
+// Those tests need an OpenGL implementation, so they are ignored by
+// default. On the headless build boxes, where there is no GPU nor X
+// server, they run with the software renderer of Mesa (OSMesa):
+//
+//     cargo test -p sarosa_engine -- --ignored golden
+//
+// The rendering is compared with the reference images of
+// `sarosa_engine/tests/golden`, a missing one fails the test. Those are
+// written by the bless mode, to be reviewed before being committed:
+//
+//     SAROSA_BLESS=missing cargo test -p sarosa_engine -- --ignored golden
+//
+// writes the references that don't exist yet and checks the other ones,
+// while `SAROSA_BLESS=all` writes them all again after a wanted change.
+#[cfg(test)]
+mod test {
+
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use image;
+    use cgmath::Vector2;
+    use cgmath::Zero;
+
+    use animation::{AnimationManager, TextureId};
+    use models::game::GameData;
+    use models::map::GameMap;
+    use models::player::Player;
+    use models::settings::DisplayMode;
+    use rendering::font::{Font, DEFAULT_FONT_PATH};
+    use rendering::scene::WorldScene;
+    use Settings;
+    use Window;
+    use super::{GameRenderer, Textures, PLAYER_SKINS};
+
+    const MAP: &'static str = r#"
+        width = 4
+        height = 3
+
+        [tileset]
+        path = "./assets/maps/tiles.png"
+        columns = 30
+        rows = 16
+
+        [[layer]]
+        name = "ground"
+        tiles = """
+        6 7 8 6
+        7 7 6 8
+        8 6 7 7
+        """
+
+        [[layer]]
+        name = "tree tops"
+        pass = "above"
+        tiles = """
+        . 261 262 263
+        . 291 292 293
+        . . . .
+        """
+    "#;
+
+    // Assets paths are relative to the root of the repository.
+    fn asset<P: AsRef<Path>>(path: P) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..").join(path)
+    }
+
+    fn headless_window() -> Window {
+        let mut settings = Settings::new("localhost:7777".to_string(), true);
+        settings.window_mut().set_display_mode(DisplayMode::Headless);
+        settings.window_mut().set_dimensions(128, 96);
+        Window::new(settings, "test")
+    }
+
+    fn textures(map: &GameMap) -> Textures {
+        Textures {
+            tileset: image::open(asset(map.tileset().path())).unwrap().to_rgba(),
+            skins: PLAYER_SKINS.iter().map(|path| image::open(asset(path)).unwrap().to_rgba()).collect(),
+        }
+    }
+
+    fn renderer(window: &Window, map: &GameMap) -> GameRenderer {
+        let font = Font::load(asset(DEFAULT_FONT_PATH)).unwrap();
+        GameRenderer::with_font(window, textures(map), font)
+    }
+
+    fn check_golden(name: &str, image: &image::RgbaImage) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name));
+
+        let bless = match env::var("SAROSA_BLESS") {
+            Ok(ref mode) if mode == "all" => true,
+            Ok(ref mode) if mode == "missing" => !path.exists(),
+            Ok(mode) => panic!("unknown SAROSA_BLESS mode `{}`, expected `missing` or `all`", mode),
+            Err(_) => false,
+        };
+        if bless {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image.save(&path).unwrap();
+            return;
+        }
+        assert!(path.exists(), "missing reference image {}, run with SAROSA_BLESS=missing to write it",
+                path.display());
+
+        let golden = image::open(&path).unwrap().to_rgba();
+        assert_eq!(golden.dimensions(), image.dimensions());
+        // Software implementations don't always round the same way.
+        for (a, b) in golden.pixels().zip(image.pixels()) {
+            for (ca, cb) in a.data.iter().zip(b.data.iter()) {
+                assert!((*ca as i32 - *cb as i32).abs() <= 2,
+                        "{} differs from the reference image", name);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn golden_map() {
+        let window = headless_window();
+        let anim_manager = AnimationManager::new();
+        let map: GameMap = MAP.parse().unwrap();
+        let mut renderer = renderer(&window, &map);
+        let game_data = GameData::new(TextureId(0), &anim_manager, map);
+
+        renderer.initialize_gpu_mem(&game_data, &window);
+
+        let image = renderer.render_to_image(&WorldScene::new(), &window);
+        check_golden("map", &image);
+    }
+
+    #[test]
+    #[ignore]
+    fn golden_players() {
+        let window = headless_window();
+        let anim_manager = AnimationManager::new();
+        let map: GameMap = MAP.parse().unwrap();
+        let mut renderer = renderer(&window, &map);
+        let mut game_data = GameData::new(TextureId(0), &anim_manager, map);
+        for id in 0..3 {
+            let position = Vector2::new(id as f32 * 4.0 - 4.0, 0.0);
+            let player = Player::new(position, Vector2::zero(), TextureId(id), &anim_manager);
+            game_data.add_player(id as u64, player);
+        }
+
+        renderer.initialize_gpu_mem(&game_data, &window);
+        renderer.update_gpu_mem(&game_data);
 
-        // Swap buffers
-        target.finish().unwrap();
+        let image = renderer.render_to_image(&WorldScene::new(), &window);
+        check_golden("players", &image);
     }
 }
@@ -1,5 +1,5 @@
 use std::cmp;
-use image;
+use image::RgbaImage;
 use cgmath::Matrix4;
 use glium::Surface;
 use glium::program::Program;
@@ -10,12 +10,11 @@ use glium::index::{
 use glium::draw_parameters::DrawParameters;
 use glium::texture::Texture2dArray;
 use glium::VertexBuffer;
-use glium::texture::RawImage2d;
-use glium::Frame;
 
 use models::game::GameData;
 use animation::AbsoluteTextureId;
 use rendering::renderer::shaders;
+use rendering::renderer::textures;
 use Window;
 use unit::GAME_UNIT_TO_PX;
 
@@ -31,33 +30,11 @@ const MAX_SPRITES: usize = 1024;
 
 impl PlayersRenderer {
 
-    pub fn new(window: &Window) -> PlayersRenderer {
+    pub fn new(window: &Window, skins: Vec<RgbaImage>) -> PlayersRenderer {
 
         let ref display = window.display;
 
-        let texture = {
-            // TODO(Nemikolh): Use a ResourceManager to load
-            // them before being here and do something clever with it.
-            let images = vec![
-                {
-                    let img = image::open("./assets/players/Vurf.png").unwrap().to_rgba();
-                    let dims = img.dimensions();
-                    RawImage2d::from_raw_rgba_reversed(img.into_raw(), dims)
-                },
-                {
-                    let img = image::open("./assets/players/frost.png").unwrap().to_rgba();
-                    let dims = img.dimensions();
-                    RawImage2d::from_raw_rgba_reversed(img.into_raw(), dims)
-                },
-                {
-                    let img = image::open("./assets/players/Kiwan.png").unwrap().to_rgba();
-                    let dims = img.dimensions();
-                    RawImage2d::from_raw_rgba_reversed(img.into_raw(), dims)
-                },
-            ];
-
-            Texture2dArray::new(display, images).unwrap()
-        };
+        let texture = textures::texture_array(display, skins);
 
         let (vertex_buffer, index_buffer) = {
             let vb: VertexBuffer<shaders::players::Vertex> =
@@ -129,7 +106,7 @@ impl PlayersRenderer {
         }
     }
 
-    pub fn render(&self, target: &mut Frame, mvp: &Matrix4<f32>, draw_parameters: &DrawParameters) {
+    pub fn render<S: Surface>(&self, target: &mut S, mvp: &Matrix4<f32>, draw_parameters: &DrawParameters) {
 
         use glium::uniforms::MagnifySamplerFilter::Nearest;
         use glium::uniforms::MinifySamplerFilter::NearestMipmapNearest;
@@ -9,25 +9,29 @@ implement_vertex!(Vertex, i_position, i_tex_id);
 pub const VERTEX_140: &'static str = r"
     #version 140
     uniform mat4 mvp;
+    uniform uint tiles_x;
+    uniform uint tiles_y;
     in vec2 i_position;
     in uint i_tex_id;
     out vec2 v_tex_coords;
     flat out uint v_tex_id;
     void main() {
         gl_Position = mvp * vec4(i_position, 0.0, 1.0);
-        uint sprite_x = i_tex_id % uint(30 * 16);
-        uint sprite_y = sprite_x / uint(30);
-        sprite_x = sprite_x % uint(30);
+        uint sprite_x = i_tex_id % (tiles_x * tiles_y);
+        uint sprite_y = sprite_x / tiles_x;
+        sprite_x = sprite_x % tiles_x;
+        float w = float(tiles_x);
+        float h = float(tiles_y);
         if (gl_VertexID % 4 == 0) {
-            v_tex_coords = vec2(float(sprite_x) * 1.0 / 30,     float(sprite_y) * 1.0 / 16);
+            v_tex_coords = vec2(float(sprite_x) / w,     float(sprite_y) / h);
         } else if (gl_VertexID % 4 == 1) {
-            v_tex_coords = vec2(float(sprite_x + uint(1)) * 1.0 / 30, float(sprite_y) * 1.0 / 16);
+            v_tex_coords = vec2(float(sprite_x + uint(1)) / w, float(sprite_y) / h);
         } else if (gl_VertexID % 4 == 2) {
-            v_tex_coords = vec2(float(sprite_x) * 1.0 / 30,     float(sprite_y + uint(1)) * 1.0 / 16);
+            v_tex_coords = vec2(float(sprite_x) / w,     float(sprite_y + uint(1)) / h);
         } else {
-            v_tex_coords = vec2(float(sprite_x + uint(1)) * 1.0 / 30, float(sprite_y + uint(1)) * 1.0 / 16);
+            v_tex_coords = vec2(float(sprite_x + uint(1)) / w, float(sprite_y + uint(1)) / h);
         }
-        v_tex_id = i_tex_id / uint(30 * 16);
+        v_tex_id = i_tex_id / (tiles_x * tiles_y);
     }
 ";
 
@@ -1,3 +1,4 @@
 
 pub mod map;
 pub mod players;
+pub mod text;
@@ -0,0 +1,40 @@
+#[derive(Copy, Clone)]
+pub struct Vertex {
+    pub i_position: [f32; 2],
+    pub i_tex_coords: [f32; 2],
+    pub i_color: [f32; 4],
+}
+
+implement_vertex!(Vertex, i_position, i_tex_coords, i_color);
+
+/// Texture coordinates of the vertices of a solid quad,
+/// the atlas isn't sampled for those.
+pub const SOLID: [f32; 2] = [-1.0, -1.0];
+
+pub const VERTEX_140: &'static str = r"
+    #version 140
+    uniform mat4 mvp;
+    in vec2 i_position;
+    in vec2 i_tex_coords;
+    in vec4 i_color;
+    out vec2 v_tex_coords;
+    out vec4 v_color;
+    void main() {
+        gl_Position = mvp * vec4(i_position, 0.0, 1.0);
+        v_tex_coords = i_tex_coords;
+        v_color = i_color;
+    }
+";
+
+pub const FRAGMENT_140: &'static str = r"
+    #version 140
+    uniform sampler2D tex;
+    in vec2 v_tex_coords;
+    in vec4 v_color;
+    out vec4 f_color;
+    void main() {
+        // The glyphs are white, their coverage is in the alpha channel.
+        float alpha = v_tex_coords.x < 0.0 ? 1.0 : texture(tex, v_tex_coords).a;
+        f_color = vec4(v_color.rgb, v_color.a * alpha);
+    }
+";
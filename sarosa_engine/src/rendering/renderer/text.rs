@@ -0,0 +1,380 @@
+use std::cmp;
+use image;
+use cgmath;
+use cgmath::Matrix4;
+use glium::Surface;
+use glium::program::Program;
+use glium::index::{
+    PrimitiveType,
+    IndexBuffer
+};
+use glium::draw_parameters::DrawParameters;
+use glium::texture::Texture2d;
+use glium::texture::RawImage2d;
+use glium::VertexBuffer;
+
+use rendering::font::{Font, DEFAULT_FONT_PATH};
+use rendering::renderer::shaders;
+use rendering::renderer::shaders::text::{Vertex, SOLID};
+use Window;
+
+// Per space.
+const MAX_QUADS: usize = 4096;
+
+/// Coordinates used to place text, in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextSpace {
+    /// y goes up, like the world. Text follows the camera.
+    World,
+    /// The origin is the top left corner of the window and y
+    /// goes down. Text stays in place whatever the camera does.
+    Screen,
+}
+
+/// Projection matrix for the text in `TextSpace::Screen`.
+pub fn screen_projection(width: u32, height: u32) -> Matrix4<f32> {
+    cgmath::ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0)
+}
+
+/// Quads of text and solid rectangles, ready to be uploaded.
+pub struct TextBatch {
+    space: TextSpace,
+    vertices: Vec<Vertex>,
+}
+
+impl TextBatch {
+
+    pub fn new(space: TextSpace) -> TextBatch {
+        TextBatch {
+            space: space,
+            vertices: Vec::new(),
+        }
+    }
+
+    pub fn space(&self) -> TextSpace {
+        self.space
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn quads(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    #[cfg(test)]
+    pub fn vertex_positions(&self) -> Vec<[f32; 2]> {
+        self.vertices.iter().map(|v| v.i_position).collect()
+    }
+
+    #[cfg(test)]
+    pub fn vertex_colors(&self) -> Vec<[f32; 4]> {
+        self.vertices.iter().map(|v| v.i_color).collect()
+    }
+
+    /// Move `y` by `pixels` towards the bottom of the screen.
+    pub fn below(&self, y: f32, pixels: f32) -> f32 {
+        match self.space {
+            TextSpace::World => y - pixels,
+            TextSpace::Screen => y + pixels,
+        }
+    }
+
+    pub fn push_rect(&mut self, top_left: [f32; 2], bottom_right: [f32; 2], color: [f32; 4]) {
+        self.push_quad(top_left, bottom_right, SOLID, SOLID, color);
+    }
+
+    /// Add `text`, the top left corner of its first line being at `top_left`.
+    /// Lines are separated by `\n`. Positions are rounded to whole pixels
+    /// to keep the glyphs sharp.
+    pub fn push_text(&mut self, font: &Font, text: &str, top_left: [f32; 2], color: [f32; 4]) {
+        self.push_scaled_text(font, text, top_left, 1.0, color);
+    }
+
+    /// Add `text` with the glyphs scaled by `scale`.
+    pub fn push_scaled_text(
+        &mut self,
+        font: &Font,
+        text: &str,
+        top_left: [f32; 2],
+        scale: f32,
+        color: [f32; 4])
+    {
+        let (atlas_w, atlas_h) = font.atlas_size();
+        let (atlas_w, atlas_h) = (atlas_w as f32, atlas_h as f32);
+        let left = top_left[0].round();
+        let mut top = top_left[1].round();
+
+        for line in text.lines() {
+            let advances = font.advances(line);
+            for (c, &pen) in line.chars().zip(advances.iter()) {
+                let glyph = match font.glyph(c) {
+                    Some(glyph) => *glyph,
+                    None => continue,
+                };
+                if glyph.width == 0 || glyph.height == 0 {
+                    continue;
+                }
+                let glyph_left = left + (pen + glyph.x_offset) as f32 * scale;
+                let glyph_top = self.below(top, glyph.y_offset as f32 * scale);
+                let glyph_bottom = self.below(glyph_top, glyph.height as f32 * scale);
+                // The atlas is uploaded upside down.
+                let tex_top_left = [glyph.x as f32 / atlas_w, 1.0 - glyph.y as f32 / atlas_h];
+                let tex_bottom_right = [
+                    (glyph.x + glyph.width) as f32 / atlas_w,
+                    1.0 - (glyph.y + glyph.height) as f32 / atlas_h,
+                ];
+                self.push_quad(
+                    [glyph_left, glyph_top],
+                    [glyph_left + glyph.width as f32 * scale, glyph_bottom],
+                    tex_top_left,
+                    tex_bottom_right,
+                    color);
+            }
+            top = self.below(top, font.line_height() as f32 * scale);
+        }
+    }
+
+    // Vertices go top left, top right, bottom left, bottom right.
+    fn push_quad(
+        &mut self,
+        top_left: [f32; 2],
+        bottom_right: [f32; 2],
+        tex_top_left: [f32; 2],
+        tex_bottom_right: [f32; 2],
+        color: [f32; 4])
+    {
+        let corners = [
+            ([top_left[0], top_left[1]], [tex_top_left[0], tex_top_left[1]]),
+            ([bottom_right[0], top_left[1]], [tex_bottom_right[0], tex_top_left[1]]),
+            ([top_left[0], bottom_right[1]], [tex_top_left[0], tex_bottom_right[1]]),
+            ([bottom_right[0], bottom_right[1]], [tex_bottom_right[0], tex_bottom_right[1]]),
+        ];
+        for &(position, tex_coords) in corners.iter() {
+            self.vertices.push(Vertex {
+                i_position: position,
+                i_tex_coords: tex_coords,
+                i_color: color,
+            });
+        }
+    }
+}
+
+// A batch and the GPU memory it is uploaded to.
+struct TextLayer {
+    batch: TextBatch,
+    vertex_buffer: VertexBuffer<Vertex>,
+    nb_quads: usize,
+}
+
+impl TextLayer {
+
+    fn upload(&mut self) {
+        self.nb_quads = cmp::min(self.batch.quads(), MAX_QUADS);
+        if self.batch.quads() > MAX_QUADS {
+            warn!("Too much text to draw, {} quads dropped", self.batch.quads() - MAX_QUADS);
+        }
+        if self.nb_quads > 0 {
+            let slice = self.vertex_buffer.slice(0 .. self.nb_quads * 4).unwrap();
+            slice.write(&self.batch.vertices[.. self.nb_quads * 4]);
+        }
+    }
+}
+
+/// Draws text with a bitmap font, both in the world and on the screen.
+///
+/// The text of each space is rebuilt as a whole with `update`
+/// and stays on screen until the next update.
+pub struct TextRenderer {
+    program: Program,
+    font: Font,
+    texture: Texture2d,
+    index_buffer: IndexBuffer<u16>,
+    world: TextLayer,
+    screen: TextLayer,
+}
+
+impl TextRenderer {
+
+    pub fn new(window: &Window) -> TextRenderer {
+
+        let font = Font::load(DEFAULT_FONT_PATH).unwrap_or_else(|e| {
+            panic!("Couldn't load font `{}`: {}", DEFAULT_FONT_PATH, e)
+        });
+        TextRenderer::with_font(window, font)
+    }
+
+    pub fn with_font(window: &Window, font: Font) -> TextRenderer {
+
+        let ref display = window.display;
+
+        let texture = {
+            let img = image::open(font.atlas()).unwrap().to_rgba();
+            let dims = img.dimensions();
+            Texture2d::new(display, RawImage2d::from_raw_rgba_reversed(img.into_raw(), dims)).unwrap()
+        };
+
+        let index_buffer = {
+            let mut ib_data = Vec::with_capacity(MAX_QUADS * 6);
+
+            for num in 0..MAX_QUADS {
+                let num = num as u16;
+                ib_data.push(num * 4);
+                ib_data.push(num * 4 + 1);
+                ib_data.push(num * 4 + 2);
+                ib_data.push(num * 4 + 1);
+                ib_data.push(num * 4 + 3);
+                ib_data.push(num * 4 + 2);
+            }
+            IndexBuffer::new(display, PrimitiveType::TrianglesList, &ib_data).unwrap()
+        };
+
+        let layer = |space| TextLayer {
+            batch: TextBatch::new(space),
+            vertex_buffer: VertexBuffer::empty_dynamic(display, MAX_QUADS * 4).unwrap(),
+            nb_quads: 0,
+        };
+
+        let program = program!(display,
+            140 => {
+                vertex: shaders::text::VERTEX_140,
+                fragment: shaders::text::FRAGMENT_140
+            },
+        ).unwrap();
+
+        TextRenderer {
+            program: program,
+            font: font,
+            texture: texture,
+            index_buffer: index_buffer,
+            world: layer(TextSpace::World),
+            screen: layer(TextSpace::Screen),
+        }
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Replace the text drawn in `space` by what `build` adds to the batch.
+    pub fn update<F>(&mut self, space: TextSpace, build: F)
+        where F: FnOnce(&mut TextBatch, &Font)
+    {
+        let layer = match space {
+            TextSpace::World => &mut self.world,
+            TextSpace::Screen => &mut self.screen,
+        };
+        layer.batch.clear();
+        build(&mut layer.batch, &self.font);
+        layer.upload();
+    }
+
+    /// Draw the text of `space`. `mvp` is the world transform
+    /// or the `screen_projection` depending on the space.
+    pub fn render<S: Surface>(
+        &self,
+        space: TextSpace,
+        target: &mut S,
+        mvp: &Matrix4<f32>,
+        draw_parameters: &DrawParameters)
+    {
+        use glium::uniforms::MagnifySamplerFilter::Nearest;
+        use glium::uniforms::MinifySamplerFilter;
+
+        let layer = match space {
+            TextSpace::World => &self.world,
+            TextSpace::Screen => &self.screen,
+        };
+        if layer.nb_quads == 0 {
+            return;
+        }
+
+        let ib_slice = self.index_buffer.slice(0 .. layer.nb_quads * 6).unwrap();
+
+        let uniforms = uniform! {
+            mvp: Into::<[[f32; 4]; 4]>::into(*mvp),
+            tex: self.texture.sampled()
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(Nearest)
+        };
+
+        target.draw(
+            &layer.vertex_buffer,
+            &ib_slice,
+            &self.program,
+            &uniforms,
+            draw_parameters
+        ).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use rendering::font::Font;
+    use super::{TextBatch, TextSpace};
+
+    const FONT: &'static str = r#"
+common lineHeight=8 base=6 scaleW=64 scaleH=32 pages=1 packed=0
+page id=0 file="test.png"
+char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4 page=0 chnl=15
+char id=97 x=5 y=1 width=3 height=4 xoffset=1 yoffset=3 xadvance=4 page=0 chnl=15
+kerning first=97 second=97 amount=-1
+"#;
+
+    #[test]
+    fn glyph_quads() {
+        let font: Font = FONT.parse().unwrap();
+        let mut batch = TextBatch::new(TextSpace::World);
+        batch.push_text(&font, "a a", [10.0, 20.0], [1.0; 4]);
+
+        // Spaces don't need a quad.
+        assert_eq!(batch.quads(), 2);
+        let second = &batch.vertices[4..8];
+        // Top left corner of the second `a`.
+        assert_eq!(second[0].i_position, [10.0 + 8.0 + 1.0, 20.0 - 3.0]);
+        // Bottom right corner.
+        assert_eq!(second[3].i_position, [10.0 + 8.0 + 1.0 + 3.0, 20.0 - 3.0 - 4.0]);
+        assert_eq!(second[0].i_tex_coords, [5.0 / 64.0, 1.0 - 1.0 / 32.0]);
+        assert_eq!(second[3].i_tex_coords, [8.0 / 64.0, 1.0 - 5.0 / 32.0]);
+    }
+
+    #[test]
+    fn kerning() {
+        let font: Font = FONT.parse().unwrap();
+        let mut batch = TextBatch::new(TextSpace::World);
+        batch.push_text(&font, "aa", [0.0, 0.0], [1.0; 4]);
+
+        assert_eq!(batch.vertices[4].i_position[0], 4.0 - 1.0 + 1.0);
+    }
+
+    #[test]
+    fn screen_space_lines() {
+        let font: Font = FONT.parse().unwrap();
+        let mut batch = TextBatch::new(TextSpace::Screen);
+        batch.push_text(&font, "a\n a", [10.0, 20.0], [1.0; 4]);
+
+        assert_eq!(batch.quads(), 2);
+        // y goes down on the screen, the texture is still the right way up.
+        let first = &batch.vertices[0..4];
+        assert_eq!(first[0].i_position, [10.0 + 1.0, 20.0 + 3.0]);
+        assert_eq!(first[3].i_position, [10.0 + 1.0 + 3.0, 20.0 + 3.0 + 4.0]);
+        assert_eq!(first[0].i_tex_coords, [5.0 / 64.0, 1.0 - 1.0 / 32.0]);
+        // The second line starts a line height lower.
+        let second = &batch.vertices[4..8];
+        assert_eq!(second[0].i_position, [10.0 + 4.0 + 1.0, 20.0 + 8.0 + 3.0]);
+    }
+
+    #[test]
+    fn scaled_text() {
+        let font: Font = FONT.parse().unwrap();
+        let mut batch = TextBatch::new(TextSpace::Screen);
+        batch.push_scaled_text(&font, "a\na", [0.0, 0.0], 2.0, [1.0; 4]);
+
+        let first = &batch.vertices[0..4];
+        assert_eq!(first[0].i_position, [2.0, 6.0]);
+        assert_eq!(first[3].i_position, [2.0 + 6.0, 6.0 + 8.0]);
+        assert_eq!(batch.vertices[4].i_position, [2.0, 16.0 + 6.0]);
+    }
+}
@@ -0,0 +1,29 @@
+use image::RgbaImage;
+use glium::backend::Facade;
+use glium::texture::Texture2dArray;
+use glium::texture::RawImage2d;
+
+/// Skins of the players, the index is their `TextureId`.
+pub const PLAYER_SKINS: &'static [&'static str] = &[
+    "./assets/players/Vurf.png",
+    "./assets/players/frost.png",
+    "./assets/players/Kiwan.png",
+];
+
+/// The images of the world, decoded but not yet
+/// sent to the GPU. Loading them can be deferred.
+pub struct Textures {
+    /// Tileset of the map.
+    pub tileset: RgbaImage,
+    /// Skins of the players, see `PLAYER_SKINS`.
+    pub skins: Vec<RgbaImage>,
+}
+
+/// Send `images` to the GPU, they must have the same size.
+pub fn texture_array<F: Facade>(display: &F, images: Vec<RgbaImage>) -> Texture2dArray {
+    let images = images.into_iter().map(|img| {
+        let dims = img.dimensions();
+        RawImage2d::from_raw_rgba_reversed(img.into_raw(), dims)
+    }).collect();
+    Texture2dArray::new(display, images).unwrap()
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+
+use events::{EventSystem, MenuKey};
+use events::commands::Direction;
+use ui::libs::{Libs, DEFAULT_LIBS_DIR};
+use ui::markup::{Action, Binding, Element, Library, Tag, TextPart, UilError, DEFAULT_MARKUP_DIR, MAIN_VIEW};
+use ui::query::Query;
+use ui::style::{StyleSheet, DEFAULT_STYLE_DIR};
+use ui::layout::{layout, LayoutBox, Measure};
+use ui::focus::Focus;
+
+/// What the player asked for in the UI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UiEvent {
+    /// A button was pressed, or a line input submitted
+    /// which gives `Action::Send`.
+    Action(Action),
+    /// Leave the menu.
+    Back,
+}
+
+/// The views of the UI with their style, the view shown,
+/// the values of the data bindings and the focus.
+///
+/// The layout is kept until the window is resized, another view
+/// is shown or a value changes.
+pub struct Document {
+    library: Library,
+    style: StyleSheet,
+    measure: Box<Measure>,
+    view: String,
+    size: (u32, u32),
+    values: HashMap<String, String>,
+    layout: Option<LayoutBox>,
+    focus: Focus,
+}
+
+impl Document {
+
+    /// Show the main view in a window of `size` pixels,
+    /// the text is measured with `measure`.
+    pub fn new(library: Library, style: StyleSheet, size: (u32, u32), measure: Box<Measure>) -> Document {
+        Document {
+            library: library,
+            style: style,
+            measure: measure,
+            view: MAIN_VIEW.to_string(),
+            size: size,
+            values: HashMap::new(),
+            layout: None,
+            focus: Focus::new(),
+        }
+    }
+
+    /// Load the libs, markup and style sheets of the game.
+    pub fn load(size: (u32, u32), measure: Box<Measure>) -> Result<Document, UilError> {
+        let libs = try!(Libs::load(DEFAULT_LIBS_DIR));
+        let style = try!(StyleSheet::load(DEFAULT_STYLE_DIR, &libs));
+        let library = try!(Library::load(DEFAULT_MARKUP_DIR));
+        Ok(Document::new(library, style, size, measure))
+    }
+
+    pub fn library(&self) -> &Library {
+        &self.library
+    }
+
+    /// Name of the view shown.
+    pub fn view_name(&self) -> &str {
+        &self.view
+    }
+
+    pub fn view(&self) -> Option<&Element> {
+        self.library.view(&self.view)
+    }
+
+    /// Show the view `name`. Returns `false` if there is no such view.
+    pub fn goto(&mut self, name: &str) -> bool {
+        if self.library.view(name).is_none() {
+            return false;
+        }
+        if self.view != name {
+            self.view = name.to_string();
+            self.layout = None;
+            self.focus.clear();
+        }
+        true
+    }
+
+    /// Value of the data binding `path`, such as `player.name`.
+    pub fn value(&self, path: &str) -> Option<&str> {
+        self.values.get(path).map(|v| &v[..])
+    }
+
+    pub fn set_value(&mut self, path: &str, value: String) {
+        if self.value(path) != Some(&value[..]) {
+            self.values.insert(path.to_string(), value);
+            self.layout = None;
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if self.size != (width, height) {
+            self.size = (width, height);
+            self.layout = None;
+        }
+    }
+
+    /// Index of the element with the focus, see `LayoutBox::element`.
+    pub fn focused(&self) -> Option<usize> {
+        self.focus.focused()
+    }
+
+    pub fn focused_element(&self) -> Option<&Element> {
+        let view = match self.view() {
+            Some(view) => view,
+            None => return None,
+        };
+        self.focus.focused().and_then(|index| view.select(&Query::new()).get(index).cloned())
+    }
+
+    /// Handle the keys and text of the menu, and the size of the window.
+    pub fn event_update(&mut self, event_sys: &EventSystem) -> Vec<UiEvent> {
+        if let Some((width, height)) = event_sys.resized() {
+            self.resize(width, height);
+        }
+        let mut events = Vec::new();
+        if !event_sys.typed_text().is_empty() {
+            self.edit_input(|value| value.push_str(event_sys.typed_text()));
+        }
+        for &key in event_sys.menu_keys() {
+            match key {
+                MenuKey::Back => events.push(UiEvent::Back),
+                MenuKey::Erase => self.edit_input(|value| { value.pop(); }),
+                MenuKey::Activate => events.extend(self.activate()),
+                _ => self.move_focus(key),
+            }
+        }
+        events
+    }
+
+    /// Layout of the view shown, computed again only when needed.
+    pub fn layout(&mut self) -> Option<&LayoutBox> {
+        self.update_layout();
+        self.layout.as_ref()
+    }
+
+    fn update_layout(&mut self) {
+        if self.layout.is_some() {
+            return;
+        }
+        let values = &self.values;
+        let bindings = |binding: &Binding| lookup(values, binding);
+        let size = (self.size.0 as f32, self.size.1 as f32);
+        let measure = &*self.measure;
+        let style = &self.style;
+        self.layout = self.library.view(&self.view).map(|view| {
+            layout(view, style, size, measure, &bindings)
+        });
+        if let Some(ref layout) = self.layout {
+            self.focus.ensure(layout);
+        }
+    }
+
+    fn move_focus(&mut self, key: MenuKey) {
+        self.update_layout();
+        if let Some(ref layout) = self.layout {
+            match key {
+                MenuKey::Up => self.focus.move_towards(layout, Direction::North),
+                MenuKey::Down => self.focus.move_towards(layout, Direction::South),
+                MenuKey::Left => self.focus.move_towards(layout, Direction::West),
+                MenuKey::Right => self.focus.move_towards(layout, Direction::East),
+                MenuKey::Next => self.focus.next(layout),
+                MenuKey::Previous => self.focus.previous(layout),
+                _ => (),
+            }
+        }
+    }
+
+    // Change the value bound to the line input with the focus.
+    fn edit_input<F: FnOnce(&mut String)>(&mut self, edit: F) {
+        self.update_layout();
+        let path = match self.focused_element().and_then(input_path) {
+            Some(path) => path,
+            None => return,
+        };
+        let mut value = self.value(&path).unwrap_or("").to_string();
+        edit(&mut value);
+        self.set_value(&path, value);
+    }
+
+    fn activate(&mut self) -> Option<UiEvent> {
+        self.update_layout();
+        let (tag, goto, action) = match self.focused_element() {
+            Some(element) => {
+                let values = &self.values;
+                let action = element.attribute("action").map(|text| text.evaluate(|b| lookup(values, b)));
+                (element.tag(), element.literal("goto-view").map(|v| v.to_string()), action)
+            }
+            None => return None,
+        };
+        match tag {
+            Tag::LineInput => Some(UiEvent::Action(Action::Send)),
+            Tag::Button => {
+                if let Some(view) = goto {
+                    self.goto(&view);
+                }
+                action.and_then(|name| Action::from_name(&name)).map(UiEvent::Action)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn lookup(values: &HashMap<String, String>, binding: &Binding) -> Option<String> {
+    values.get(&binding.path().join(".")).cloned()
+}
+
+// Path of the value edited by a line input, its value
+// must be a single data binding to be editable.
+fn input_path(element: &Element) -> Option<String> {
+    if element.tag() != Tag::LineInput {
+        return None;
+    }
+    let parts = match element.attribute("value") {
+        Some(text) => text.parts(),
+        None => return None,
+    };
+    match parts.first() {
+        Some(&TextPart::Binding(ref binding)) if parts.len() == 1 => Some(binding.path().join(".")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+
+    use events::{EventSystem, MenuKey, PushEvent};
+    use ui::libs::{Libs, DEFAULT_LIBS_DIR};
+    use ui::markup::{Action, Library, DEFAULT_MARKUP_DIR};
+    use ui::style::{Style, StyleSheet, DEFAULT_STYLE_DIR};
+    use ui::layout::{Content, Measure};
+    use super::{Document, UiEvent};
+
+    struct Monospace;
+
+    impl Measure for Monospace {
+        fn measure(&self, text: &str, _: &Style) -> (f32, f32) {
+            (text.chars().count() as f32 * 10.0, 20.0)
+        }
+    }
+
+    fn document() -> Document {
+        let library: Library = r#"
+            <view name="main">
+                <h1>Hi {{player.name}}</h1>
+                <button goto-view="options">Options</button>
+                <line-input value="{{chat.msg}}"/>
+                <button action="quit">Quit</button>
+            </view>
+            <view name="options">
+                <h1>Options</h1>
+                <button goto-view="main" action="{{options.action}}">Back</button>
+            </view>
+        "#.parse().unwrap();
+        let style = StyleSheet::parse("", &Libs::new()).unwrap();
+        Document::new(library, style, (800, 600), Box::new(Monospace))
+    }
+
+    fn text(document: &mut Document) -> Content {
+        document.layout().unwrap().boxes()[2].content.clone()
+    }
+
+    fn keys(document: &mut Document, keys: &[MenuKey], typed: &str) -> Vec<UiEvent> {
+        let mut event_sys = EventSystem::default();
+        event_sys.set_menu_open(true);
+        for &key in keys {
+            event_sys.push_menu_key(key);
+        }
+        for c in typed.chars() {
+            event_sys.push_char(c);
+        }
+        document.event_update(&event_sys)
+    }
+
+    #[test]
+    fn layout_is_updated() {
+        let mut document = document();
+        assert_eq!(text(&mut document), Content::Text("Hi ".to_string()));
+
+        document.set_value("player.name", "Bob".to_string());
+        assert_eq!(text(&mut document), Content::Text("Hi Bob".to_string()));
+
+        let mut event_sys = EventSystem::default();
+        event_sys.push_resize(400, 300);
+        document.event_update(&event_sys);
+        assert_eq!(document.layout().unwrap().rect.width, 400.0);
+
+        assert!(!document.goto("missing"));
+        assert!(document.goto("options"));
+        assert_eq!(document.view_name(), "options");
+        assert_eq!(text(&mut document), Content::Text("Options".to_string()));
+    }
+
+    #[test]
+    fn navigation_and_actions() {
+        let mut document = document();
+        // The first button has the focus once the view is shown.
+        document.layout();
+        assert_eq!(document.focused_element().map(|e| e.literal("goto-view")), Some(Some("options")));
+
+        // Text only goes to the line inputs.
+        assert!(keys(&mut document, &[], "a").is_empty());
+        assert_eq!(document.value("chat.msg"), None);
+
+        // Type in the line input, then submit it.
+        assert!(keys(&mut document, &[MenuKey::Down], "").is_empty());
+        assert!(keys(&mut document, &[], "hey").is_empty());
+        assert_eq!(document.value("chat.msg"), Some("hey"));
+        assert_eq!(keys(&mut document, &[MenuKey::Erase, MenuKey::Activate], ""),
+                   vec![UiEvent::Action(Action::Send)]);
+        assert_eq!(document.value("chat.msg"), Some("he"));
+
+        assert_eq!(keys(&mut document, &[MenuKey::Next, MenuKey::Activate, MenuKey::Back], ""),
+                   vec![UiEvent::Action(Action::Quit), UiEvent::Back]);
+
+        // Buttons change the view, their action can be bound.
+        document.set_value("options.action", "play".to_string());
+        assert!(keys(&mut document, &[MenuKey::Previous, MenuKey::Previous, MenuKey::Activate], "").is_empty());
+        assert_eq!(document.view_name(), "options");
+        assert_eq!(keys(&mut document, &[MenuKey::Activate], ""), vec![UiEvent::Action(Action::Play)]);
+        assert_eq!(document.view_name(), "main");
+    }
+
+    #[test]
+    fn game_assets() {
+        // Assets paths are relative to the root of the repository.
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+        let libs = Libs::load(root.join(DEFAULT_LIBS_DIR)).unwrap();
+        StyleSheet::load(root.join(DEFAULT_STYLE_DIR), &libs).unwrap();
+        let library = Library::load(root.join(DEFAULT_MARKUP_DIR)).unwrap();
+        let views = ["main", "connect", "settings", "connecting", "loading",
+                     "reconnecting", "disconnected", "pause"];
+        for view in &views {
+            assert!(library.view(view).is_some(), "missing view {}", view);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use events::commands::Direction;
+use ui::markup::Tag;
+use ui::layout::LayoutBox;
+
+/// The element of the view which has the focus.
+///
+/// The focus is only moved with the keyboard, see `docs/focus_uil.md`:
+/// to the closest element in a direction, or to the next one in the
+/// order of the markup. Only buttons and line inputs can have it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Focus {
+    focused: Option<usize>,
+}
+
+impl Focus {
+
+    pub fn new() -> Focus {
+        Focus::default()
+    }
+
+    /// Index of the element with the focus, see `LayoutBox::element`.
+    pub fn focused(&self) -> Option<usize> {
+        self.focused
+    }
+
+    /// Forget the focus, when another view is shown.
+    pub fn clear(&mut self) {
+        self.focused = None;
+    }
+
+    /// Give the focus to the first element if none has it.
+    pub fn ensure(&mut self, layout: &LayoutBox) {
+        let focusables = focusables(layout);
+        let valid = self.focused.map_or(false, |f| focusables.iter().any(|b| b.element == Some(f)));
+        if !valid {
+            self.focused = focusables.first().and_then(|b| b.element);
+        }
+    }
+
+    /// Focus the next element in the order of the markup, the first after the last.
+    pub fn next(&mut self, layout: &LayoutBox) {
+        self.step(layout, true);
+    }
+
+    pub fn previous(&mut self, layout: &LayoutBox) {
+        self.step(layout, false);
+    }
+
+    /// Focus the closest element in `direction`, north being the top of the
+    /// screen. The focus stays where it is if there is none.
+    pub fn move_towards(&mut self, layout: &LayoutBox, direction: Direction) {
+        let focusables = focusables(layout);
+        let from = match self.focused.and_then(|f| focusables.iter().find(|b| b.element == Some(f))) {
+            Some(b) => center(b),
+            None => return self.ensure(layout),
+        };
+        let mut best: Option<(f32, Option<usize>)> = None;
+        for b in focusables.iter().filter(|b| b.element != self.focused) {
+            let to = center(b);
+            let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+            // Distance in the direction and away from it.
+            let (along, across) = match direction {
+                Direction::North => (-dy, dx),
+                Direction::South => (dy, dx),
+                Direction::East => (dx, dy),
+                Direction::West => (-dx, dy),
+            };
+            if along <= 0.0 {
+                continue;
+            }
+            // Elements in line are preferred to closer ones aside.
+            let score = along + 2.0 * across.abs();
+            if best.map_or(true, |(best_score, _)| score < best_score) {
+                best = Some((score, b.element));
+            }
+        }
+        if let Some((_, element)) = best {
+            self.focused = element;
+        }
+    }
+
+    fn step(&mut self, layout: &LayoutBox, forward: bool) {
+        let focusables = focusables(layout);
+        if focusables.is_empty() {
+            self.focused = None;
+            return;
+        }
+        let count = focusables.len();
+        let index = match self.focused.and_then(|f| focusables.iter().position(|b| b.element == Some(f))) {
+            Some(i) if forward => (i + 1) % count,
+            Some(i) => (i + count - 1) % count,
+            None if forward => 0,
+            None => count - 1,
+        };
+        self.focused = focusables[index].element;
+    }
+}
+
+/// Whether elements with `tag` can have the focus.
+pub fn is_focusable(tag: Tag) -> bool {
+    tag == Tag::Button || tag == Tag::LineInput
+}
+
+fn focusables(layout: &LayoutBox) -> Vec<&LayoutBox> {
+    layout.boxes().into_iter().filter(|b| b.tag.map_or(false, is_focusable)).collect()
+}
+
+fn center(b: &LayoutBox) -> (f32, f32) {
+    (b.rect.x + b.rect.width / 2.0, b.rect.y + b.rect.height / 2.0)
+}
+
+#[cfg(test)]
+mod test {
+
+    use events::commands::Direction;
+    use ui::markup::Tag;
+    use ui::layout::{Content, LayoutBox, Rect};
+    use ui::style::Style;
+    use super::Focus;
+
+    fn element(index: usize, tag: Tag, x: f32, y: f32) -> LayoutBox {
+        LayoutBox {
+            rect: Rect { x: x, y: y, width: 100.0, height: 20.0 },
+            element: Some(index),
+            tag: Some(tag),
+            content: Content::None,
+            style: Style::default(),
+            children: Vec::new(),
+        }
+    }
+
+    // Two columns of buttons below a title,
+    // and a line input below the first column.
+    //
+    //   1 title
+    //   2 button   3 button
+    //   4 button   5 button
+    //   6 input
+    fn grid() -> LayoutBox {
+        let mut root = element(0, Tag::View, 0.0, 0.0);
+        root.children = vec![
+            element(1, Tag::H1, 0.0, 0.0),
+            element(2, Tag::Button, 0.0, 40.0),
+            element(3, Tag::Button, 200.0, 40.0),
+            element(4, Tag::Button, 0.0, 80.0),
+            element(5, Tag::Button, 200.0, 80.0),
+            element(6, Tag::LineInput, 0.0, 120.0),
+        ];
+        root
+    }
+
+    #[test]
+    fn tab_order() {
+        let layout = grid();
+        let mut focus = Focus::new();
+        focus.ensure(&layout);
+        assert_eq!(focus.focused(), Some(2));
+        focus.next(&layout);
+        assert_eq!(focus.focused(), Some(3));
+        for _ in 0..3 {
+            focus.next(&layout);
+        }
+        assert_eq!(focus.focused(), Some(6));
+        focus.next(&layout);
+        assert_eq!(focus.focused(), Some(2));
+        focus.previous(&layout);
+        assert_eq!(focus.focused(), Some(6));
+
+        focus.clear();
+        focus.previous(&layout);
+        assert_eq!(focus.focused(), Some(6));
+    }
+
+    #[test]
+    fn directions() {
+        let layout = grid();
+        let mut focus = Focus::new();
+        focus.move_towards(&layout, Direction::South);
+        assert_eq!(focus.focused(), Some(2));
+
+        focus.move_towards(&layout, Direction::East);
+        assert_eq!(focus.focused(), Some(3));
+        focus.move_towards(&layout, Direction::South);
+        assert_eq!(focus.focused(), Some(5));
+        // The input is the only element below, even if it is aside.
+        focus.move_towards(&layout, Direction::South);
+        assert_eq!(focus.focused(), Some(6));
+        focus.move_towards(&layout, Direction::North);
+        assert_eq!(focus.focused(), Some(4));
+        focus.move_towards(&layout, Direction::North);
+        assert_eq!(focus.focused(), Some(2));
+        // The title can't have the focus.
+        focus.move_towards(&layout, Direction::North);
+        assert_eq!(focus.focused(), Some(2));
+        focus.move_towards(&layout, Direction::West);
+        assert_eq!(focus.focused(), Some(2));
+    }
+}
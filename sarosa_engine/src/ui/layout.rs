@@ -0,0 +1,405 @@
+use rendering::font::Font;
+use ui::markup::{Binding, Element, Node, Tag};
+use ui::style::{Align, Direction, Justify, Style, StyleSheet};
+
+// Size of the widgets which have no text to fit, in pixels.
+const INPUT_MIN_WIDTH: f32 = 200.0;
+const PROGRESS_BAR_SIZE: (f32, f32) = (200.0, 12.0);
+
+/// Size of the text of the UI.
+pub trait Measure {
+    /// Width and height of `text` drawn with `style`, in pixels.
+    fn measure(&self, text: &str, style: &Style) -> (f32, f32);
+}
+
+impl Measure for Font {
+    fn measure(&self, text: &str, style: &Style) -> (f32, f32) {
+        // Bitmap fonts are scaled to the requested size.
+        let scale = style.font_size.map_or(1.0, |size| size / self.line_height() as f32);
+        // An empty line still takes the height of a line.
+        let lines = ::std::cmp::max(text.lines().count(), 1) as f32;
+        (self.width(text) as f32 * scale, lines * self.line_height() as f32 * scale)
+    }
+}
+
+/// A rectangle in screen space: from the top left
+/// corner of the window, y going down, in pixels.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+
+    // The rectangle without `length` on each side.
+    fn shrink(&self, length: f32) -> Rect {
+        Rect {
+            x: self.x + length,
+            y: self.y + length,
+            width: (self.width - 2.0 * length).max(0.0),
+            height: (self.height - 2.0 * length).max(0.0),
+        }
+    }
+}
+
+/// What is drawn inside a box, besides its background.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    None,
+    /// Text with the data bindings replaced by their value.
+    Text(String),
+    /// A progress bar filled between 0 and 1.
+    Progress(f32),
+}
+
+/// A box placed on screen by the layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutBox {
+    /// Includes the padding, not the margin.
+    pub rect: Rect,
+    /// Index of the element in the order of `Element::select`,
+    /// `None` for the text between the elements.
+    pub element: Option<usize>,
+    pub tag: Option<Tag>,
+    pub content: Content,
+    pub style: Style,
+    pub children: Vec<LayoutBox>,
+}
+
+impl LayoutBox {
+
+    /// The box and its descendants, parents first.
+    pub fn boxes(&self) -> Vec<&LayoutBox> {
+        let mut boxes = vec![self];
+        for child in self.children.iter() {
+            boxes.extend(child.boxes());
+        }
+        boxes
+    }
+
+    /// The box of the element with the index `element`.
+    pub fn find(&self, element: usize) -> Option<&LayoutBox> {
+        self.boxes().into_iter().find(|b| b.element == Some(element))
+    }
+}
+
+/// Place `view` in a window of `size` pixels.
+///
+/// Like a flexbox, each element places its children one after the
+/// other in its `layout` direction. They take their preferred size
+/// (what they contain, or their `width` and `height`) and share the
+/// free space according to `grow`. Across the direction, they are
+/// stretched unless their parent aligns them otherwise. The view
+/// always fills the window.
+///
+/// `lookup` gives the value of the data bindings.
+pub fn layout(
+    view: &Element,
+    sheet: &StyleSheet,
+    size: (f32, f32),
+    measure: &Measure,
+    lookup: &Fn(&Binding) -> Option<String>) -> LayoutBox
+{
+    let mut builder = Builder {
+        sheet: sheet,
+        measure: measure,
+        lookup: lookup,
+        next_element: 0,
+    };
+    let mut root = builder.element(view, &Style::default());
+    arrange(&mut root, Rect { x: 0.0, y: 0.0, width: size.0, height: size.1 });
+    root
+}
+
+// Builds the tree of boxes with their style and preferred size.
+struct Builder<'a> {
+    sheet: &'a StyleSheet,
+    measure: &'a Measure,
+    lookup: &'a Fn(&Binding) -> Option<String>,
+    next_element: usize,
+}
+
+impl<'a> Builder<'a> {
+
+    fn element(&mut self, element: &Element, parent: &Style) -> LayoutBox {
+        let index = self.next_element;
+        self.next_element += 1;
+        let style = self.sheet.compute(element.classes(), parent);
+
+        let lookup = self.lookup;
+        let evaluate = |name: &str| {
+            element.attribute(name).map_or(String::new(), |text| text.evaluate(|b| lookup(b)))
+        };
+        let content = match element.tag() {
+            Tag::LineInput => Content::Text(evaluate("value")),
+            Tag::ProgressBar => {
+                // The value is a percentage, anything else shows an empty bar.
+                let percent = evaluate("value").trim().parse::<f32>().unwrap_or(0.0);
+                Content::Progress((percent / 100.0).max(0.0).min(1.0))
+            }
+            _ => Content::None,
+        };
+
+        let mut children = Vec::new();
+        for child in element.children() {
+            match *child {
+                Node::Element(ref child) => children.push(self.element(child, &style)),
+                Node::Text(ref text) => children.push(self.text(text.evaluate(|b| lookup(b)), &style)),
+            }
+        }
+
+        let (width, height) = match (element.tag(), &content) {
+            (Tag::LineInput, &Content::Text(ref value)) => {
+                let (width, height) = self.measure.measure(value, &style);
+                (width.max(INPUT_MIN_WIDTH), height)
+            }
+            (Tag::ProgressBar, _) => PROGRESS_BAR_SIZE,
+            _ => content_size(&style, &children),
+        };
+        LayoutBox {
+            rect: preferred_rect(&style, width, height),
+            element: Some(index),
+            tag: Some(element.tag()),
+            content: content,
+            style: style,
+            children: children,
+        }
+    }
+
+    fn text(&self, text: String, parent: &Style) -> LayoutBox {
+        let style = parent.inherit();
+        let (width, height) = self.measure.measure(&text, &style);
+        LayoutBox {
+            rect: Rect { x: 0.0, y: 0.0, width: width, height: height },
+            element: None,
+            tag: None,
+            content: Content::Text(text),
+            style: style,
+            children: Vec::new(),
+        }
+    }
+}
+
+// Size of the children placed one after the other, without padding.
+fn content_size(style: &Style, children: &[LayoutBox]) -> (f32, f32) {
+    let gaps = style.gap * children.len().saturating_sub(1) as f32;
+    let sizes = children.iter().map(outer_size);
+    let (along, across) = sizes.fold((gaps, 0.0f32), |(along, across), (w, h)| match style.direction {
+        Direction::Column => (along + h, across.max(w)),
+        Direction::Row => (along + w, across.max(h)),
+    });
+    match style.direction {
+        Direction::Column => (across, along),
+        Direction::Row => (along, across),
+    }
+}
+
+fn preferred_rect(style: &Style, content_width: f32, content_height: f32) -> Rect {
+    Rect {
+        x: 0.0,
+        y: 0.0,
+        width: style.width.unwrap_or(content_width + 2.0 * style.padding),
+        height: style.height.unwrap_or(content_height + 2.0 * style.padding),
+    }
+}
+
+// Size of the box including its margin.
+fn outer_size(b: &LayoutBox) -> (f32, f32) {
+    (b.rect.width + 2.0 * b.style.margin, b.rect.height + 2.0 * b.style.margin)
+}
+
+// Place `b` in `rect` and its children inside it, their
+// rect holds their preferred size until then.
+fn arrange(b: &mut LayoutBox, rect: Rect) {
+    b.rect = rect;
+    let inner = rect.shrink(b.style.padding);
+    let style = b.style.clone();
+    let column = style.direction == Direction::Column;
+    // Lengths along and across the direction of the layout.
+    let (inner_along, inner_across) = if column {
+        (inner.height, inner.width)
+    } else {
+        (inner.width, inner.height)
+    };
+    let along = |size: (f32, f32)| if column { size.1 } else { size.0 };
+    let across = |size: (f32, f32)| if column { size.0 } else { size.1 };
+
+    let count = b.children.len();
+    let gaps = style.gap * count.saturating_sub(1) as f32;
+    let used = b.children.iter().map(|c| along(outer_size(c))).fold(gaps, |sum, l| sum + l);
+    let mut free = (inner_along - used).max(0.0);
+
+    let total_grow = b.children.iter().map(|c| c.style.grow).fold(0.0, |sum, g| sum + g);
+    let grow_unit = if total_grow > 0.0 { free / total_grow } else { 0.0 };
+    if total_grow > 0.0 {
+        free = 0.0;
+    }
+
+    let (mut position, spacing) = match style.justify {
+        Justify::Start => (0.0, 0.0),
+        Justify::Center => (free / 2.0, 0.0),
+        Justify::End => (free, 0.0),
+        Justify::SpaceBetween if count > 1 => (0.0, free / (count - 1) as f32),
+        Justify::SpaceBetween => (0.0, 0.0),
+    };
+
+    for child in b.children.iter_mut() {
+        let margin = child.style.margin;
+        let preferred = (child.rect.width, child.rect.height);
+        let length = along(preferred) + child.style.grow * grow_unit;
+
+        let fixed_across = if column { child.style.width } else { child.style.height };
+        let available = (inner_across - 2.0 * margin).max(0.0);
+        let breadth = match style.align {
+            Align::Stretch if fixed_across.is_none() => available,
+            _ => across(preferred),
+        };
+        let offset = match style.align {
+            Align::Start | Align::Stretch => 0.0,
+            Align::Center => (available - breadth) / 2.0,
+            Align::End => available - breadth,
+        };
+
+        let start_along = position + margin;
+        let start_across = offset + margin;
+        let child_rect = if column {
+            Rect { x: inner.x + start_across, y: inner.y + start_along, width: breadth, height: length }
+        } else {
+            Rect { x: inner.x + start_along, y: inner.y + start_across, width: length, height: breadth }
+        };
+        arrange(child, child_rect);
+        position += length + 2.0 * margin + style.gap + spacing;
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use ui::libs::Libs;
+    use ui::markup::{Binding, Library, Tag};
+    use ui::query::Query;
+    use ui::style::{Style, StyleSheet};
+    use super::{layout, Content, LayoutBox, Measure, Rect};
+
+    // Every character is 10 pixels wide and 20 pixels high.
+    struct Monospace;
+
+    impl Measure for Monospace {
+        fn measure(&self, text: &str, _: &Style) -> (f32, f32) {
+            (text.chars().count() as f32 * 10.0, 20.0)
+        }
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect { x: x, y: y, width: width, height: height }
+    }
+
+    fn layout_view(markup: &str, style: &str, size: (f32, f32)) -> LayoutBox {
+        let library: Library = markup.parse().unwrap();
+        let sheet = StyleSheet::parse(style, &Libs::new()).unwrap();
+        let lookup = |binding: &Binding| if binding.path().join(".") == "player.name" {
+            Some("Bob".to_string())
+        } else {
+            None
+        };
+        layout(library.view("main").unwrap(), &sheet, size, &Monospace, &lookup)
+    }
+
+    #[test]
+    fn column_stretch() {
+        let root = layout_view(r#"
+            <view name="main" class="root">
+                <h1>Title</h1>
+                <button class="btn">Play</button>
+            </view>
+        "#, ".root { padding: 10px; gap: 5px } .btn { height: 30px; margin: 2px }", (800.0, 600.0));
+
+        assert_eq!(root.rect, rect(0.0, 0.0, 800.0, 600.0));
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.children[0].rect, rect(10.0, 10.0, 780.0, 20.0));
+        assert_eq!(root.children[1].rect, rect(12.0, 37.0, 776.0, 30.0));
+        // The label of the button.
+        assert_eq!(root.children[1].children[0].rect, rect(12.0, 37.0, 776.0, 20.0));
+        assert_eq!(root.children[1].children[0].content, Content::Text("Play".to_string()));
+        assert_eq!(root.children[1].children[0].element, None);
+    }
+
+    #[test]
+    fn row_grow_and_justify() {
+        let style = ".row { layout: row; align: center; justify: center } \
+                     .grow { grow: 1 } \
+                     .fixed { width: 100px; height: 40px }";
+        let root = layout_view(r#"
+            <view name="main" class="row">
+                <group class="fixed"/>
+                <group class="fixed"/>
+            </view>
+        "#, style, (400.0, 100.0));
+        assert_eq!(root.children[0].rect, rect(100.0, 30.0, 100.0, 40.0));
+        assert_eq!(root.children[1].rect, rect(200.0, 30.0, 100.0, 40.0));
+
+        // The free space goes to the elements which grow.
+        let root = layout_view(r#"
+            <view name="main" class="row">
+                <group class="fixed"/>
+                <group class="grow">ab</group>
+            </view>
+        "#, style, (400.0, 100.0));
+        assert_eq!(root.children[0].rect, rect(0.0, 30.0, 100.0, 40.0));
+        assert_eq!(root.children[1].rect, rect(100.0, 40.0, 300.0, 20.0));
+    }
+
+    #[test]
+    fn space_between_and_end() {
+        let root = layout_view(r#"
+            <view name="main" class="menu">
+                <h1>a</h1>
+                <h1>b</h1>
+                <h1>c</h1>
+            </view>
+        "#, ".menu { justify: space-between; align: end }", (100.0, 100.0));
+        let tops: Vec<_> = root.children.iter().map(|c| c.rect.y).collect();
+        assert_eq!(tops, vec![0.0, 40.0, 80.0]);
+        assert!(root.children.iter().all(|c| c.rect.x == 90.0 && c.rect.width == 10.0));
+    }
+
+    #[test]
+    fn contents_and_elements() {
+        let root = layout_view(r#"
+            <view name="main">
+                <group>Hello {{player.name}}!</group>
+                <line-input value="{{chat.msg}}"/>
+                <progress-bar value="25"/>
+            </view>
+        "#, "", (800.0, 600.0));
+
+        let boxes = root.boxes();
+        assert_eq!(boxes[2].content, Content::Text("Hello Bob!".to_string()));
+        assert_eq!(boxes[3].content, Content::Text(String::new()));
+        assert_eq!(boxes[3].rect.height, 20.0);
+        assert_eq!(boxes[4].content, Content::Progress(0.25));
+
+        // The indices of the boxes follow the order of the markup.
+        let library: Library = r#"<view name="main"><group/><line-input/><progress-bar/></view>"#
+            .parse().unwrap();
+        let elements = library.view("main").unwrap().select(&Query::new());
+        for (index, element) in elements.iter().enumerate() {
+            assert_eq!(root.find(index).and_then(|b| b.tag), Some(element.tag()));
+        }
+        assert_eq!(root.find(1).map(|b| b.tag), Some(Some(Tag::Group)));
+        assert_eq!(root.find(4), None);
+    }
+
+    #[test]
+    fn resize() {
+        let markup = r#"<view name="main" class="c"><group class="g"/></view>"#;
+        let style = ".c { align: center; justify: center } .g { width: 50px; height: 50px }";
+        let small = layout_view(markup, style, (100.0, 100.0));
+        let large = layout_view(markup, style, (300.0, 200.0));
+        assert_eq!(small.children[0].rect, rect(25.0, 25.0, 50.0, 50.0));
+        assert_eq!(large.children[0].rect, rect(125.0, 75.0, 50.0, 50.0));
+    }
+}
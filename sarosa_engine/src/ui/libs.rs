@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use ui::markup::UilError;
+use ui::style::{parse_color, Color};
+use ui::tokens::{Token, Tokens};
+use ui::xml::{Position, SyntaxError};
+use ui::read_sources;
+
+/// Directory of the libs of the game.
+pub const DEFAULT_LIBS_DIR: &'static str = "./assets/ui/libs";
+
+const LIBS_EXTENSION: &'static str = "libs";
+
+/// A resource or a constant defined in the libs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LibValue {
+    /// Lengths are in pixels, `px` is optional.
+    Number(f32),
+    Str(String),
+    Color(Color),
+    Tuple(Vec<LibValue>),
+    Font {
+        /// Path of the font description, from the root of the game.
+        path: String,
+        /// Size of the text in pixels, the size of the font by default.
+        size: Option<f32>,
+    },
+}
+
+/// The resources and constants the style sheets refer to,
+/// named after their namespace such as `fonts.default`.
+///
+/// Libs look like:
+///
+/// ```text
+/// fonts {
+///     default: Font {
+///         path: "./assets/fonts/dejavu_sans_mono_12.fnt",
+///         size: 12,
+///     },
+/// }
+///
+/// cst {
+///     btn-width: 200px,
+///     highlight: #FFCC00,
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Libs {
+    values: BTreeMap<String, LibValue>,
+}
+
+impl Libs {
+
+    pub fn new() -> Libs {
+        Libs::default()
+    }
+
+    /// Load all the libs of `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Libs, UilError> {
+        let mut libs = Libs::new();
+        for (path, source) in try!(read_sources(dir.as_ref(), LIBS_EXTENSION)) {
+            try!(libs.add(&source).map_err(|e| e.in_file(&path)));
+        }
+        Ok(libs)
+    }
+
+    pub fn add(&mut self, source: &str) -> Result<(), UilError> {
+        let mut tokens = try!(Tokens::new(source));
+        while !tokens.is_empty() {
+            let namespace = try!(tokens.ident());
+            try!(tokens.expect('{'));
+            while !tokens.eat('}') {
+                let position = tokens.position();
+                let name = format!("{}.{}", namespace, try!(tokens.ident()));
+                try!(tokens.expect(':'));
+                let value = try!(parse_value(&mut tokens));
+                if self.values.contains_key(&name) {
+                    return Err(UilError::Invalid(format!("{}: `{}` is defined twice", position, name)));
+                }
+                self.values.insert(name, value);
+                if !tokens.eat(',') && tokens.peek() != Some(&Token::Punct('}')) {
+                    return Err(tokens.unexpected("`,` or `}`").into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The value named `path`, such as `fonts.default`.
+    pub fn get(&self, path: &str) -> Option<&LibValue> {
+        self.values.get(path)
+    }
+}
+
+impl FromStr for Libs {
+    type Err = UilError;
+
+    fn from_str(s: &str) -> Result<Libs, UilError> {
+        let mut libs = Libs::new();
+        try!(libs.add(s));
+        Ok(libs)
+    }
+}
+
+fn parse_value(tokens: &mut Tokens) -> Result<LibValue, SyntaxError> {
+    let position = tokens.position();
+    match tokens.next() {
+        Some(Token::Number(value, None)) => Ok(LibValue::Number(value)),
+        Some(Token::Number(value, Some(ref unit))) if unit == "px" => Ok(LibValue::Number(value)),
+        Some(Token::Number(_, Some(unit))) => Err(SyntaxError {
+            position: position,
+            message: format!("unknown unit `{}`", unit),
+        }),
+        Some(Token::Str(s)) => Ok(LibValue::Str(s)),
+        Some(Token::Color(hex)) => parse_color(&hex).map(LibValue::Color).ok_or_else(|| SyntaxError {
+            position: position,
+            message: format!("invalid color `#{}`", hex),
+        }),
+        Some(Token::Punct('(')) => {
+            let mut values = Vec::new();
+            while !tokens.eat(')') {
+                values.push(try!(parse_value(tokens)));
+                if !tokens.eat(',') && tokens.peek() != Some(&Token::Punct(')')) {
+                    return Err(tokens.unexpected("`,` or `)`"));
+                }
+            }
+            Ok(LibValue::Tuple(values))
+        }
+        Some(Token::Ident(ref constructor)) if constructor == "Font" => parse_font(tokens, position),
+        Some(token) => Err(SyntaxError {
+            position: position,
+            message: format!("expected a value, found {}", token.describe()),
+        }),
+        None => Err(tokens.unexpected("a value")),
+    }
+}
+
+fn parse_font(tokens: &mut Tokens, position: Position) -> Result<LibValue, SyntaxError> {
+    let mut path = None;
+    let mut size = None;
+    try!(tokens.expect('{'));
+    while !tokens.eat('}') {
+        let field_position = tokens.position();
+        let field = try!(tokens.ident());
+        try!(tokens.expect(':'));
+        match (&field[..], try!(parse_value(tokens))) {
+            ("path", LibValue::Str(s)) => path = Some(s),
+            ("size", LibValue::Number(n)) => size = Some(n),
+            _ => return Err(SyntaxError {
+                position: field_position,
+                message: format!("invalid field `{}` of `Font`", field),
+            }),
+        }
+        if !tokens.eat(',') && tokens.peek() != Some(&Token::Punct('}')) {
+            return Err(tokens.unexpected("`,` or `}`"));
+        }
+    }
+    match path {
+        Some(path) => Ok(LibValue::Font { path: path, size: size }),
+        None => Err(SyntaxError {
+            position: position,
+            message: "`Font` needs a `path`".to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use ui::UilError;
+    use super::{Libs, LibValue};
+
+    #[test]
+    fn parse_libs() {
+        let libs: Libs = r#"
+            fonts {
+                default: Font {
+                    path: "./assets/fonts/dejavu_sans_mono_12.fnt",
+                    size: 12,
+                },
+                small: Font { path: "small.fnt" }
+            }
+
+            cst {
+                btn-width: 200px,
+                ratio: 0.5,
+                highlight: #FFCC00,
+                size: (1, 2),
+            }
+        "#.parse().unwrap();
+
+        assert_eq!(libs.get("fonts.default"), Some(&LibValue::Font {
+            path: "./assets/fonts/dejavu_sans_mono_12.fnt".to_string(),
+            size: Some(12.0),
+        }));
+        assert_eq!(libs.get("fonts.small"), Some(&LibValue::Font {
+            path: "small.fnt".to_string(),
+            size: None,
+        }));
+        assert_eq!(libs.get("cst.btn-width"), Some(&LibValue::Number(200.0)));
+        assert_eq!(libs.get("cst.ratio"), Some(&LibValue::Number(0.5)));
+        assert_eq!(libs.get("cst.highlight"), Some(&LibValue::Color([1.0, 0.8, 0.0, 1.0])));
+        assert_eq!(libs.get("cst.size"), Some(&LibValue::Tuple(vec![LibValue::Number(1.0),
+                                                                  LibValue::Number(2.0)])));
+        assert_eq!(libs.get("cst.missing"), None);
+    }
+
+    fn error(source: &str) -> String {
+        match source.parse::<Libs>() {
+            Err(UilError::Syntax(e)) | Err(UilError::Invalid(e)) => e,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("`{}` should be rejected", source),
+        }
+    }
+
+    #[test]
+    fn invalid_libs() {
+        assert_eq!(error("cst { a: 1, a: 2 }"), "line 1, column 13: `cst.a` is defined twice");
+        assert_eq!(error("cst { a: 1em }"), "line 1, column 10: unknown unit `em`");
+        assert_eq!(error("cst { a: 1 b: 2 }"), "line 1, column 12: expected `,` or `}`, found `b`");
+        assert_eq!(error("cst { a: #GG0000 }"), "line 1, column 10: invalid color `#GG0000`");
+        assert_eq!(error("fonts { a: Font { size: 2 } }"), "line 1, column 12: `Font` needs a `path`");
+        assert_eq!(error("fonts { a: Font { path: 2 } }"),
+                   "line 1, column 19: invalid field `path` of `Font`");
+        assert_eq!(error("cst { a: Image {} }"), "line 1, column 10: expected a value, found `Image`");
+    }
+}
@@ -0,0 +1,811 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::fmt;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use events::keys::key_from_name;
+use ui::query::Query;
+use ui::xml;
+use ui::xml::{Position, SyntaxError, XmlElement, XmlNode};
+use ui::read_sources;
+
+/// Directory of the markup files of the game.
+pub const DEFAULT_MARKUP_DIR: &'static str = "./assets/ui/markup";
+
+/// View shown when the menus are opened.
+pub const MAIN_VIEW: &'static str = "main";
+
+// Only the files with this extension are loaded from a directory.
+const MARKUP_EXTENSION: &'static str = "xml";
+
+// Attributes naming things resolved when the markup is loaded,
+// a data binding would only be known later.
+const STATIC_ATTRIBUTES: &'static [&'static str] = &["class", "name", "path", "goto-view", "template-name"];
+
+// Generates the enum of the tags and the conversions
+// with their names in the markup.
+macro_rules! tags {
+    ($($tag:ident => $name:expr, [$($attribute:expr),*], $content:expr;)*) => {
+
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum Tag {
+            $($tag,)*
+        }
+
+        impl Tag {
+
+            pub fn from_name(name: &str) -> Option<Tag> {
+                match name {
+                    $($name => Some(Tag::$tag),)*
+                    _ => None,
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $(Tag::$tag => $name,)*
+                }
+            }
+
+            // Attributes accepted besides `class`.
+            fn attributes(&self) -> &'static [&'static str] {
+                match *self {
+                    $(Tag::$tag => &[$($attribute),*],)*
+                }
+            }
+
+            // Whether the tag can contain text and other tags.
+            fn has_content(&self) -> bool {
+                match *self {
+                    $(Tag::$tag => $content,)*
+                }
+            }
+        }
+    }
+}
+
+tags! {
+    View        => "view",          ["name"],                       true;
+    // Either the definition of a template or its inclusion.
+    Template    => "template",      ["name", "path"],               true;
+    Group       => "group",         [],                             true;
+    H1          => "h1",            [],                             true;
+    Button      => "button",        ["goto-view", "action", "key"], true;
+    LineInput   => "line-input",    ["value", "key"],               false;
+    ProgressBar => "progress-bar",  ["value"],                      false;
+    Repeat      => "repeat",        ["iter", "template-name"],      false;
+}
+
+/// What the engine does when a button is pressed,
+/// see `docs/actions_uil.md`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Leave the game.
+    Quit,
+    /// Join the game where the player left it.
+    Play,
+    /// Start the game with a local server.
+    Offline,
+    /// Send the content of the view to the server, what it
+    /// means is defined by the code handling the view.
+    Send,
+}
+
+impl Action {
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        match name {
+            "quit" => Some(Action::Quit),
+            "play" => Some(Action::Play),
+            "offline" => Some(Action::Offline),
+            "send" => Some(Action::Send),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Action::Quit => "quit",
+            Action::Play => "play",
+            Action::Offline => "offline",
+            Action::Send => "send",
+        }
+    }
+}
+
+/// A data binding such as `{{player.name}}`: a path
+/// in the namespaces of the models attached to the view.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Binding {
+    path: Vec<String>,
+}
+
+impl Binding {
+
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    // `content` is what is between the braces.
+    fn parse(content: &str) -> Option<Binding> {
+        let path: Vec<String> = content.trim().split('.').map(|s| s.to_string()).collect();
+        let valid = path.iter().all(|name| {
+            !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        });
+        if valid {
+            Some(Binding { path: path })
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{{{{}}}}}", self.path.join("."))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextPart {
+    Literal(String),
+    Binding(Binding),
+}
+
+/// Text of the markup or value of an attribute,
+/// which can contain data bindings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    parts: Vec<TextPart>,
+}
+
+impl Text {
+
+    pub fn parts(&self) -> &[TextPart] {
+        &self.parts
+    }
+
+    /// The text, if it doesn't contain any data binding.
+    pub fn literal(&self) -> Option<&str> {
+        if self.parts.is_empty() {
+            return Some("");
+        }
+        match self.parts[0] {
+            TextPart::Literal(ref s) if self.parts.len() == 1 => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn bindings(&self) -> Vec<&Binding> {
+        self.parts.iter().filter_map(|part| match *part {
+            TextPart::Binding(ref binding) => Some(binding),
+            TextPart::Literal(_) => None,
+        }).collect()
+    }
+
+    /// Replace the data bindings by their value,
+    /// the unknown ones are replaced by nothing.
+    pub fn evaluate<F>(&self, mut lookup: F) -> String
+        where F: FnMut(&Binding) -> Option<String>
+    {
+        let mut text = String::new();
+        for part in self.parts.iter() {
+            match *part {
+                TextPart::Literal(ref s) => text.push_str(s),
+                TextPart::Binding(ref binding) => {
+                    if let Some(value) = lookup(binding) {
+                        text.push_str(&value);
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+impl FromStr for Text {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Text, String> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(TextPart::Literal(rest[..start].to_string()));
+            }
+            let end = try!(rest[start..].find("}}").ok_or_else(|| {
+                format!("unclosed data binding `{}`", &rest[start..])
+            })) + start;
+            let content = &rest[start + 2..end];
+            let binding = try!(Binding::parse(content).ok_or_else(|| {
+                format!("invalid data binding `{{{{{}}}}}`", content)
+            }));
+            parts.push(TextPart::Binding(binding));
+            rest = &rest[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(TextPart::Literal(rest.to_string()));
+        }
+        Ok(Text { parts: parts })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(Text),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    tag: Tag,
+    classes: Vec<String>,
+    attributes: BTreeMap<String, Text>,
+    children: Vec<Node>,
+    position: Position,
+}
+
+impl Element {
+
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&Text> {
+        self.attributes.get(name)
+    }
+
+    /// Value of the attribute `name` if it doesn't contain data bindings.
+    pub fn literal(&self, name: &str) -> Option<&str> {
+        self.attribute(name).and_then(Text::literal)
+    }
+
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    /// Where the element starts in its markup file.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// The element and its descendants matching `query`, in the order of the markup.
+    pub fn select(&self, query: &Query) -> Vec<&Element> {
+        let mut selected = Vec::new();
+        self.select_into(query, &mut selected);
+        selected
+    }
+
+    pub fn select_first(&self, query: &Query) -> Option<&Element> {
+        self.select(query).into_iter().next()
+    }
+
+    /// All the data bindings used by the element and its descendants.
+    pub fn bindings(&self) -> Vec<&Binding> {
+        let mut bindings = Vec::new();
+        for element in self.select(&Query::new()) {
+            for text in element.attributes.values() {
+                bindings.extend(text.bindings());
+            }
+            for child in element.children.iter() {
+                if let Node::Text(ref text) = *child {
+                    bindings.extend(text.bindings());
+                }
+            }
+        }
+        bindings
+    }
+
+    fn select_into<'a>(&'a self, query: &Query, selected: &mut Vec<&'a Element>) {
+        if query.matches(self) {
+            selected.push(self);
+        }
+        for child in self.children.iter() {
+            if let Node::Element(ref element) = *child {
+                element.select_into(query, selected);
+            }
+        }
+    }
+}
+
+/// All the views and templates of the UI.
+///
+/// Markup sources are added one by one, then `resolve` checks the
+/// references between them and includes the templates where they
+/// are used.
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    views: BTreeMap<String, Element>,
+    templates: BTreeMap<String, Element>,
+}
+
+#[derive(Debug)]
+pub enum UilError {
+    Io(io::Error),
+    /// The markup isn't well-formed XML.
+    Syntax(String),
+    /// The markup is well-formed but isn't valid UIL.
+    Invalid(String),
+}
+
+impl Library {
+
+    pub fn new() -> Library {
+        Library::default()
+    }
+
+    /// Load and resolve all the markup files of `dir`.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Library, UilError> {
+        let mut library = Library::new();
+        for (path, source) in try!(read_sources(dir.as_ref(), MARKUP_EXTENSION)) {
+            try!(library.add(&source).map_err(|e| e.in_file(&path)));
+        }
+        try!(library.resolve());
+        Ok(library)
+    }
+
+    /// Add the views and templates of a markup source.
+    pub fn add(&mut self, source: &str) -> Result<(), UilError> {
+        let roots = try!(xml::parse(source));
+        for root in roots.iter() {
+            let element = try!(build(root, Context::Root));
+            let name = element.literal("name").unwrap().to_string();
+            let (kind, elements) = match element.tag {
+                Tag::View => ("view", &mut self.views),
+                _ => ("template", &mut self.templates),
+            };
+            if elements.contains_key(&name) {
+                return Err(invalid(element.position, format!("{} `{}` is defined twice", kind, name)));
+            }
+            elements.insert(name, element);
+        }
+        Ok(())
+    }
+
+    /// Include the templates where they are used and check that
+    /// the views and templates referred to exist.
+    pub fn resolve(&mut self) -> Result<(), UilError> {
+        let mut templates = BTreeMap::new();
+        for (name, template) in self.templates.iter() {
+            let mut included = vec![name.clone()];
+            templates.insert(name.clone(), try!(self.include_templates(template, &mut included)));
+        }
+        let mut views = BTreeMap::new();
+        for (name, view) in self.views.iter() {
+            views.insert(name.clone(), try!(self.include_templates(view, &mut Vec::new())));
+        }
+        self.templates = templates;
+        self.views = views;
+
+        for element in self.views.values().chain(self.templates.values()) {
+            for button in element.select(&Query::new().tag(Tag::Button)) {
+                if let Some(view) = button.literal("goto-view") {
+                    if !self.views.contains_key(view) {
+                        return Err(invalid(button.position, format!("unknown view `{}`", view)));
+                    }
+                }
+            }
+            for repeat in element.select(&Query::new().tag(Tag::Repeat)) {
+                let template = repeat.literal("template-name").unwrap();
+                if !self.templates.contains_key(template) {
+                    return Err(invalid(repeat.position, format!("unknown template `{}`", template)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn view(&self, name: &str) -> Option<&Element> {
+        self.views.get(name)
+    }
+
+    pub fn template(&self, name: &str) -> Option<&Element> {
+        self.templates.get(name)
+    }
+
+    pub fn view_names(&self) -> Vec<&str> {
+        self.views.keys().map(|name| &name[..]).collect()
+    }
+
+    // Copy of `element` where the inclusions of templates are replaced by
+    // a group with the content of the template. `included` are the templates
+    // being included, to detect cycles.
+    fn include_templates(&self, element: &Element, included: &mut Vec<String>)
+        -> Result<Element, UilError>
+    {
+        let mut children = Vec::with_capacity(element.children.len());
+        for child in element.children.iter() {
+            let child = match *child {
+                Node::Element(ref child) if child.tag == Tag::Template => {
+                    let path = child.literal("path").unwrap();
+                    let template = try!(self.templates.get(path).ok_or_else(|| {
+                        invalid(child.position, format!("unknown template `{}`", path))
+                    }));
+                    if included.iter().any(|name| name == path) {
+                        return Err(invalid(child.position, format!("template `{}` includes itself", path)));
+                    }
+                    included.push(path.to_string());
+                    let content = try!(self.include_templates(template, included));
+                    included.pop();
+
+                    let mut classes = child.classes.clone();
+                    classes.extend(content.classes.into_iter());
+                    Node::Element(Element {
+                        tag: Tag::Group,
+                        classes: classes,
+                        attributes: BTreeMap::new(),
+                        children: content.children,
+                        position: child.position,
+                    })
+                }
+                Node::Element(ref child) => Node::Element(try!(self.include_templates(child, included))),
+                Node::Text(ref text) => Node::Text(text.clone()),
+            };
+            children.push(child);
+        }
+        Ok(Element {
+            tag: element.tag,
+            classes: element.classes.clone(),
+            attributes: element.attributes.clone(),
+            children: children,
+            position: element.position,
+        })
+    }
+}
+
+impl FromStr for Library {
+    type Err = UilError;
+
+    /// Parse and resolve a single markup source.
+    fn from_str(s: &str) -> Result<Library, UilError> {
+        let mut library = Library::new();
+        try!(library.add(s));
+        try!(library.resolve());
+        Ok(library)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Context {
+    Root,
+    Content,
+}
+
+// Build and validate an element and its descendants.
+fn build(xml: &XmlElement, context: Context) -> Result<Element, UilError> {
+    let position = xml.position;
+    let tag = try!(Tag::from_name(&xml.name).ok_or_else(|| {
+        invalid(position, format!("unknown tag `<{}>`", xml.name))
+    }));
+
+    match (context, tag) {
+        (Context::Root, Tag::View) | (Context::Root, Tag::Template) => (),
+        (Context::Root, _) => {
+            return Err(invalid(position, format!("`<{}>` must be in a view or a template", tag.name())));
+        }
+        (Context::Content, Tag::View) => {
+            return Err(invalid(position, "`<view>` can't be in another view or template"));
+        }
+        (Context::Content, _) => (),
+    }
+
+    let mut classes = Vec::new();
+    let mut attributes = BTreeMap::new();
+    for &(ref name, ref value) in xml.attributes.iter() {
+        if name != "class" && !tag.attributes().contains(&&name[..]) {
+            return Err(invalid(position, format!("unknown attribute `{}` on `<{}>`", name, tag.name())));
+        }
+        let text: Text = try!(value.parse().map_err(|e| invalid(position, e)));
+        if STATIC_ATTRIBUTES.contains(&&name[..]) && text.literal().is_none() {
+            return Err(invalid(position, format!("`{}` can't contain a data binding", name)));
+        }
+        if name == "class" {
+            for class in value.split_whitespace() {
+                if !class.chars().all(|c| c.is_alphanumeric() || c == '-') {
+                    return Err(invalid(position, format!("invalid class `{}`", class)));
+                }
+                classes.push(class.to_string());
+            }
+        } else {
+            attributes.insert(name.clone(), text);
+        }
+    }
+
+    let element = Element {
+        tag: tag,
+        classes: classes,
+        attributes: attributes,
+        children: try!(build_children(xml, tag)),
+        position: position,
+    };
+    try!(check_attributes(&element, context));
+    Ok(element)
+}
+
+fn build_children(xml: &XmlElement, tag: Tag) -> Result<Vec<Node>, UilError> {
+    let mut children = Vec::new();
+    for child in xml.children.iter() {
+        let node = match *child {
+            XmlNode::Element(ref child) => Node::Element(try!(build(child, Context::Content))),
+            XmlNode::Text(ref text, position) => {
+                // Like in HTML, whitespace is collapsed.
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if text.is_empty() {
+                    continue;
+                }
+                Node::Text(try!(text.parse().map_err(|e| invalid(position, e))))
+            }
+        };
+        children.push(node);
+    }
+    if !children.is_empty() && !tag.has_content() {
+        return Err(invalid(xml.position, format!("`<{}>` can't have content", tag.name())));
+    }
+    Ok(children)
+}
+
+fn check_attributes(element: &Element, context: Context) -> Result<(), UilError> {
+    let position = element.position;
+    let required: &[&str] = match (element.tag, context) {
+        (Tag::View, _) => &["name"],
+        (Tag::Template, Context::Root) => &["name"],
+        (Tag::Template, Context::Content) => &["path"],
+        (Tag::Repeat, _) => &["iter", "template-name"],
+        _ => &[],
+    };
+    for name in required.iter() {
+        if element.attribute(name).is_none() {
+            return Err(invalid(position, format!("`<{}>` needs a `{}`", element.tag.name(), name)));
+        }
+    }
+
+    match (element.tag, context) {
+        (Tag::Template, Context::Root) if element.attribute("path").is_some() => {
+            return Err(invalid(position, "the definition of a template can't have a `path`"));
+        }
+        (Tag::Template, Context::Content) => {
+            if element.attribute("name").is_some() {
+                return Err(invalid(position, "templates must be defined outside of views and templates"));
+            }
+            if !element.children.is_empty() {
+                return Err(invalid(position, "the inclusion of a template can't have content"));
+            }
+        }
+        (Tag::Repeat, _) => {
+            let iter = element.attribute("iter").unwrap();
+            if iter.parts().len() != 1 || iter.bindings().len() != 1 {
+                return Err(invalid(position, "`iter` must be a single data binding"));
+            }
+        }
+        _ => (),
+    }
+
+    if let Some(action) = element.literal("action") {
+        if Action::from_name(action).is_none() {
+            return Err(invalid(position, format!("unknown action `{}`", action)));
+        }
+    }
+    if let Some(key) = element.literal("key") {
+        if key_from_name(key).is_none() {
+            return Err(invalid(position, format!("unknown key `{}`", key)));
+        }
+    }
+    if element.tag == Tag::ProgressBar {
+        if let Some(value) = element.literal("value") {
+            if value.parse::<f32>().is_err() {
+                return Err(invalid(position, format!("invalid progress `{}`", value)));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invalid<S: Into<String>>(position: Position, reason: S) -> UilError {
+    UilError::Invalid(format!("{}: {}", position, reason.into()))
+}
+
+impl UilError {
+
+    /// Tell in which file the error is.
+    pub fn in_file(self, path: &Path) -> UilError {
+        match self {
+            UilError::Syntax(e) => UilError::Syntax(format!("{}: {}", path.display(), e)),
+            UilError::Invalid(e) => UilError::Invalid(format!("{}: {}", path.display(), e)),
+            e => e,
+        }
+    }
+}
+
+impl From<SyntaxError> for UilError {
+    fn from(e: SyntaxError) -> UilError {
+        UilError::Syntax(e.to_string())
+    }
+}
+
+impl From<io::Error> for UilError {
+    fn from(e: io::Error) -> UilError {
+        UilError::Io(e)
+    }
+}
+
+impl fmt::Display for UilError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UilError::Io(ref e) => write!(f, "{}", e),
+            UilError::Syntax(ref e) => write!(f, "{}", e),
+            UilError::Invalid(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for UilError {
+    fn description(&self) -> &str {
+        match *self {
+            UilError::Io(ref e) => e.description(),
+            UilError::Syntax(_) => "invalid xml",
+            UilError::Invalid(_) => "invalid markup",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use ui::query::Query;
+    use super::{Binding, Library, Node, Tag, Text, TextPart, UilError};
+
+    // The examples of `docs/uil.md`.
+    const MARKUP: &'static str = r#"
+        <view name="main">
+            <h1>Slayers Online</h1>
+            Salut {{player.name}}! Bienvenue sur {{server.name}}.
+            <group class="menu">
+                <button goto-view="connect" action="play">Play</button>
+                <button goto-view="options">Options</button>
+                <button action="quit" key="Q">Quit</button>
+            </group>
+            <template path="amis" class="side"/>
+        </view>
+        <view name="connect"/>
+        <view name="options"/>
+        <view name="amis">
+            <repeat iter="{{player.friends}}" template-name="ami"/>
+        </view>
+        <template name="ami">
+            <group>{{name}} est {{status}}</group>
+        </template>
+        <template name="amis" class="friends">
+            <h1>Amis</h1>
+            <repeat iter="{{player.friends}}" template-name="ami"/>
+        </template>
+    "#;
+
+    fn binding(path: &str) -> Binding {
+        Binding { path: path.split('.').map(|s| s.to_string()).collect() }
+    }
+
+    #[test]
+    fn parse_views() {
+        let library: Library = MARKUP.parse().unwrap();
+        assert_eq!(library.view_names(), vec!["amis", "connect", "main", "options"]);
+
+        let main = library.view("main").unwrap();
+        assert_eq!(main.tag(), Tag::View);
+        assert_eq!(main.position().line, 2);
+        assert_eq!(main.children().len(), 4);
+
+        match main.children()[1] {
+            Node::Text(ref text) => {
+                assert_eq!(text.parts()[0], TextPart::Literal("Salut ".to_string()));
+                assert_eq!(text.bindings(), vec![&binding("player.name"), &binding("server.name")]);
+            }
+            _ => panic!("expected text"),
+        }
+
+        let buttons = main.select(&Query::new().tag(Tag::Button));
+        assert_eq!(buttons.len(), 3);
+        assert_eq!(buttons[0].literal("goto-view"), Some("connect"));
+        assert_eq!(buttons[2].literal("key"), Some("Q"));
+    }
+
+    #[test]
+    fn include_templates() {
+        let library: Library = MARKUP.parse().unwrap();
+        let main = library.view("main").unwrap();
+
+        // The inclusion is replaced by a group with the content of the template.
+        let included = main.select_first(&Query::new().class("friends")).unwrap();
+        assert_eq!(included.tag(), Tag::Group);
+        assert_eq!(included.classes(), &["side".to_string(), "friends".to_string()]);
+        assert_eq!(included.children().len(), 2);
+        assert!(main.select(&Query::new().tag(Tag::Template)).is_empty());
+
+        assert_eq!(main.bindings(), vec![&binding("player.name"), &binding("server.name"),
+                                         &binding("player.friends")]);
+    }
+
+    #[test]
+    fn text_and_bindings() {
+        let text: Text = "{{ a.b }} and {{c}}".parse().unwrap();
+        assert_eq!(text.literal(), None);
+        let value = text.evaluate(|b| if b.path().join(".") == "a.b" {
+            Some("x".to_string())
+        } else {
+            None
+        });
+        assert_eq!(value, "x and ");
+        assert_eq!(format!("{}", binding("a.b")), "{{a.b}}");
+
+        let text: Text = "no binding }}".parse().unwrap();
+        assert_eq!(text.literal(), Some("no binding }}"));
+        assert!("{{a.}}".parse::<Text>().is_err());
+        assert!("{{a".parse::<Text>().is_err());
+    }
+
+    fn error(markup: &str) -> String {
+        match markup.parse::<Library>() {
+            Err(UilError::Invalid(e)) | Err(UilError::Syntax(e)) => e,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("`{}` should be rejected", markup),
+        }
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(error("<view name=\"main\"><button>Play</buttton></view>"),
+                   "line 1, column 31: expected `</button>`, found `</buttton>`");
+    }
+
+    #[test]
+    fn invalid_markup() {
+        assert_eq!(error("<view name='a'/>\n<view name='a'/>"),
+                   "line 2, column 1: view `a` is defined twice");
+        assert_eq!(error("<view name='a'><blink/></view>"),
+                   "line 1, column 16: unknown tag `<blink>`");
+        assert_eq!(error("<group/>"),
+                   "line 1, column 1: `<group>` must be in a view or a template");
+        assert_eq!(error("<view name='a'><view name='b'/></view>"),
+                   "line 1, column 16: `<view>` can't be in another view or template");
+        assert_eq!(error("<view/>"),
+                   "line 1, column 1: `<view>` needs a `name`");
+        assert_eq!(error("<view name='a'><button href='b'/></view>"),
+                   "line 1, column 16: unknown attribute `href` on `<button>`");
+        assert_eq!(error("<view name='a' class='{{b}}'/>"),
+                   "line 1, column 1: `class` can't contain a data binding");
+        assert_eq!(error("<view name='a' class='b_c'/>"),
+                   "line 1, column 1: invalid class `b_c`");
+        assert_eq!(error("<view name='a'><button action='fly'/></view>"),
+                   "line 1, column 16: unknown action `fly`");
+        assert_eq!(error("<view name='a'><button key='Hyper'/></view>"),
+                   "line 1, column 16: unknown key `Hyper`");
+        assert_eq!(error("<view name='a'><line-input>text</line-input></view>"),
+                   "line 1, column 16: `<line-input>` can't have content");
+        assert_eq!(error("<view name='a'><repeat iter='a' template-name='t'/></view>"),
+                   "line 1, column 16: `iter` must be a single data binding");
+        assert_eq!(error("<view name='a'><template name='t'/></view>"),
+                   "line 1, column 16: `<template>` needs a `path`");
+    }
+
+    #[test]
+    fn invalid_references() {
+        assert_eq!(error("<view name='a'><button goto-view='b'/></view>"),
+                   "line 1, column 16: unknown view `b`");
+        assert_eq!(error("<view name='a'><template path='t'/></view>"),
+                   "line 1, column 16: unknown template `t`");
+        assert_eq!(error("<view name='a'><repeat iter='{{i}}' template-name='t'/></view>"),
+                   "line 1, column 16: unknown template `t`");
+        assert_eq!(error("<template name='t'><group><template path='u'/></group></template>\n\
+                          <template name='u'><template path='t'/></template>"),
+                   "line 2, column 20: template `t` includes itself");
+    }
+}
@@ -0,0 +1,74 @@
+//! The UIL, the language describing the menus of the game,
+//! see `docs/uil.md`.
+//!
+//! The markup is parsed into a DOM of views and templates which is
+//! validated when loaded. The code attaches its models to the
+//! elements found with a `Query`. Style sheets, which refer to the
+//! resources of the libs, give the style of the elements, and the
+//! layout places them on screen.
+
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub use self::markup::{
+    Action,
+    Binding,
+    Element,
+    Library,
+    Node,
+    Tag,
+    Text,
+    TextPart,
+    UilError,
+    DEFAULT_MARKUP_DIR,
+    MAIN_VIEW,
+};
+pub use self::query::Query;
+pub use self::xml::Position;
+pub use self::libs::{Libs, LibValue, DEFAULT_LIBS_DIR};
+pub use self::style::{
+    Align,
+    Color,
+    Direction,
+    Justify,
+    Style,
+    StyleSheet,
+    DEFAULT_STYLE_DIR,
+};
+pub use self::layout::{layout, Content, LayoutBox, Measure, Rect};
+pub use self::document::{Document, UiEvent};
+pub use self::focus::{is_focusable, Focus};
+
+mod xml;
+mod tokens;
+mod markup;
+mod query;
+mod libs;
+mod style;
+mod layout;
+mod focus;
+mod document;
+
+// The files of `dir` with `extension` and their content,
+// sorted so that errors don't depend on the order of the directory.
+fn read_sources(dir: &Path, extension: &str) -> Result<Vec<(PathBuf, String)>, UilError> {
+    let mut paths = Vec::new();
+    for entry in try!(fs::read_dir(dir)) {
+        let path = try!(entry).path();
+        if path.extension().map_or(false, |e| e == extension) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut sources = Vec::new();
+    for path in paths {
+        let mut source = String::new();
+        let mut file = try!(File::open(&path));
+        try!(file.read_to_string(&mut source));
+        sources.push((path, source));
+    }
+    Ok(sources)
+}
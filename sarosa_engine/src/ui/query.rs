@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+use ui::markup::{Element, Tag, UilError};
+
+/// Selects the elements of the markup, for instance
+/// to attach models to them from the code.
+///
+/// A query can be built step by step or parsed from a selector
+/// such as `button.menu[action=quit]`: a tag, classes and
+/// attributes, all optional. An attribute without a value
+/// only needs to be present.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    tag: Option<Tag>,
+    classes: Vec<String>,
+    attributes: Vec<(String, Option<String>)>,
+}
+
+impl Query {
+
+    /// A query matching all the elements.
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    pub fn tag(mut self, tag: Tag) -> Query {
+        self.tag = Some(tag);
+        self
+    }
+
+    pub fn class(mut self, class: &str) -> Query {
+        self.classes.push(class.to_string());
+        self
+    }
+
+    pub fn has_attribute(mut self, name: &str) -> Query {
+        self.attributes.push((name.to_string(), None));
+        self
+    }
+
+    /// Only matches literal values, not data bindings.
+    pub fn attribute(mut self, name: &str, value: &str) -> Query {
+        self.attributes.push((name.to_string(), Some(value.to_string())));
+        self
+    }
+
+    pub fn matches(&self, element: &Element) -> bool {
+        self.tag.map_or(true, |tag| tag == element.tag())
+            && self.classes.iter().all(|class| element.has_class(class))
+            && self.attributes.iter().all(|&(ref name, ref value)| match *value {
+                Some(ref value) => element.literal(name) == Some(&value[..]),
+                None => element.attribute(name).is_some(),
+            })
+    }
+}
+
+impl FromStr for Query {
+    type Err = UilError;
+
+    fn from_str(s: &str) -> Result<Query, UilError> {
+        let invalid = |reason: &str| UilError::Invalid(format!("invalid query `{}`: {}", s, reason));
+        let is_name = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+
+        let tag_end = s.find(|c| !is_name(c)).unwrap_or(s.len());
+        let mut query = Query::new();
+        if tag_end > 0 {
+            let tag = try!(Tag::from_name(&s[..tag_end]).ok_or_else(|| invalid("unknown tag")));
+            query = query.tag(tag);
+        }
+
+        let mut rest = &s[tag_end..];
+        while !rest.is_empty() {
+            if rest.starts_with('.') {
+                let end = rest[1..].find(|c| !is_name(c)).map_or(rest.len(), |end| end + 1);
+                if end == 1 {
+                    return Err(invalid("missing class name"));
+                }
+                query = query.class(&rest[1..end]);
+                rest = &rest[end..];
+            } else if rest.starts_with('[') {
+                let end = try!(rest.find(']').ok_or_else(|| invalid("missing `]`")));
+                let mut attribute = rest[1..end].splitn(2, '=');
+                let name = attribute.next().unwrap_or("").trim();
+                if name.is_empty() {
+                    return Err(invalid("missing attribute name"));
+                }
+                query = match attribute.next() {
+                    Some(value) => query.attribute(name, value.trim()),
+                    None => query.has_attribute(name),
+                };
+                rest = &rest[end + 1..];
+            } else {
+                return Err(invalid("expected `.` or `[`"));
+            }
+        }
+        Ok(query)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use ui::markup::{Library, Tag};
+    use super::Query;
+
+    const MARKUP: &'static str = r#"
+        <view name="main">
+            <group class="menu big">
+                <button class="first" action="play">Play</button>
+                <button action="quit">Quit</button>
+                <button action="{{custom.action}}">?</button>
+            </group>
+            <line-input value="{{chat.msg}}"/>
+        </view>
+    "#;
+
+    fn select(selector: &str) -> usize {
+        let library: Library = MARKUP.parse().unwrap();
+        let query: Query = selector.parse().unwrap();
+        library.view("main").unwrap().select(&query).len()
+    }
+
+    #[test]
+    fn parse_queries() {
+        assert_eq!("button.a.b[action=quit][key]".parse::<Query>().unwrap(),
+                   Query::new().tag(Tag::Button).class("a").class("b")
+                       .attribute("action", "quit").has_attribute("key"));
+        assert_eq!("".parse::<Query>().unwrap(), Query::new());
+        assert!("blink".parse::<Query>().is_err());
+        assert!("button.".parse::<Query>().is_err());
+        assert!("button[action".parse::<Query>().is_err());
+        assert!("button > group".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn select_elements() {
+        assert_eq!(select(""), 6);
+        assert_eq!(select("button"), 3);
+        assert_eq!(select(".menu.big"), 1);
+        assert_eq!(select(".menu.small"), 0);
+        assert_eq!(select("button.first"), 1);
+        assert_eq!(select("[action]"), 3);
+        assert_eq!(select("[action=quit]"), 1);
+        // Data bindings are only known once the models are attached.
+        assert_eq!(select("[action={{custom.action}}]"), 0);
+        assert_eq!(select("line-input[value]"), 1);
+    }
+}
@@ -0,0 +1,453 @@
+use std::path::Path;
+
+use ui::libs::{Libs, LibValue};
+use ui::markup::UilError;
+use ui::tokens::{Token, Tokens};
+use ui::xml::{Position, SyntaxError};
+use ui::read_sources;
+
+/// Directory of the style sheets of the game.
+pub const DEFAULT_STYLE_DIR: &'static str = "./assets/ui/style";
+
+const STYLE_EXTENSION: &'static str = "style";
+
+/// Red, green, blue and alpha, between 0 and 1.
+pub type Color = [f32; 4];
+
+const WHITE: Color = [1.0, 1.0, 1.0, 1.0];
+
+/// Parse the hexadecimal digits of `#RGB`, `#RRGGBB` or `#RRGGBBAA`.
+pub fn parse_color(hex: &str) -> Option<Color> {
+    let digits: Vec<u32> = hex.chars().filter_map(|c| c.to_digit(16)).collect();
+    if digits.len() != hex.len() {
+        return None;
+    }
+    let channels: Vec<u32> = match digits.len() {
+        3 => digits.iter().map(|&d| d * 17).collect(),
+        6 | 8 => digits.chunks(2).map(|c| c[0] * 16 + c[1]).collect(),
+        _ => return None,
+    };
+    let channel = |i: usize| channels.get(i).map_or(1.0, |&c| c as f32 / 255.0);
+    Some([channel(0), channel(1), channel(2), channel(3)])
+}
+
+/// How the children of an element are placed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// From the top to the bottom.
+    Column,
+    /// From the left to the right.
+    Row,
+}
+
+/// Placement of the children across the direction of the layout.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    /// As large as their parent, unless they have a size.
+    Stretch,
+}
+
+/// Placement of the children along the direction of the layout,
+/// when they don't fill their parent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Justify {
+    Start,
+    Center,
+    End,
+    /// The free space is shared between the children.
+    SpaceBetween,
+}
+
+/// Style of an element after the cascade. Lengths are in pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    /// Size of the element including its padding,
+    /// it fits its content by default.
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    pub margin: f32,
+    pub padding: f32,
+    /// Space between the children.
+    pub gap: f32,
+    pub direction: Direction,
+    pub align: Align,
+    pub justify: Justify,
+    /// Share of the free space of the parent the element takes.
+    pub grow: f32,
+    pub background_color: Option<Color>,
+    // The following properties are inherited from the parent.
+    pub color: Color,
+    /// Path of the font, the font of the game by default.
+    pub font: Option<String>,
+    /// Size of the text, the size of the font by default.
+    pub font_size: Option<f32>,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            width: None,
+            height: None,
+            margin: 0.0,
+            padding: 0.0,
+            gap: 0.0,
+            direction: Direction::Column,
+            align: Align::Stretch,
+            justify: Justify::Start,
+            grow: 0.0,
+            background_color: None,
+            color: WHITE,
+            font: None,
+            font_size: None,
+        }
+    }
+}
+
+impl Style {
+
+    /// Style of a child before its own rules are applied.
+    pub fn inherit(&self) -> Style {
+        Style {
+            color: self.color,
+            font: self.font.clone(),
+            font_size: self.font_size,
+            .. Style::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Property {
+    Width(f32),
+    Height(f32),
+    Margin(f32),
+    Padding(f32),
+    Gap(f32),
+    Direction(Direction),
+    Align(Align),
+    Justify(Justify),
+    Grow(f32),
+    BackgroundColor(Color),
+    Color(Color),
+    Font(String, Option<f32>),
+    FontSize(f32),
+}
+
+impl Property {
+
+    fn apply(&self, style: &mut Style) {
+        match *self {
+            Property::Width(w) => style.width = Some(w),
+            Property::Height(h) => style.height = Some(h),
+            Property::Margin(m) => style.margin = m,
+            Property::Padding(p) => style.padding = p,
+            Property::Gap(g) => style.gap = g,
+            Property::Direction(d) => style.direction = d,
+            Property::Align(a) => style.align = a,
+            Property::Justify(j) => style.justify = j,
+            Property::Grow(g) => style.grow = g,
+            Property::BackgroundColor(c) => style.background_color = Some(c),
+            Property::Color(c) => style.color = c,
+            Property::Font(ref path, size) => {
+                style.font = Some(path.clone());
+                if size.is_some() {
+                    style.font_size = size;
+                }
+            }
+            Property::FontSize(s) => style.font_size = Some(s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    class: String,
+    properties: Vec<Property>,
+}
+
+/// The rules of the style sheets, in the order they were added.
+///
+/// A rule applies to the elements with the class of its selector.
+/// When several rules set the same property, the last one wins.
+///
+/// ```text
+/// .menu-button {
+///     width: 200px;
+///     font-path: fonts.default;
+///     background-color: #FFFFFF;
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StyleSheet {
+    rules: Vec<Rule>,
+}
+
+impl StyleSheet {
+
+    pub fn new() -> StyleSheet {
+        StyleSheet::default()
+    }
+
+    /// Load all the style sheets of `dir`, their references are resolved with `libs`.
+    pub fn load<P: AsRef<Path>>(dir: P, libs: &Libs) -> Result<StyleSheet, UilError> {
+        let mut sheet = StyleSheet::new();
+        for (path, source) in try!(read_sources(dir.as_ref(), STYLE_EXTENSION)) {
+            try!(sheet.add(&source, libs).map_err(|e| e.in_file(&path)));
+        }
+        Ok(sheet)
+    }
+
+    pub fn parse(source: &str, libs: &Libs) -> Result<StyleSheet, UilError> {
+        let mut sheet = StyleSheet::new();
+        try!(sheet.add(source, libs));
+        Ok(sheet)
+    }
+
+    pub fn add(&mut self, source: &str, libs: &Libs) -> Result<(), UilError> {
+        let mut tokens = try!(Tokens::new(source));
+        while !tokens.is_empty() {
+            try!(tokens.expect('.'));
+            let class = try!(tokens.ident());
+            try!(tokens.expect('{'));
+            let mut properties = Vec::new();
+            while !tokens.eat('}') {
+                properties.push(try!(parse_property(&mut tokens, libs)));
+                if !tokens.eat(';') && tokens.peek() != Some(&Token::Punct('}')) {
+                    return Err(tokens.unexpected("`;` or `}`").into());
+                }
+            }
+            self.rules.push(Rule {
+                class: class,
+                properties: properties,
+            });
+        }
+        Ok(())
+    }
+
+    /// Style of an element with `classes` whose parent has the style `parent`.
+    pub fn compute(&self, classes: &[String], parent: &Style) -> Style {
+        let mut style = parent.inherit();
+        for rule in self.rules.iter().filter(|r| classes.contains(&r.class)) {
+            for property in rule.properties.iter() {
+                property.apply(&mut style);
+            }
+        }
+        style
+    }
+}
+
+// A value as written in the style sheet.
+enum Value {
+    Number(f32, Option<String>),
+    Color(Color),
+    Name(String),
+    Path(String),
+}
+
+fn parse_property(tokens: &mut Tokens, libs: &Libs) -> Result<Property, UilError> {
+    let position = tokens.position();
+    let name = try!(tokens.ident());
+    try!(tokens.expect(':'));
+
+    let value_position = tokens.position();
+    let value = match tokens.peek().cloned() {
+        Some(Token::Number(n, unit)) => {
+            tokens.next();
+            Value::Number(n, unit)
+        }
+        Some(Token::Color(hex)) => {
+            tokens.next();
+            Value::Color(try!(parse_color(&hex).ok_or_else(|| {
+                invalid(value_position, format!("invalid color `#{}`", hex))
+            })))
+        }
+        Some(Token::Ident(_)) => {
+            let path = try!(tokens.path());
+            if path.contains('.') { Value::Path(path) } else { Value::Name(path) }
+        }
+        _ => return Err(tokens.unexpected("a value").into()),
+    };
+
+    // References to the libs are replaced by what they refer to.
+    let value = match value {
+        Value::Path(path) => match libs.get(&path) {
+            Some(&LibValue::Number(n)) => Value::Number(n, Some("px".to_string())),
+            Some(&LibValue::Color(c)) => Value::Color(c),
+            Some(&LibValue::Font { ref path, size }) => {
+                return if name == "font-path" {
+                    Ok(Property::Font(path.clone(), size))
+                } else {
+                    Err(invalid(value_position, format!("`{}` can't be a font", name)))
+                };
+            }
+            Some(_) => return Err(invalid(value_position, format!("`{}` can't be used here", path))),
+            None => return Err(invalid(value_position, format!("unknown resource `{}`", path))),
+        },
+        value => value,
+    };
+
+    let length = |value: &Value| match *value {
+        Value::Number(n, Some(ref unit)) if unit == "px" => Ok(n),
+        // Like in CSS, zero doesn't need a unit.
+        Value::Number(n, None) if n == 0.0 => Ok(n),
+        _ => Err(invalid(value_position, format!("`{}` must be a length in pixels", name))),
+    };
+    let color = |value: &Value| match *value {
+        Value::Color(c) => Ok(c),
+        _ => Err(invalid(value_position, format!("`{}` must be a color", name))),
+    };
+    let keyword = |value: &Value| match *value {
+        Value::Name(ref keyword) => Ok(keyword.clone()),
+        _ => Err(invalid(value_position, format!("`{}` must be a keyword", name))),
+    };
+    let unknown_keyword = |keyword: String| {
+        invalid(value_position, format!("unknown value `{}` for `{}`", keyword, name))
+    };
+
+    let property = match &name[..] {
+        "width" => Property::Width(try!(length(&value))),
+        "height" => Property::Height(try!(length(&value))),
+        "margin" => Property::Margin(try!(length(&value))),
+        "padding" => Property::Padding(try!(length(&value))),
+        "gap" => Property::Gap(try!(length(&value))),
+        "font-size" => Property::FontSize(try!(length(&value))),
+        "background-color" => Property::BackgroundColor(try!(color(&value))),
+        "color" => Property::Color(try!(color(&value))),
+        "grow" => match value {
+            Value::Number(n, None) if n >= 0.0 => Property::Grow(n),
+            _ => return Err(invalid(value_position, "`grow` must be a positive number")),
+        },
+        "layout" => match &try!(keyword(&value))[..] {
+            "column" => Property::Direction(Direction::Column),
+            "row" => Property::Direction(Direction::Row),
+            other => return Err(unknown_keyword(other.to_string())),
+        },
+        "align" => match &try!(keyword(&value))[..] {
+            "start" => Property::Align(Align::Start),
+            "center" => Property::Align(Align::Center),
+            "end" => Property::Align(Align::End),
+            "stretch" => Property::Align(Align::Stretch),
+            other => return Err(unknown_keyword(other.to_string())),
+        },
+        "justify" => match &try!(keyword(&value))[..] {
+            "start" => Property::Justify(Justify::Start),
+            "center" => Property::Justify(Justify::Center),
+            "end" => Property::Justify(Justify::End),
+            "space-between" => Property::Justify(Justify::SpaceBetween),
+            other => return Err(unknown_keyword(other.to_string())),
+        },
+        "font-path" => return Err(invalid(value_position, "`font-path` must refer to a font of the libs")),
+        _ => return Err(invalid(position, format!("unknown property `{}`", name))),
+    };
+    Ok(property)
+}
+
+fn invalid<S: Into<String>>(position: Position, reason: S) -> UilError {
+    UilError::Invalid(SyntaxError { position: position, message: reason.into() }.to_string())
+}
+
+#[cfg(test)]
+mod test {
+
+    use ui::libs::Libs;
+    use ui::UilError;
+    use super::{parse_color, Align, Direction, Justify, Style, StyleSheet};
+
+    const LIBS: &'static str = r#"
+        fonts {
+            title: Font { path: "title.fnt", size: 24 },
+        }
+        cst {
+            btn-width: 200px,
+            highlight: #FFCC00,
+        }
+    "#;
+
+    fn classes(classes: &[&str]) -> Vec<String> {
+        classes.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("FFF"), Some([1.0, 1.0, 1.0, 1.0]));
+        assert_eq!(parse_color("ff000080"), Some([1.0, 0.0, 0.0, 128.0 / 255.0]));
+        assert_eq!(parse_color("FFFF"), None);
+        assert_eq!(parse_color("GGGGGG"), None);
+    }
+
+    #[test]
+    fn cascade() {
+        let libs: Libs = LIBS.parse().unwrap();
+        let sheet = StyleSheet::parse(r#"
+            .menu {
+                layout: row;
+                align: center;
+                justify: space-between;
+                gap: 4px;
+                font-path: fonts.title;
+                color: cst.highlight;
+            }
+            .button {
+                width: cst.btn-width;
+                padding: 2px;
+                grow: 1
+            }
+            .small {
+                width: 100px;
+                font-size: 10px;
+            }
+        "#, &libs).unwrap();
+
+        let menu = sheet.compute(&classes(&["menu"]), &Style::default());
+        assert_eq!(menu.direction, Direction::Row);
+        assert_eq!(menu.align, Align::Center);
+        assert_eq!(menu.justify, Justify::SpaceBetween);
+        assert_eq!(menu.gap, 4.0);
+        assert_eq!(menu.font, Some("title.fnt".to_string()));
+        assert_eq!(menu.font_size, Some(24.0));
+
+        // The last rule wins, whatever the order of the classes.
+        let button = sheet.compute(&classes(&["small", "button"]), &menu);
+        assert_eq!(button.width, Some(100.0));
+        assert_eq!(button.padding, 2.0);
+        assert_eq!(button.grow, 1.0);
+        assert_eq!(button.font_size, Some(10.0));
+        // Inherited from the menu, unlike the layout.
+        assert_eq!(button.color, [1.0, 0.8, 0.0, 1.0]);
+        assert_eq!(button.font, Some("title.fnt".to_string()));
+        assert_eq!(button.direction, Direction::Column);
+        assert_eq!(button.gap, 0.0);
+
+        let other = sheet.compute(&classes(&["other"]), &Style::default());
+        assert_eq!(other, Style::default());
+    }
+
+    fn error(source: &str) -> String {
+        let libs: Libs = LIBS.parse().unwrap();
+        match StyleSheet::parse(source, &libs) {
+            Err(UilError::Syntax(e)) | Err(UilError::Invalid(e)) => e,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("`{}` should be rejected", source),
+        }
+    }
+
+    #[test]
+    fn invalid_styles() {
+        assert_eq!(error("menu {}"), "line 1, column 1: expected `.`, found `menu`");
+        assert_eq!(error(".a { width: 2px height: 3px }"),
+                   "line 1, column 17: expected `;` or `}`, found `height`");
+        assert_eq!(error(".a { blink: 1 }"), "line 1, column 6: unknown property `blink`");
+        assert_eq!(error(".a { width: 20 }"), "line 1, column 13: `width` must be a length in pixels");
+        assert_eq!(error(".a { color: 20px }"), "line 1, column 13: `color` must be a color");
+        assert_eq!(error(".a { layout: diagonal }"),
+                   "line 1, column 14: unknown value `diagonal` for `layout`");
+        assert_eq!(error(".a { width: cst.missing }"), "line 1, column 13: unknown resource `cst.missing`");
+        assert_eq!(error(".a { width: fonts.title }"), "line 1, column 13: `width` can't be a font");
+        assert_eq!(error(".a { font-path: cst.btn-width }"),
+                   "line 1, column 17: `font-path` must refer to a font of the libs");
+        assert_eq!(error(".a { grow: -1 }"), "line 1, column 12: `grow` must be a positive number");
+    }
+}
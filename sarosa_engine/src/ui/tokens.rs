@@ -0,0 +1,303 @@
+//! Tokens of the style sheets and libs, which share their syntax
+//! for names, numbers, colors, strings and comments.
+
+use ui::xml::{Position, SyntaxError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    /// Letters, digits, `-` and `_`, not starting with a digit.
+    Ident(String),
+    /// A number and its unit, as in `20px`.
+    Number(f32, Option<String>),
+    /// The hexadecimal digits of `#FFFFFF`.
+    Color(String),
+    /// A string between double quotes.
+    Str(String),
+    Punct(char),
+}
+
+impl Token {
+
+    pub fn describe(&self) -> String {
+        match *self {
+            Token::Ident(ref name) => format!("`{}`", name),
+            Token::Number(value, Some(ref unit)) => format!("`{}{}`", value, unit),
+            Token::Number(value, None) => format!("`{}`", value),
+            Token::Color(ref hex) => format!("`#{}`", hex),
+            Token::Str(ref s) => format!("\"{}\"", s),
+            Token::Punct(c) => format!("`{}`", c),
+        }
+    }
+}
+
+/// A cursor on the tokens of a source.
+pub struct Tokens {
+    tokens: Vec<(Token, Position)>,
+    index: usize,
+    end: Position,
+}
+
+impl Tokens {
+
+    pub fn new(source: &str) -> Result<Tokens, SyntaxError> {
+        let mut lexer = Lexer {
+            source: source,
+            offset: 0,
+            position: Position { line: 1, column: 1 },
+        };
+        let mut tokens = Vec::new();
+        while let Some(token) = try!(lexer.token()) {
+            tokens.push(token);
+        }
+        Ok(Tokens {
+            tokens: tokens,
+            index: 0,
+            end: lexer.position,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index == self.tokens.len()
+    }
+
+    pub fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|&(ref token, _)| token)
+    }
+
+    /// Position of the next token, or of the end of the source.
+    pub fn position(&self) -> Position {
+        self.tokens.get(self.index).map_or(self.end, |&(_, position)| position)
+    }
+
+    pub fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        if token.is_some() {
+            self.index += 1;
+        }
+        token
+    }
+
+    pub fn error<S: Into<String>>(&self, message: S) -> SyntaxError {
+        SyntaxError {
+            position: self.position(),
+            message: message.into(),
+        }
+    }
+
+    /// Error about the next token not being what is `expected`.
+    pub fn unexpected(&self, expected: &str) -> SyntaxError {
+        match self.peek() {
+            Some(token) => self.error(format!("expected {}, found {}", expected, token.describe())),
+            None => self.error(format!("expected {}, found the end of the file", expected)),
+        }
+    }
+
+    /// Consume the next token if it is `c`.
+    pub fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn expect(&mut self, c: char) -> Result<(), SyntaxError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", c)))
+        }
+    }
+
+    pub fn ident(&mut self) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Some(&Token::Ident(_)) => (),
+            _ => return Err(self.unexpected("a name")),
+        }
+        match self.next() {
+            Some(Token::Ident(name)) => Ok(name),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Names separated by dots, such as `fonts.default`.
+    pub fn path(&mut self) -> Result<String, SyntaxError> {
+        let mut path = try!(self.ident());
+        while self.eat('.') {
+            path.push('.');
+            path.push_str(&try!(self.ident()));
+        }
+        Ok(path)
+    }
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    offset: usize,
+    position: Position,
+}
+
+impl<'a> Lexer<'a> {
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.offset..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.offset += c.len_utf8();
+            if c == '\n' {
+                self.position.line += 1;
+                self.position.column = 1;
+            } else {
+                self.position.column += 1;
+            }
+        }
+        c
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break;
+            }
+            s.push(c);
+            self.next();
+        }
+        s
+    }
+
+    fn error<S: Into<String>>(&self, position: Position, message: S) -> SyntaxError {
+        SyntaxError {
+            position: position,
+            message: message.into(),
+        }
+    }
+
+    // Whitespace and comments.
+    fn skip_blank(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            let position = self.position;
+            if self.peek().map_or(false, char::is_whitespace) {
+                self.next();
+            } else if self.rest().starts_with("//") {
+                self.take_while(|c| c != '\n');
+            } else if self.rest().starts_with("/*") {
+                match self.rest().find("*/") {
+                    Some(end) => {
+                        let comment = &self.rest()[..end + 2];
+                        for _ in comment.chars() {
+                            self.next();
+                        }
+                    }
+                    None => return Err(self.error(position, "unclosed comment")),
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<Option<(Token, Position)>, SyntaxError> {
+        try!(self.skip_blank());
+        let position = self.position;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let is_name = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+
+        let token = if c.is_alphabetic() || c == '_' {
+            Token::Ident(self.take_while(is_name))
+        } else if c.is_digit(10) || (c == '-' && self.rest()[1..].starts_with(|c: char| c.is_digit(10))) {
+            self.next();
+            let mut number = c.to_string();
+            number.push_str(&self.take_while(|c| c.is_digit(10) || c == '.'));
+            let value = try!(number.parse().map_err(|_| {
+                self.error(position, format!("invalid number `{}`", number))
+            }));
+            let unit = self.take_while(|c| c.is_alphabetic() || c == '%');
+            Token::Number(value, if unit.is_empty() { None } else { Some(unit) })
+        } else if c == '#' {
+            self.next();
+            Token::Color(self.take_while(|c| c.is_alphanumeric()))
+        } else if c == '"' {
+            self.next();
+            let mut s = String::new();
+            loop {
+                match self.next() {
+                    Some('"') => break,
+                    Some('\\') => match self.next() {
+                        Some(c) if c == '"' || c == '\\' => s.push(c),
+                        _ => return Err(self.error(position, "invalid escape in string")),
+                    },
+                    Some('\n') | None => return Err(self.error(position, "unclosed string")),
+                    Some(c) => s.push(c),
+                }
+            }
+            Token::Str(s)
+        } else if "{}()[]:;,.".contains(c) {
+            self.next();
+            Token::Punct(c)
+        } else {
+            return Err(self.error(position, format!("unexpected character `{}`", c)));
+        };
+        Ok(Some((token, position)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Token, Tokens};
+
+    fn tokens(source: &str) -> Vec<Token> {
+        let mut tokens = Tokens::new(source).unwrap();
+        let mut all = Vec::new();
+        while let Some(token) = tokens.next() {
+            all.push(token);
+        }
+        all
+    }
+
+    #[test]
+    fn tokenize() {
+        assert_eq!(tokens(".menu-button { // comment\n width: -20.5px; /* a\n b */ grow: 1 }"), vec![
+            Token::Punct('.'), Token::Ident("menu-button".to_string()), Token::Punct('{'),
+            Token::Ident("width".to_string()), Token::Punct(':'),
+            Token::Number(-20.5, Some("px".to_string())), Token::Punct(';'),
+            Token::Ident("grow".to_string()), Token::Punct(':'), Token::Number(1.0, None),
+            Token::Punct('}'),
+        ]);
+        assert_eq!(tokens("#FF00aa \"a \\\"b\\\"\" fonts.default"), vec![
+            Token::Color("FF00aa".to_string()), Token::Str("a \"b\"".to_string()),
+            Token::Ident("fonts".to_string()), Token::Punct('.'), Token::Ident("default".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn positions_and_errors() {
+        let mut tokens = Tokens::new("a\n  b").unwrap();
+        tokens.next();
+        assert_eq!((tokens.position().line, tokens.position().column), (2, 3));
+        assert_eq!(tokens.expect('{').unwrap_err().to_string(),
+                   "line 2, column 3: expected `{`, found `b`");
+        tokens.next();
+        assert_eq!(tokens.unexpected("a value").to_string(),
+                   "line 2, column 4: expected a value, found the end of the file");
+
+        assert_eq!(Tokens::new("a @").err().unwrap().to_string(),
+                   "line 1, column 3: unexpected character `@`");
+        assert_eq!(Tokens::new("/* a").err().unwrap().to_string(),
+                   "line 1, column 1: unclosed comment");
+        assert_eq!(Tokens::new("\"a").err().unwrap().to_string(),
+                   "line 1, column 1: unclosed string");
+    }
+}
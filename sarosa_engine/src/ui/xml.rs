@@ -0,0 +1,367 @@
+//! The subset of XML used by the UIL markup: elements, attributes,
+//! text, comments and entities. Doctypes and CDATA sections are
+//! not supported, processing instructions are ignored.
+
+use std::fmt;
+
+/// Where something starts in the source, both counted from 1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlElement {
+    pub name: String,
+    /// In the order of the source.
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum XmlNode {
+    Element(XmlElement),
+    /// Text with the entities replaced, whitespace is kept as is.
+    Text(String, Position),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub position: Position,
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+/// Parse a document. Unlike XML, several root elements are allowed.
+pub fn parse(source: &str) -> Result<Vec<XmlElement>, SyntaxError> {
+    let mut parser = Parser {
+        source: source,
+        offset: 0,
+        position: Position { line: 1, column: 1 },
+    };
+    let mut roots = Vec::new();
+    for node in try!(parser.nodes(None)) {
+        match node {
+            XmlNode::Element(element) => roots.push(element),
+            XmlNode::Text(ref text, position) if !text.trim().is_empty() => {
+                return Err(SyntaxError {
+                    position: position,
+                    message: "text outside of an element".to_string(),
+                });
+            }
+            XmlNode::Text(..) => (),
+        }
+    }
+    Ok(roots)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    offset: usize,
+    position: Position,
+}
+
+impl<'a> Parser<'a> {
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.offset..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.offset += c.len_utf8();
+            if c == '\n' {
+                self.position.line += 1;
+                self.position.column = 1;
+            } else {
+                self.position.column += 1;
+            }
+        }
+        c
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            for _ in s.chars() {
+                self.next();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<S: Into<String>>(&self, message: S) -> SyntaxError {
+        self.error_at(self.position, message)
+    }
+
+    fn error_at<S: Into<String>>(&self, position: Position, message: S) -> SyntaxError {
+        SyntaxError {
+            position: position,
+            message: message.into(),
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), SyntaxError> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", s)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    fn skip_until(&mut self, end: &str, what: &str, start: Position) -> Result<(), SyntaxError> {
+        while !self.eat(end) {
+            if self.next().is_none() {
+                return Err(self.error_at(start, format!("unclosed {}", what)));
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<String, SyntaxError> {
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            let valid = if name.is_empty() {
+                c.is_alphabetic() || c == '_'
+            } else {
+                c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == ':'
+            };
+            if !valid {
+                break;
+            }
+            name.push(c);
+            self.next();
+        }
+        if name.is_empty() {
+            Err(self.error("expected a name"))
+        } else {
+            Ok(name)
+        }
+    }
+
+    // Nodes until the closing tag of `parent`, or the end of the source.
+    fn nodes(&mut self, parent: Option<&str>) -> Result<Vec<XmlNode>, SyntaxError> {
+        let mut nodes = Vec::new();
+        loop {
+            let position = self.position;
+            if self.rest().is_empty() {
+                return match parent {
+                    Some(name) => Err(self.error(format!("missing `</{}>`", name))),
+                    None => Ok(nodes),
+                };
+            } else if self.eat("<!--") {
+                try!(self.skip_until("-->", "comment", position));
+            } else if self.eat("<?") {
+                try!(self.skip_until("?>", "processing instruction", position));
+            } else if self.eat("</") {
+                let name = try!(self.name());
+                self.skip_whitespace();
+                try!(self.expect(">"));
+                return match parent {
+                    Some(parent) if parent == name => Ok(nodes),
+                    Some(parent) => Err(self.error_at(position,
+                        format!("expected `</{}>`, found `</{}>`", parent, name))),
+                    None => Err(self.error_at(position, format!("unexpected `</{}>`", name))),
+                };
+            } else if self.peek() == Some('<') {
+                nodes.push(XmlNode::Element(try!(self.element())));
+            } else {
+                let text = try!(self.text('<'));
+                nodes.push(XmlNode::Text(text, position));
+            }
+        }
+    }
+
+    fn element(&mut self) -> Result<XmlElement, SyntaxError> {
+        let position = self.position;
+        try!(self.expect("<"));
+        let name = try!(self.name());
+        let mut attributes: Vec<(String, String)> = Vec::new();
+
+        loop {
+            let had_whitespace = self.peek().map_or(false, char::is_whitespace);
+            self.skip_whitespace();
+            if self.eat("/>") {
+                return Ok(XmlElement {
+                    name: name,
+                    attributes: attributes,
+                    children: Vec::new(),
+                    position: position,
+                });
+            }
+            if self.eat(">") {
+                let children = try!(self.nodes(Some(&name[..])));
+                return Ok(XmlElement {
+                    name: name,
+                    attributes: attributes,
+                    children: children,
+                    position: position,
+                });
+            }
+            if !had_whitespace {
+                return Err(self.error("expected whitespace, `>` or `/>`"));
+            }
+
+            let attribute_position = self.position;
+            let attribute = try!(self.name());
+            self.skip_whitespace();
+            try!(self.expect("="));
+            self.skip_whitespace();
+            let quote = match self.next() {
+                Some(quote) if quote == '"' || quote == '\'' => quote,
+                _ => return Err(self.error("expected a quoted value")),
+            };
+            let value = try!(self.text(quote));
+            if self.next() != Some(quote) {
+                return Err(self.error_at(attribute_position,
+                    format!("unclosed value of `{}`", attribute)));
+            }
+            if attributes.iter().any(|&(ref a, _)| *a == attribute) {
+                return Err(self.error_at(attribute_position,
+                    format!("duplicate attribute `{}`", attribute)));
+            }
+            attributes.push((attribute, value));
+        }
+    }
+
+    // Text until `end` or `<`, which are not consumed.
+    fn text(&mut self, end: char) -> Result<String, SyntaxError> {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if c == end || c == '<' {
+                break;
+            }
+            if c == '&' {
+                text.push(try!(self.entity()));
+            } else {
+                text.push(c);
+                self.next();
+            }
+        }
+        Ok(text)
+    }
+
+    fn entity(&mut self) -> Result<char, SyntaxError> {
+        let position = self.position;
+        let end = match self.rest().find(';') {
+            // Entities are short, don't look too far.
+            Some(end) if end <= 10 => end,
+            _ => return Err(self.error("unclosed entity, `&` must be written `&amp;`")),
+        };
+        let entity = &self.rest()[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(::std::char::from_u32)
+            }
+            _ if entity.starts_with('#') => {
+                entity[1..].parse().ok().and_then(::std::char::from_u32)
+            }
+            _ => None,
+        };
+        match c {
+            Some(c) => {
+                for _ in 0..end + 1 {
+                    self.next();
+                }
+                Ok(c)
+            }
+            None => Err(self.error_at(position, format!("unknown entity `&{};`", entity))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{parse, Position, XmlNode};
+
+    #[test]
+    fn elements_and_text() {
+        let roots = parse(r#"
+            <?xml version="1.0"?>
+            <!-- Two roots. -->
+            <view name="main" class='a b'>
+                Hello &amp; <b>welcome</b>&#33;
+            </view>
+            <template name="empty"/>
+        "#).unwrap();
+
+        assert_eq!(roots.len(), 2);
+        let view = &roots[0];
+        assert_eq!(view.name, "view");
+        assert_eq!(view.position, Position { line: 4, column: 13 });
+        assert_eq!(view.attributes, vec![("name".to_string(), "main".to_string()),
+                                         ("class".to_string(), "a b".to_string())]);
+        assert_eq!(view.children.len(), 3);
+        match view.children[0] {
+            XmlNode::Text(ref text, _) => assert_eq!(text.trim(), "Hello &"),
+            _ => panic!("expected text"),
+        }
+        match view.children[1] {
+            XmlNode::Element(ref b) => assert_eq!(b.children.len(), 1),
+            _ => panic!("expected an element"),
+        }
+        match view.children[2] {
+            XmlNode::Text(ref text, _) => assert_eq!(text.trim(), "!"),
+            _ => panic!("expected text"),
+        }
+        assert!(roots[1].children.is_empty());
+    }
+
+    #[test]
+    fn unicode_and_entities_in_attributes() {
+        let roots = parse("<a title=\"&lt;é&#x263A;&gt;\" other='\"'/>").unwrap();
+        assert_eq!(roots[0].attributes[0].1, "<é☺>");
+        assert_eq!(roots[0].attributes[1].1, "\"");
+    }
+
+    fn error(source: &str) -> (usize, usize, String) {
+        let e = parse(source).unwrap_err();
+        (e.position.line, e.position.column, e.message)
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(error("<a>\n  <b></c>\n</a>"),
+                   (2, 6, "expected `</b>`, found `</c>`".to_string()));
+        assert_eq!(error("<a>"), (1, 4, "missing `</a>`".to_string()));
+        assert_eq!(error("</a>"), (1, 1, "unexpected `</a>`".to_string()));
+        assert_eq!(error("<a b='1' b='2'/>"), (1, 10, "duplicate attribute `b`".to_string()));
+        assert_eq!(error("<a b='1'c='2'/>").2, "expected whitespace, `>` or `/>`");
+        assert_eq!(error("<a b=1/>").2, "expected a quoted value");
+        assert_eq!(error("<a>&nbsp;</a>").2, "unknown entity `&nbsp;`");
+        assert_eq!(error("<a>fish & chips</a>").2, "unclosed entity, `&` must be written `&amp;`");
+        assert_eq!(error("<a/>\n <!-- oops"), (2, 2, "unclosed comment".to_string()));
+        assert_eq!(error("hello <a/>"), (1, 1, "text outside of an element".to_string()));
+    }
+}
@@ -1,2 +1,5 @@
 
 pub const GAME_UNIT_TO_PX: f32 = 8.0;
+
+/// Size of a map tile, in game units.
+pub const TILE_SIZE: f32 = 2.0;
@@ -1,10 +1,14 @@
 extern crate sarosa_engine as sarosa;
+#[macro_use] extern crate log;
 extern crate log4rs;
 extern crate rustc_serialize;
 extern crate docopt;
 
 use docopt::Docopt;
-use std::ops::Deref;
+use sarosa::models::settings::{SettingsError, SETTINGS_PATH};
+use std::io;
+use std::io::Write;
+use std::process;
 
 // Version support
 include!(concat!(env!("OUT_DIR"), "/sarosa_version.rs"));
@@ -13,8 +17,9 @@ static USAGE: &'static str = "
 Sarosa client.
 
 Usage:
-  sarosa [--host <host> --port <port>]
-  sarosa --offline
+  sarosa [--config <file>] [--host <host>] [--port <port>] [--record <file>]
+  sarosa [--config <file>] --offline
+  sarosa [--config <file>] --replay <file> [--speed <factor>]
   sarosa (-h | --help)
   sarosa --version
 
@@ -22,18 +27,54 @@ Options:
   -h --help         Show this screen.
   --version         Show version.
   --offline         Run a self-hosted offline server.
-  --port <port>     Server port     [default: 7777].
-  --host <host>     Server Hostname [default: localhost].
+  --config <file>   Settings file, created on exit if missing.
+  --port <port>     Server port, overrides the settings file.
+  --host <host>     Server hostname or IP, overrides the settings file.
+  --record <file>   Record what the server says to a file.
+  --replay <file>   Play a recording instead of connecting to a server.
+  --speed <factor>  How many times faster the recording is played [default: 1].
 ";
 
 #[derive(Debug, RustcDecodable)]
 struct Args {
     flag_offline: bool,
-    flag_host: String,
-    flag_port: u16,
+    flag_config: Option<String>,
+    flag_host: Option<String>,
+    flag_port: Option<u16>,
+    flag_record: Option<String>,
+    flag_replay: Option<String>,
+    flag_speed: f32,
     flag_version: bool,
 }
 
+// Replace the parts of `address` given on the command line.
+// IPv6 hosts are written in brackets when followed by a port.
+fn override_address(address: &str, host: Option<String>, port: Option<u16>) -> String {
+    let (old_host, old_port) = split_address(address);
+    let host = host.as_ref().map_or(old_host, |h| h.trim_left_matches('[').trim_right_matches(']'));
+    let port = port.map_or(old_port.to_string(), |p| p.to_string());
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+// Split `address` into its host, without brackets, and its port.
+fn split_address(address: &str) -> (&str, &str) {
+    if address.starts_with('[') {
+        if let Some(end) = address.find(']') {
+            let port = &address[end + 1..];
+            return (&address[1..end], if port.starts_with(':') { &port[1..] } else { "7777" });
+        }
+    }
+    // More than one colon is an IPv6 address without port.
+    match address.rfind(':') {
+        Some(i) if address.find(':') == Some(i) => (&address[..i], &address[i + 1..]),
+        _ => (address, "7777"),
+    }
+}
+
 fn main() {
     let args: Args = Docopt::new(USAGE)
                             .and_then(|d| d.decode())
@@ -46,19 +87,77 @@ fn main() {
 
     log4rs::init_file("log.toml", Default::default()).unwrap();
 
-    let mut address = args.flag_host;
-    address.push_str(":"); address.push_str(args.flag_port.to_string().deref());
+    // Settings: defaults < settings file < command line
+    let config = args.flag_config.unwrap_or(SETTINGS_PATH.to_string());
+    let (mut settings, save_settings) = match sarosa::Settings::load(&config) {
+        Ok(settings) => (settings, true),
+        Err(SettingsError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => {
+            (sarosa::Settings::default(), true)
+        }
+        Err(e) => {
+            // Don't overwrite a file the user may want to fix.
+            warn!("Invalid settings file `{}`, using the defaults: {}", config, e);
+            (sarosa::Settings::default(), false)
+        }
+    };
+    // Go straight to the game when the command line tells where.
+    let skip_menu = args.flag_offline || args.flag_replay.is_some() ||
+                    args.flag_host.is_some() || args.flag_port.is_some();
+    if args.flag_host.is_some() || args.flag_port.is_some() {
+        let address = override_address(settings.network().addr(), args.flag_host, args.flag_port);
+        settings.network_mut().set_addr(address);
+    }
+    settings.network_mut().set_offline_server(args.flag_offline);
+    settings.network_mut().set_record_path(args.flag_record);
+    settings.network_mut().set_replay_path(args.flag_replay);
+    // Also rejects NaN, which compares false to anything.
+    if !(args.flag_speed > 0.0) || !args.flag_speed.is_finite() {
+        let _ = writeln!(io::stderr(), "The replay speed must be a positive number, not {}", args.flag_speed);
+        process::exit(1);
+    }
+    settings.network_mut().set_replay_speed(args.flag_speed);
 
     // Initialization
-    let settings = sarosa::Settings::new(address, args.flag_offline);
-    let mut server = sarosa::Server::new(settings.clone());
-    let mut win = sarosa::Window::new(settings.clone(), "Sarosa - Renaissance Project");
-    let mut instance = sarosa::GameInstance::new(&win, settings.clone());
-    let mut game = sarosa::GameLoop::new();
+    let server = sarosa::Server::new(settings.clone());
+    let win = sarosa::Window::new(settings.clone(), "Sarosa - Renaissance Project");
+    let mut game = sarosa::GameLoop::new(win, server, settings.clone());
 
-    // Try to connect to the server
-    server.connect();
+    // Run the game, from the main menu unless the server is known.
+    let first: Box<sarosa::GameState> = if skip_menu {
+        Box::new(sarosa::Connecting::new())
+    } else {
+        Box::new(sarosa::MainMenu::new())
+    };
+    game.run_loop(first);
 
-    // Run the game.
-    game.run_loop(&mut win, &mut instance, &mut server);
+    if save_settings {
+        if let Err(e) = settings.save(&config) {
+            error!("Couldn't save the settings to `{}`: {}", config, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::override_address;
+
+    #[test]
+    fn override_ipv4() {
+        assert_eq!(override_address("example.org:7000", None, Some(8000)), "example.org:8000");
+        assert_eq!(override_address("example.org:7000", Some("127.0.0.1".to_string()), None),
+                   "127.0.0.1:7000");
+        assert_eq!(override_address("example.org", None, None), "example.org:7777");
+    }
+
+    #[test]
+    fn override_ipv6() {
+        assert_eq!(override_address("[::1]:7000", None, Some(8000)), "[::1]:8000");
+        assert_eq!(override_address("::1", None, None), "[::1]:7777");
+        assert_eq!(override_address("example.org:7000", Some("::1".to_string()), None), "[::1]:7000");
+        assert_eq!(override_address("[::1]:7000", Some("[fe80::1]".to_string()), None),
+                   "[fe80::1]:7000");
+        assert_eq!(override_address("[::1]:7000", Some("localhost".to_string()), None),
+                   "localhost:7000");
+    }
 }